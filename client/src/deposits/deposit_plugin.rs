@@ -0,0 +1,81 @@
+use bevy::{
+    app::{Plugin, Update},
+    asset::Handle,
+    ecs::{
+        entity::Entity,
+        event::EventReader,
+        query::Changed,
+        system::{Commands, Query, Res},
+    },
+    log::{info, warn},
+    prelude::{default, Color, Vec2},
+    render::texture::Image,
+    sprite::Sprite,
+};
+use common::{
+    components::DepositState,
+    network::events::{Mined, MiningFailed},
+};
+
+use crate::network::{EntityMapper, ReceiveFromServer};
+
+const MIN_DEPOSIT_SIZE: f32 = 15.0;
+const MAX_DEPOSIT_SIZE: f32 = 60.0;
+
+pub struct DepositPlugin;
+
+impl Plugin for DepositPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(Update, (update_deposit_sprites, report_mining));
+    }
+}
+
+// Deposits shrink as they're mined out
+fn update_deposit_sprites(
+    mut commands: Commands,
+    deposits: Query<(Entity, &DepositState), Changed<DepositState>>,
+) {
+    for (entity, deposit) in &deposits {
+        let fraction = deposit.remaining.as_grams() as f32 / deposit.total.as_grams().max(1) as f32;
+        let size = MIN_DEPOSIT_SIZE + (MAX_DEPOSIT_SIZE - MIN_DEPOSIT_SIZE) * fraction;
+
+        commands.entity(entity).insert((
+            Sprite {
+                color: Color::rgba(0.55, 0.35, 0.2, 1.0),
+                custom_size: Some(Vec2 { x: size, y: size }),
+                ..default()
+            },
+            Handle::<Image>::default(),
+        ));
+    }
+}
+
+fn report_mining(
+    mut mined_events: EventReader<ReceiveFromServer<Mined>>,
+    mut mining_failed_events: EventReader<ReceiveFromServer<MiningFailed>>,
+    deposits: Query<&DepositState>,
+    mapper: Res<EntityMapper>,
+) {
+    for event in mined_events.read() {
+        let deposit = mapper
+            .0
+            .get(&event.message.deposit)
+            .and_then(|local_entity| deposits.get(*local_entity).ok());
+        match deposit {
+            Some(deposit) => info!(
+                "Mined {} of {} ({} held), deposit has {} of {} left (composition {:?})",
+                event.message.extracted,
+                deposit.material,
+                event.message.held,
+                deposit.remaining,
+                deposit.total,
+                deposit.ratios
+            ),
+            None => info!("{}", event.message),
+        }
+    }
+
+    for event in mining_failed_events.read() {
+        warn!("{}", event.message);
+    }
+}
@@ -0,0 +1,3 @@
+mod deposit_plugin;
+
+pub use deposit_plugin::DepositPlugin;
@@ -4,7 +4,7 @@ use bevy::{
         schedule::{common_conditions::in_state, IntoSystemConfigs},
         system::{Res, ResMut},
     },
-    input::{keyboard::KeyCode, Input}, prelude::default,
+    input::{keyboard::KeyCode, mouse::MouseButton, Input}, prelude::default,
 };
 
 use common::network::events::PlayerInput;
@@ -23,12 +23,18 @@ impl Plugin for PlayerControlPlugin {
     }
 }
 
-fn handle_player_input(keyboard: Res<Input<KeyCode>>, mut input: ResMut<PlayerInput>) {
+fn handle_player_input(
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    mut input: ResMut<PlayerInput>,
+) {
     let new_input = PlayerInput {
         left: keyboard.pressed(KeyCode::A) as u8,
         right: keyboard.pressed(KeyCode::D) as u8,
         forward: keyboard.pressed(KeyCode::W) as u8,
         backward: keyboard.pressed(KeyCode::S) as u8,
+        primary: mouse.pressed(MouseButton::Left) as u8,
+        secondary: mouse.pressed(MouseButton::Right) as u8,
         ..default()
     };
 
@@ -44,4 +50,10 @@ fn handle_player_input(keyboard: Res<Input<KeyCode>>, mut input: ResMut<PlayerIn
     if input.backward != new_input.backward {
         input.backward = new_input.backward;
     }
+    if input.primary != new_input.primary {
+        input.primary = new_input.primary;
+    }
+    if input.secondary != new_input.secondary {
+        input.secondary = new_input.secondary;
+    }
 }
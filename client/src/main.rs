@@ -1,27 +1,43 @@
 extern crate common;
 
+mod deposits;
 mod input;
+mod market;
 mod network;
+mod worlds;
+
+use std::{io, path::Path};
 
-use bevy::input::keyboard::KeyboardInput;
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
-use bevy::render::camera;
 use bevy_asset_loader::loading_state::LoadingState;
 use bevy_asset_loader::loading_state::LoadingStateAppExt;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
-use bevy_renet::client_connected;
+use common::components::Appearance;
 use common::loaders::KdlAsset;
 use common::loaders::KdlLoader;
-use common::network::events::CreateEntity;
 use common::network::events::GetPlayerEntity;
+use common::network::events::PlayerEntityFailure;
 use common::network::events::GetWorldState;
-use common::network::events::PlayerInput;
+use common::network::login::{is_valid_account_name, load_or_create_secret};
+use common::network::rpc::RequestError;
+use common::network::settings::NetworkSettings;
 use network::EntityMapper;
-use network::ReceiveFromServer;
+use network::InterpolationPlugin;
+use network::MessageRecorder;
+use network::NetworkDiagnosticsPlugin;
+use network::Playback;
+use network::Predicted;
+use network::PredictionPlugin;
+use network::RequestFailed;
+use network::Requester;
+use network::ResponseReceived;
 use network::SendToServer;
 
+use crate::deposits::DepositPlugin;
 use crate::input::PlayerControlPlugin;
+use crate::market::MarketPlugin;
+use crate::worlds::{CurrentWorld, WorldPlugin};
 
 #[derive(Resource, Default)]
 struct PlayerEntity(Option<Entity>);
@@ -36,21 +52,71 @@ pub enum GameState {
     LoadConfigs,
     Loading,
     Gameplay,
-}
-
-pub fn advance_state(mut state: ResMut<NextState<GameState>>) {
-    state.set(GameState::Gameplay);
+    // There's no connection, either yet or since losing it, and the client is logging in for one
+    Reconnecting,
+    // The server turned the client down, which a new connection wouldn't change
+    Rejected,
 }
 
 fn main() {
     info!("Client v{} using common v{}", version(), common::version());
 
+    let args: Vec<String> = std::env::args().collect();
+    let settings = match NetworkSettings::from_args(&args) {
+        Ok(settings) => settings,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    let account = account_name(&args);
+    let secret = match account_secret(&args, &account) {
+        Ok(secret) => secret,
+        Err(error) => {
+            eprintln!("Failed to read or create the secret for {} ({})", account, error);
+            std::process::exit(1);
+        }
+    };
+
     let mut app = App::new();
     app.add_plugins(DefaultPlugins.build().set::<LogPlugin>(LogPlugin {
         level: bevy::log::Level::DEBUG,
         filter: "client=debug,error".into()
     }))
-    .add_plugins((common::materials::MaterialsPlugin, network::NetworkPlugin))
+    .insert_resource(Time::<Fixed>::from_seconds(settings.tick_seconds()))
+    .insert_resource(settings);
+
+    // --replay shows a recorded session instead of connecting, --record keeps one to show later
+    if let Some(path) = flag_value(&args, "--replay") {
+        match Playback::load(Path::new(path)) {
+            Ok((_, playback)) => {
+                app.insert_resource(playback);
+            }
+            Err(error) => {
+                eprintln!("Failed to read {} ({})", path, error);
+                std::process::exit(1);
+            }
+        }
+    } else if let Some(path) = flag_value(&args, "--record") {
+        match MessageRecorder::create(Path::new(path), &account) {
+            Ok(recorder) => {
+                app.insert_resource(recorder);
+            }
+            Err(error) => {
+                eprintln!("Failed to start recording to {} ({})", path, error);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    app.add_plugins((
+        common::materials::MaterialsPlugin,
+        network::NetworkPlugin { account, secret },
+        InterpolationPlugin,
+        PredictionPlugin,
+        NetworkDiagnosticsPlugin,
+    ))
     .add_plugins(WorldInspectorPlugin::new())
     .add_state::<GameState>()
     .init_asset::<KdlAsset>()
@@ -62,18 +128,13 @@ fn main() {
         GameState::LoadConfigs,
     )
     .add_systems(Startup, setup)
-    .add_systems(Update, advance_state.run_if(in_state(GameState::Loading)).run_if(client_connected()))
-    .add_plugins(PlayerControlPlugin)
+    .add_plugins((PlayerControlPlugin, MarketPlugin, DepositPlugin, WorldPlugin))
     .insert_resource(PlayerEntity::default());
 
-    app.add_systems(Update, create_entity_system)
-        .add_systems(
-            PreUpdate,
-            send_user_input.run_if(resource_changed::<PlayerInput>()),
-        )
+    app.add_systems(Update, apply_appearance)
         .add_systems(
             Update,
-            set_player_entity.run_if(in_state(GameState::Gameplay)),
+            (set_player_entity, retry_player_entity).run_if(in_state(GameState::Gameplay)),
         )
         .add_systems(
             PostUpdate,
@@ -88,6 +149,28 @@ fn main() {
     app.run();
 }
 
+// Taken from --account, falling back to the name of the user running the client
+fn account_name(args: &[String]) -> String {
+    args.iter()
+        .position(|arg| arg == "--account")
+        .and_then(|index| args.get(index + 1).cloned())
+        .or_else(|| std::env::var("USER").ok().filter(|user| is_valid_account_name(user)))
+        .unwrap_or_else(|| String::from("player"))
+}
+
+// Taken from --secret, falling back to one kept in the working directory for the account
+fn account_secret(args: &[String], account: &str) -> io::Result<String> {
+    match flag_value(args, "--secret") {
+        Some(secret) => Ok(secret.to_string()),
+        None => load_or_create_secret(Path::new(&format!("{}.secret", account))),
+    }
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let index = args.iter().position(|arg| arg == flag)?;
+    args.get(index + 1).map(|arg| arg.as_str())
+}
+
 fn version() -> &'static str {
     env!("CARGO_PKG_VERSION")
 }
@@ -98,67 +181,105 @@ fn print_player_entity(player_entity_resource: Res<PlayerEntity>, keyboard: Res<
     }
 }
 
-fn get_world_state(mut get_world_state_event_writer: EventWriter<SendToServer<GetWorldState>>) {
+fn get_world_state(
+    mut get_world_state_event_writer: EventWriter<SendToServer<GetWorldState>>,
+    current_world: Res<CurrentWorld>,
+) {
     get_world_state_event_writer.send(SendToServer {
-        message: GetWorldState { world: 0 },
+        message: GetWorldState {
+            world: current_world.0,
+        },
     });
 }
 
 fn get_player_entity(
-    mut get_player_entity_event_writer: EventWriter<SendToServer<GetPlayerEntity>>,
+    mut requester: Requester<GetPlayerEntity>,
+    current_world: Res<CurrentWorld>,
 ) {
-    get_player_entity_event_writer.send(SendToServer {
-        message: GetPlayerEntity { world: 0 },
+    requester.send(GetPlayerEntity {
+        request: 0,
+        world: current_world.0,
     });
 }
 
+// Only a lost connection stops the server answering, but should an answer still not arrive in time
+// the player entity is asked for again
+fn retry_player_entity(
+    mut failed_event_reader: EventReader<RequestFailed<GetPlayerEntity>>,
+    mut requester: Requester<GetPlayerEntity>,
+    current_world: Res<CurrentWorld>,
+) {
+    for event in failed_event_reader.read() {
+        if event.error == RequestError::TimedOut {
+            warn!(
+                "Request {} for the player entity failed as {}, asking again",
+                event.request, event.error
+            );
+            requester.send(GetPlayerEntity {
+                request: 0,
+                world: current_world.0,
+            });
+        }
+    }
+}
+
+// The player's entity can be named by the server before its replicated spawn has arrived, so the
+// remote entity is kept and mapped every frame. This also clears it once the entity is despawned.
+// The server knows best which world the player is in, so if it's in another one the client
+// follows it there
 fn set_player_entity(
-    mut player_entity_event_reader: EventReader<
-        ReceiveFromServer<common::network::events::PlayerEntity>,
-    >,
+    mut player_entity_event_reader: EventReader<ResponseReceived<GetPlayerEntity>>,
+    mut requester: Requester<GetPlayerEntity>,
+    mut current_world: ResMut<CurrentWorld>,
+    mut remote_player_entity: Local<Option<Entity>>,
     mut player_entity_resource: ResMut<PlayerEntity>,
+    mut commands: Commands,
     mapper: Res<EntityMapper>,
 ) {
     for event in player_entity_event_reader.read() {
-        info!("Got player entity {:?}", event.message.entity);
-        player_entity_resource.0 = mapper.0.get(&event.message.entity).copied();
+        match event.response.entity {
+            Ok(entity) => {
+                info!("Got player entity {:?} for request {}", entity, event.request);
+                *remote_player_entity = Some(entity);
+            }
+            Err(PlayerEntityFailure::InOtherWorld(world)) => {
+                info!("Request {} found the player in world {}", event.request, world);
+                current_world.0 = world;
+                requester.send(GetPlayerEntity { request: 0, world });
+            }
+            Err(PlayerEntityFailure::NoEntity) => {
+                warn!("The server has no entity for the player (request {})", event.request);
+            }
+        }
+    }
+
+    let local_entity = remote_player_entity.and_then(|remote| mapper.0.get(&remote).copied());
+    if player_entity_resource.0 != local_entity {
+        player_entity_resource.0 = local_entity;
+        if let Some(local_entity) = local_entity {
+            commands.entity(local_entity).insert(Predicted);
+        }
     }
 }
 
-fn create_entity_system(
-    mut create_entity_event_reader: EventReader<ReceiveFromServer<CreateEntity>>,
+fn apply_appearance(
     mut commands: Commands,
-    mut mapper: ResMut<EntityMapper>,
+    appearances: Query<(Entity, &Appearance), Changed<Appearance>>,
 ) {
-    for create_entity_event in create_entity_event_reader.read() {
-        info!("Creating entity {:?}", create_entity_event.message.entity);
-        let new_entity = commands
-            .spawn(SpriteBundle {
-                sprite: Sprite {
-                    color: Color::rgba(0.1, 0.3, 0.7, 1.0),
-                    custom_size: Some(Vec2 { x: 25.0, y: 25.0 }),
-                    ..default()
-                },
-                transform: Transform::IDENTITY,
+    for (entity, appearance) in &appearances {
+        let [red, green, blue, alpha] = appearance.colour;
+        let [width, height] = appearance.size;
+        commands.entity(entity).insert((
+            Sprite {
+                color: Color::rgba(red, green, blue, alpha),
+                custom_size: Some(Vec2 { x: width, y: height }),
                 ..default()
-            })
-            .id();
-
-        mapper
-            .0
-            .insert(create_entity_event.message.entity, new_entity);
+            },
+            Handle::<Image>::default(),
+        ));
     }
 }
 
-fn send_user_input(
-    input: Res<PlayerInput>,
-    mut input_event_writer: EventWriter<SendToServer<PlayerInput>>,
-) {
-    input_event_writer.send(SendToServer {
-        message: input.clone(),
-    });
-}
-
 fn track_camera(
     player_entity_resource: Res<PlayerEntity>,
     mut camera_transforms: Query<&mut Transform, With<Camera2d>>,
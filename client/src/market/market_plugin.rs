@@ -0,0 +1,93 @@
+use bevy::{
+    app::{Plugin, Update},
+    ecs::{
+        event::EventReader,
+        system::{ResMut, Resource},
+    },
+    log::{info, warn},
+    utils::HashMap,
+};
+use common::{
+    market::{Candle, CandleResolution, OrderID},
+    materials::MaterialID,
+    network::events::{
+        OrderCancelled, OrderFilled, OrderPlaced, OrderRejected, PriceHistory, TopOfBook,
+    },
+};
+
+use crate::network::ReceiveFromServer;
+
+use super::price_chart::PriceChartPlugin;
+
+pub struct MarketPlugin;
+
+impl Plugin for MarketPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_plugins(PriceChartPlugin)
+            .init_resource::<MarketQuotes>()
+            .init_resource::<OpenOrders>()
+            .init_resource::<PriceCharts>()
+            .add_systems(Update, (update_quotes, update_open_orders, update_price_charts));
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct MarketQuotes(pub HashMap<MaterialID, TopOfBook>);
+
+#[derive(Resource, Default)]
+pub struct OpenOrders(pub HashMap<OrderID, OrderPlaced>);
+
+// Candles last received for each chart, replaced whenever the server answers a GetPriceHistory
+#[derive(Resource, Default)]
+pub struct PriceCharts(pub HashMap<(MaterialID, CandleResolution), Vec<Candle>>);
+
+fn update_quotes(
+    mut top_of_book_events: EventReader<ReceiveFromServer<TopOfBook>>,
+    mut quotes: ResMut<MarketQuotes>,
+) {
+    for event in top_of_book_events.read() {
+        quotes.0.insert(event.message.material, event.message);
+    }
+}
+
+fn update_open_orders(
+    mut order_placed_events: EventReader<ReceiveFromServer<OrderPlaced>>,
+    mut order_filled_events: EventReader<ReceiveFromServer<OrderFilled>>,
+    mut order_cancelled_events: EventReader<ReceiveFromServer<OrderCancelled>>,
+    mut order_rejected_events: EventReader<ReceiveFromServer<OrderRejected>>,
+    mut open_orders: ResMut<OpenOrders>,
+) {
+    for event in order_placed_events.read() {
+        open_orders.0.insert(event.message.order, event.message);
+    }
+
+    for event in order_filled_events.read() {
+        info!("{}", event.message);
+        if let Some(order) = open_orders.0.get_mut(&event.message.order) {
+            order.quantity = event.message.remaining;
+        }
+        if event.message.remaining == Default::default() {
+            open_orders.0.remove(&event.message.order);
+        }
+    }
+
+    for event in order_cancelled_events.read() {
+        open_orders.0.remove(&event.message.order);
+    }
+
+    for event in order_rejected_events.read() {
+        warn!("{}", event.message);
+    }
+}
+
+fn update_price_charts(
+    mut price_history_events: EventReader<ReceiveFromServer<PriceHistory>>,
+    mut charts: ResMut<PriceCharts>,
+) {
+    for event in price_history_events.read() {
+        charts.0.insert(
+            (event.message.material, event.message.resolution),
+            event.message.candles.clone(),
+        );
+    }
+}
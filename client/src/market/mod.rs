@@ -0,0 +1,4 @@
+mod market_plugin;
+mod price_chart;
+
+pub use market_plugin::MarketPlugin;
@@ -0,0 +1,188 @@
+use std::fmt::Write;
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    ecs::{
+        component::Component,
+        event::EventReader,
+        query::With,
+        schedule::{common_conditions::in_state, IntoSystemConfigs},
+        system::{Commands, Local, Query, Res},
+    },
+    input::{keyboard::KeyCode, Input},
+    log::warn,
+    render::{color::Color, view::Visibility},
+    text::{Text, TextStyle},
+    time::Time,
+    ui::{node_bundles::TextBundle, PositionType, Style, Val},
+    utils::default,
+};
+use common::{
+    market::{Candle, CandleResolution},
+    network::events::GetPriceHistory,
+};
+
+use crate::{
+    network::{RequestFailed, Requester},
+    GameState,
+};
+
+use super::market_plugin::{MarketQuotes, PriceCharts};
+
+const CHART_RESOLUTION: CandleResolution = CandleResolution::OneMinute;
+const CHART_CANDLES: u32 = 15;
+// How often the history is asked for again while the chart is showing
+const CHART_REFRESH_SECONDS: f64 = 10.0;
+// Characters in the bar for the highest close on a chart
+const BAR_WIDTH: u64 = 30;
+
+// A text chart of recent candles for every material with a quote, toggled with F4
+pub struct PriceChartPlugin;
+
+impl Plugin for PriceChartPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_chart).add_systems(
+            Update,
+            (
+                toggle_chart,
+                request_price_history.run_if(in_state(GameState::Gameplay)),
+                report_failed_requests,
+                update_chart,
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Component)]
+struct PriceChart;
+
+fn spawn_chart(mut commands: Commands) {
+    let text_style = TextStyle {
+        font_size: 14.0,
+        color: Color::WHITE,
+        ..default()
+    };
+    let mut chart = TextBundle::from_section("", text_style).with_style(Style {
+        position_type: PositionType::Absolute,
+        top: Val::Px(8.0),
+        right: Val::Px(8.0),
+        ..default()
+    });
+    chart.visibility = Visibility::Hidden;
+    commands.spawn((chart, PriceChart));
+}
+
+fn toggle_chart(
+    keyboard: Res<Input<KeyCode>>,
+    mut charts: Query<&mut Visibility, With<PriceChart>>,
+) {
+    if !keyboard.just_pressed(KeyCode::F4) {
+        return;
+    }
+    for mut visibility in &mut charts {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+// Asked for as soon as the chart is shown and then every so often until it's hidden again
+fn request_price_history(
+    time: Res<Time>,
+    quotes: Res<MarketQuotes>,
+    charts: Query<&Visibility, With<PriceChart>>,
+    mut requester: Requester<GetPriceHistory>,
+    mut last_requested: Local<Option<f64>>,
+) {
+    if charts
+        .iter()
+        .all(|visibility| *visibility == Visibility::Hidden)
+    {
+        *last_requested = None;
+        return;
+    }
+    let now = time.elapsed_seconds_f64();
+    if last_requested.is_some_and(|last| now - last < CHART_REFRESH_SECONDS) {
+        return;
+    }
+
+    *last_requested = Some(now);
+    for material in quotes.0.keys() {
+        requester.send(GetPriceHistory {
+            request: 0,
+            material: *material,
+            resolution: CHART_RESOLUTION,
+            count: CHART_CANDLES,
+        });
+    }
+}
+
+fn report_failed_requests(mut failed_events: EventReader<RequestFailed<GetPriceHistory>>) {
+    for event in failed_events.read() {
+        warn!(
+            "No price history for request {} ({})",
+            event.request, event.error
+        );
+    }
+}
+
+fn update_chart(
+    quotes: Res<MarketQuotes>,
+    price_charts: Res<PriceCharts>,
+    mut charts: Query<(&mut Text, &Visibility), With<PriceChart>>,
+) {
+    for (mut text, visibility) in &mut charts {
+        if *visibility == Visibility::Hidden {
+            continue;
+        }
+        text.sections[0].value = chart_text(&quotes, &price_charts);
+    }
+}
+
+fn chart_text(quotes: &MarketQuotes, price_charts: &PriceCharts) -> String {
+    let mut materials: Vec<_> = quotes.0.keys().copied().collect();
+    materials.sort();
+
+    let mut text = String::new();
+    for material in materials {
+        let _ = writeln!(text, "{} ({})", material, CHART_RESOLUTION);
+        match price_charts.0.get(&(material, CHART_RESOLUTION)) {
+            Some(candles) if !candles.is_empty() => text.push_str(&candle_rows(candles)),
+            _ => text.push_str("No trades yet\n"),
+        }
+    }
+    if text.is_empty() {
+        text.push_str("No quotes yet\n");
+    }
+    text
+}
+
+// One row per candle with a bar for its close, scaled to the highest close shown
+fn candle_rows(candles: &[Candle]) -> String {
+    let highest = candles
+        .iter()
+        .map(|candle| candle.close.as_credits_per_kilogram().value().max(1) as u64)
+        .max()
+        .unwrap_or(1);
+
+    let mut rows = String::new();
+    for candle in candles {
+        let close = candle.close.as_credits_per_kilogram().value().max(0) as u64;
+        let bar = "#".repeat((close * BAR_WIDTH / highest) as usize);
+        let _ = writeln!(
+            rows,
+            "{:>6} {:<width$} {} / {} / {} / {} {}",
+            candle.start,
+            bar,
+            candle.open,
+            candle.high,
+            candle.low,
+            candle.close,
+            candle.volume,
+            width = BAR_WIDTH as usize
+        );
+    }
+    rows
+}
@@ -0,0 +1,170 @@
+use std::fmt::Write;
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics, RegisterDiagnostic},
+    ecs::{
+        component::Component,
+        query::With,
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut, Resource},
+    },
+    input::{keyboard::KeyCode, Input},
+    render::{color::Color, view::Visibility},
+    text::{Text, TextStyle},
+    ui::{node_bundles::TextBundle, PositionType, Style, Val},
+    utils::default,
+};
+use bevy_renet::{
+    client_connected,
+    renet::{NetworkInfo, RenetClient},
+};
+use common::network::{
+    channels::MessageChannel,
+    metrics::{queued_bytes, MessageTraffic},
+};
+
+pub const RTT: DiagnosticId = DiagnosticId::from_u128(0x6e65_7477_6f72_6b00_0000_0000_0000_0001);
+pub const PACKET_LOSS: DiagnosticId =
+    DiagnosticId::from_u128(0x6e65_7477_6f72_6b00_0000_0000_0000_0002);
+pub const BYTES_SENT: DiagnosticId =
+    DiagnosticId::from_u128(0x6e65_7477_6f72_6b00_0000_0000_0000_0003);
+pub const BYTES_RECEIVED: DiagnosticId =
+    DiagnosticId::from_u128(0x6e65_7477_6f72_6b00_0000_0000_0000_0004);
+
+const HISTORY_LENGTH: usize = 120;
+// Message types listed in the overlay
+const OVERLAY_MESSAGE_TYPES: usize = 6;
+
+pub struct NetworkDiagnosticsPlugin;
+
+impl Plugin for NetworkDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(
+            Diagnostic::new(RTT, "network_rtt", HISTORY_LENGTH).with_suffix("ms"),
+        )
+        .register_diagnostic(
+            Diagnostic::new(PACKET_LOSS, "network_packet_loss", HISTORY_LENGTH)
+                .with_suffix("%"),
+        )
+        .register_diagnostic(
+            Diagnostic::new(BYTES_SENT, "network_bytes_sent", HISTORY_LENGTH)
+                .with_suffix("B/s"),
+        )
+        .register_diagnostic(
+            Diagnostic::new(BYTES_RECEIVED, "network_bytes_received", HISTORY_LENGTH)
+                .with_suffix("B/s"),
+        )
+        .add_systems(Startup, spawn_overlay)
+        .add_systems(
+            Update,
+            (
+                update_network_stats.run_if(client_connected()),
+                toggle_overlay,
+                update_overlay,
+            )
+                .chain(),
+        );
+    }
+}
+
+// Everything known about how the connection to the server is doing. The traffic is counted as
+// messages are sent and received, the rest is read from renet each frame
+#[derive(Resource, Default)]
+pub struct NetworkStats {
+    pub traffic: MessageTraffic,
+    pub info: Option<NetworkInfo>,
+    pub queued: [usize; 4],
+}
+
+fn update_network_stats(
+    client: Res<RenetClient>,
+    mut stats: ResMut<NetworkStats>,
+    mut diagnostics: Diagnostics,
+) {
+    // renet measures round trips in seconds
+    let info = client.network_info();
+    diagnostics.add_measurement(RTT, || info.rtt * 1000.0);
+    diagnostics.add_measurement(PACKET_LOSS, || info.packet_loss * 100.0);
+    diagnostics.add_measurement(BYTES_SENT, || info.bytes_sent_per_second);
+    diagnostics.add_measurement(BYTES_RECEIVED, || info.bytes_received_per_second);
+
+    stats.info = Some(info);
+    stats.queued = queued_bytes(|channel| client.channel_available_memory(channel));
+}
+
+#[derive(Component)]
+struct NetworkOverlay;
+
+fn spawn_overlay(mut commands: Commands) {
+    let text_style = TextStyle {
+        font_size: 14.0,
+        color: Color::WHITE,
+        ..default()
+    };
+    let mut overlay = TextBundle::from_section("", text_style).with_style(Style {
+        position_type: PositionType::Absolute,
+        top: Val::Px(8.0),
+        left: Val::Px(8.0),
+        ..default()
+    });
+    overlay.visibility = Visibility::Hidden;
+    commands.spawn((overlay, NetworkOverlay));
+}
+
+fn toggle_overlay(
+    keyboard: Res<Input<KeyCode>>,
+    mut overlays: Query<&mut Visibility, With<NetworkOverlay>>,
+) {
+    if !keyboard.just_pressed(KeyCode::F3) {
+        return;
+    }
+    for mut visibility in &mut overlays {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+fn update_overlay(
+    stats: Res<NetworkStats>,
+    mut overlays: Query<(&mut Text, &Visibility), With<NetworkOverlay>>,
+) {
+    for (mut text, visibility) in &mut overlays {
+        if *visibility == Visibility::Hidden {
+            continue;
+        }
+        text.sections[0].value = overlay_text(&stats);
+    }
+}
+
+fn overlay_text(stats: &NetworkStats) -> String {
+    let mut text = String::new();
+    match &stats.info {
+        Some(info) => {
+            let _ = writeln!(
+                text,
+                "RTT {:.0}ms, loss {:.1}%, up {:.1}KB/s, down {:.1}KB/s",
+                info.rtt * 1000.0,
+                info.packet_loss * 100.0,
+                info.bytes_sent_per_second / 1024.0,
+                info.bytes_received_per_second / 1024.0
+            );
+        }
+        None => text.push_str("Not connected\n"),
+    }
+
+    for (channel, queued) in MessageChannel::ALL.iter().zip(stats.queued) {
+        let _ = writeln!(text, "{:?} queue {}B", channel, queued);
+    }
+
+    for (name, sent, received) in stats.traffic.busiest(OVERLAY_MESSAGE_TYPES) {
+        let _ = writeln!(
+            text,
+            "{}: sent {} ({}B), received {} ({}B)",
+            name, sent.messages, sent.bytes, received.messages, received.bytes
+        );
+    }
+    text
+}
@@ -0,0 +1,73 @@
+use bevy::{
+    ecs::{
+        event::{EventReader, EventWriter},
+        schedule::NextState,
+        system::{Local, Res, ResMut},
+    },
+    log::{error, info},
+    time::{Fixed, Time},
+};
+use bevy_renet::renet::RenetClient;
+use common::{
+    materials::MaterialManager,
+    network::{
+        events::{Handshake, HandshakeAccepted, HandshakeRejected},
+        handshake::{Version, PROTOCOL_VERSION},
+    },
+};
+
+use crate::GameState;
+
+use super::{ReceiveFromServer, SendToServer};
+
+// Sent once the game data has loaded, as the server checks it's the same as its own. Each new
+// connection needs its own handshake
+pub fn send_handshake(
+    client: Res<RenetClient>,
+    material_manager: Res<MaterialManager>,
+    mut handshake_event_writer: EventWriter<SendToServer<Handshake>>,
+    mut sent: Local<bool>,
+) {
+    if !client.is_connected() {
+        *sent = false;
+        return;
+    }
+    let Some(content_hash) = material_manager.content_hash() else {
+        return;
+    };
+    if *sent {
+        return;
+    }
+
+    let common_version = Version::common();
+    handshake_event_writer.send(SendToServer {
+        message: Handshake {
+            client_version: Version::parse(crate::version()).unwrap_or(common_version),
+            common_version,
+            protocol_version: PROTOCOL_VERSION,
+            content_hash,
+        },
+    });
+    *sent = true;
+}
+
+// The game only starts once the server has agreed to play, and it's the server that decides how
+// long a tick is
+pub fn receive_handshake_reply(
+    mut accepted_event_reader: EventReader<ReceiveFromServer<HandshakeAccepted>>,
+    mut rejected_event_reader: EventReader<ReceiveFromServer<HandshakeRejected>>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+    mut state: ResMut<NextState<GameState>>,
+) {
+    for event in accepted_event_reader.read() {
+        info!("{}", event.message);
+        fixed_time.set_timestep_seconds(1.0 / event.message.tick_rate);
+        state.set(GameState::Gameplay);
+    }
+
+    // Trying again would only be rejected again
+    for event in rejected_event_reader.read() {
+        error!("Couldn't join the server: {}", event.message);
+        state.set(GameState::Rejected);
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{EventReader, EventWriter},
+        query::Without,
+        schedule::IntoSystemConfigs,
+        system::{Query, Res, ResMut, Resource},
+    },
+    log::debug,
+    math::Vec3,
+    time::{Fixed, Time},
+    transform::components::Transform,
+    utils::HashMap,
+};
+use common::network::{
+    configuration::Tick,
+    events::{EntitySnapshot, Snapshot, SnapshotAcknowledged},
+    snapshot::{SnapshotHistory, SnapshotState},
+};
+
+use super::{EntityMapper, Predicted, ReceiveFromServer, SendToServer};
+
+const MAX_SNAPSHOTS: usize = 32;
+// Positions for entities that still haven't been spawned after this many ticks probably belong to
+// entities that have since been despawned
+const MAX_PENDING_TICKS: Tick = 50;
+
+pub struct InterpolationPlugin;
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InterpolationSettings>()
+            .init_resource::<ServerTime>()
+            .init_resource::<ReceivedSnapshots>()
+            .add_systems(
+                Update,
+                (buffer_snapshots, advance_server_time, interpolate_snapshots).chain(),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct InterpolationSettings {
+    // Ticks remote entities are drawn behind the server, so there's normally a later snapshot to
+    // move towards. Counted in ticks so it follows the tick rate the server's handshake sets
+    pub delay_ticks: f64,
+    // Seconds an entity keeps moving along its last velocity when snapshots stop arriving
+    pub max_extrapolation: f64,
+}
+
+impl Default for InterpolationSettings {
+    fn default() -> Self {
+        Self {
+            delay_ticks: 2.0,
+            max_extrapolation: 0.25,
+        }
+    }
+}
+
+// The client's estimate of the server's tick. It runs on between updates using the local clock,
+// but never gets further ahead of the newest tick received than could be drawn
+#[derive(Resource, Default)]
+pub struct ServerTime {
+    latest_tick: Option<Tick>,
+    estimate: f64,
+}
+
+impl ServerTime {
+    fn observe(&mut self, tick: Tick) {
+        if self.latest_tick.is_none_or(|latest| tick > latest) {
+            self.latest_tick = Some(tick);
+            self.estimate = self.estimate.max(tick as f64);
+        }
+    }
+
+    pub fn render_tick(&self, settings: &InterpolationSettings) -> f64 {
+        self.estimate - settings.delay_ticks
+    }
+}
+
+// The snapshots received from the server so far, and the newest position for each entity that
+// hasn't been spawned yet. Both only mean anything for the connection they arrived over
+#[derive(Resource, Default)]
+pub struct ReceivedSnapshots {
+    history: SnapshotHistory,
+    pending: HashMap<Entity, (Tick, Vec3)>,
+}
+
+#[derive(Clone, Copy)]
+struct BufferedPosition {
+    tick: Tick,
+    position: Vec3,
+}
+
+#[derive(Component, Default)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<BufferedPosition>,
+}
+
+impl SnapshotBuffer {
+    fn push(&mut self, tick: Tick, position: Vec3) {
+        if let Some(last) = self.snapshots.back().copied() {
+            if tick <= last.tick {
+                return;
+            }
+            // The server only sends positions that changed, so after a gap the entity was sat
+            // at its last position until the tick before this one
+            if tick - last.tick > 1 {
+                self.snapshots.push_back(BufferedPosition {
+                    tick: tick - 1,
+                    position: last.position,
+                });
+            }
+        }
+
+        self.snapshots.push_back(BufferedPosition { tick, position });
+        while self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+    }
+
+    // Extrapolation only happens while nothing newer has arrived from the server at all. If other
+    // entities have moved on this one has just stopped moving
+    fn sample(&self, render_tick: f64, latest_tick: Tick, max_ahead: f64) -> Option<Vec3> {
+        let first = self.snapshots.front()?;
+        if render_tick <= first.tick as f64 {
+            return Some(first.position);
+        }
+
+        for (from, to) in self.snapshots.iter().zip(self.snapshots.iter().skip(1)) {
+            if render_tick < to.tick as f64 {
+                let fraction = (render_tick - from.tick as f64) / (to.tick - from.tick) as f64;
+                return Some(from.position.lerp(to.position, fraction as f32));
+            }
+        }
+
+        let last = self.snapshots.back()?;
+        let previous = match self.snapshots.len() {
+            length if length >= 2 && last.tick == latest_tick => self.snapshots[length - 2],
+            _ => return Some(last.position),
+        };
+
+        let velocity = (last.position - previous.position) / (last.tick - previous.tick) as f32;
+        let ahead = (render_tick - last.tick as f64).min(max_ahead);
+        Some(last.position + velocity * ahead as f32)
+    }
+}
+
+// Each snapshot is decoded against the one the server says it was based on and then acknowledged,
+// so the server can send the next one relative to it. Positions can arrive before the replicated
+// spawn for the same entity, so only the newest position for each entity is kept back and
+// retried next frame
+fn buffer_snapshots(
+    mut snapshot_event_reader: EventReader<ReceiveFromServer<Snapshot>>,
+    mut acknowledged_event_writer: EventWriter<SendToServer<SnapshotAcknowledged>>,
+    mut received: ResMut<ReceivedSnapshots>,
+    mut buffers: Query<&mut SnapshotBuffer>,
+    mut server_time: ResMut<ServerTime>,
+    mapper: Res<EntityMapper>,
+) {
+    let ReceivedSnapshots { history, pending } = &mut *received;
+    for event in snapshot_event_reader.read() {
+        let snapshot = &event.message;
+        let state = match snapshot.baseline {
+            Some(baseline) => match history.get(baseline) {
+                Some(state) => state.apply(snapshot),
+                None => {
+                    debug!("Dropped snapshot {} as baseline {} is gone", snapshot.tick, baseline);
+                    continue;
+                }
+            },
+            None => SnapshotState::default().apply(snapshot),
+        };
+
+        server_time.observe(snapshot.tick);
+        for entity_snapshot in &snapshot.entities {
+            let entity = match *entity_snapshot {
+                EntitySnapshot::Full { entity, .. } => entity,
+                EntitySnapshot::Delta { entity, .. } => entity,
+            };
+            if let Some(position) = state.get(entity) {
+                pending.insert(entity, (snapshot.tick, position.to_vec3()));
+            }
+        }
+        for entity in &snapshot.removed {
+            pending.remove(entity);
+        }
+
+        history.insert(snapshot.tick, state);
+        acknowledged_event_writer.send(SendToServer {
+            message: SnapshotAcknowledged {
+                tick: snapshot.tick,
+            },
+        });
+    }
+
+    let latest_tick = server_time.latest_tick.unwrap_or_default();
+    pending.retain(|remote_entity, (tick, position)| {
+        let Some(mut buffer) = mapper
+            .0
+            .get(remote_entity)
+            .and_then(|local_entity| buffers.get_mut(*local_entity).ok())
+        else {
+            return *tick + MAX_PENDING_TICKS >= latest_tick;
+        };
+
+        buffer.push(*tick, *position);
+        false
+    });
+}
+
+fn advance_server_time(
+    time: Res<Time>,
+    fixed_time: Res<Time<Fixed>>,
+    settings: Res<InterpolationSettings>,
+    mut server_time: ResMut<ServerTime>,
+) {
+    let Some(latest_tick) = server_time.latest_tick else {
+        return;
+    };
+
+    let tick_seconds = fixed_time.timestep().as_secs_f64();
+    let max_ahead = settings.delay_ticks + settings.max_extrapolation / tick_seconds;
+    server_time.estimate = (server_time.estimate + time.delta_seconds_f64() / tick_seconds)
+        .min(latest_tick as f64 + max_ahead);
+}
+
+fn interpolate_snapshots(
+    fixed_time: Res<Time<Fixed>>,
+    settings: Res<InterpolationSettings>,
+    server_time: Res<ServerTime>,
+    mut entities: Query<(&SnapshotBuffer, &mut Transform), Without<Predicted>>,
+) {
+    let Some(latest_tick) = server_time.latest_tick else {
+        return;
+    };
+    // Ticks are the fixed timestep, which is set from the same tick rate as the server's
+    let tick_seconds = fixed_time.timestep().as_secs_f64();
+    let render_tick = server_time.render_tick(&settings);
+    let max_ahead = settings.max_extrapolation / tick_seconds;
+
+    for (buffer, mut transform) in &mut entities {
+        if let Some(position) = buffer.sample(render_tick, latest_tick, max_ahead) {
+            transform.translation = position;
+        }
+    }
+}
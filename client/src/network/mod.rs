@@ -1,7 +1,20 @@
 mod network_plugin;
 mod event_types;
 mod entity_mapper;
+mod diagnostics;
+mod handshake;
+mod interpolation;
+mod prediction;
+mod reconnection;
+mod recording;
+mod replication;
+mod rpc;
 
 pub use network_plugin::NetworkPlugin;
 pub use event_types::{ ReceiveFromServer, SendToServer };
-pub use entity_mapper::EntityMapper;
\ No newline at end of file
+pub use entity_mapper::EntityMapper;
+pub use diagnostics::NetworkDiagnosticsPlugin;
+pub use interpolation::{InterpolationPlugin, SnapshotBuffer};
+pub use prediction::{Predicted, PredictionPlugin};
+pub use recording::{MessageRecorder, Playback};
+pub use rpc::{RequestFailed, Requester, ResponseReceived};
\ No newline at end of file
@@ -1,133 +1,220 @@
-use std::{net::UdpSocket, time::SystemTime, fmt::Display, any};
+use std::{net::UdpSocket, time::SystemTime};
 
 use bevy::{
     app::{App, Plugin, Update},
     ecs::{
-        event::{EventReader, EventWriter},
+        event::EventReader,
         schedule::{
-            common_conditions::{in_state, resource_exists},
-            IntoSystemConfigs,
+            common_conditions::{in_state, not, resource_exists},
+            Condition, IntoSystemConfigs, OnEnter,
         },
-        system::ResMut,
+        system::{Commands, Local, ResMut, Resource},
+        world::{Mut, World},
     },
+    hierarchy::DespawnRecursiveExt,
     log::{warn, debug},
+    time::{common_conditions::on_timer, Time},
 };
 use bevy_renet::{
     renet::{
-        transport::{ClientAuthentication, NetcodeClientTransport},
-        ConnectionConfig, DefaultChannel, RenetClient,
+        transport::{
+            ClientAuthentication, ConnectToken, NetcodeClientTransport, NetcodeTransportError,
+        },
+        RenetClient,
     },
     transport::NetcodeClientPlugin,
     RenetClientPlugin, client_connected,
 };
 use common::network::{
-    configuration::{CLIENT_SOCKET_ADDRESS, PROTOCOL_ID, SERVER_SOCKET_ADDRESS},
-    events::{
-        CreateEntity, DestroyEntity, EntityPosition, GetPlayerEntity, GetWorldState, PlayerEntity,
-        PlayerInput,
+    channels::{
+        read_sequenced, write_sequenced, IncomingSequence, MessageChannel, OutgoingSequence,
     },
+    conditions::SimulatedLink,
+    message::{encode_message, NetworkMessage},
+    message_router::MessageRouter,
+    protocol::{register_protocol, register_requests, ProtocolRegistrar},
+    replication::register_replicated_components,
+    settings::NetworkSettings,
 };
-use serde::{Deserialize, Serialize};
 
-use crate::GameState;
+use crate::{worlds::CurrentWorld, GameState};
 
 use super::{
+    diagnostics::NetworkStats,
     entity_mapper::EntityMapper,
     event_types::{ReceiveFromServer, SendToServer},
+    handshake::{receive_handshake_reply, send_handshake},
+    interpolation::{ReceivedSnapshots, ServerTime},
+    reconnection::{
+        detect_disconnect, reconnect, reset_reconnection, schedule_reconnect, start_login,
+        Reconnection,
+    },
+    recording::{
+        flush_recording, play_back, MessageRecorder, Playback, RECORDING_FLUSH_INTERVAL,
+    },
+    replication::apply_replication,
+    rpc::ClientRequestRegistrar,
 };
 
-enum NetworkEventDirection {
-    Send,
-    Receive,
-    Both,
-}
+// The newest sequences received from the server, which start again with each connection
+#[derive(Resource, Default)]
+struct ServerSequence(IncomingSequence);
 
-trait NetworkEventAdder {
-    fn register_network_event<T>(&mut self, direction: NetworkEventDirection) -> &mut Self
-    where
-        T: Display + Serialize + for<'a> Deserialize<'a> + Sync + Send + 'static;
-}
+// Registers the client's side of each message declared in the shared protocol
+struct ClientProtocolRegistrar<'a>(&'a mut App);
 
-impl NetworkEventAdder for App {
-    fn register_network_event<T>(&mut self, direction: NetworkEventDirection) -> &mut Self
-    where
-        T: Display + Serialize + for<'a> Deserialize<'a> + Sync + Send + 'static
-    {
-        match direction {
-            NetworkEventDirection::Send => {
-                self.add_event::<SendToServer<T>>();
-                self.add_systems(
-                    Update,
-                    (send_messages::<T>)
-                        .run_if(client_connected())
-                        .run_if(in_state(GameState::Gameplay)),
-                );
-            }
-            NetworkEventDirection::Receive => {
-                self.add_event::<ReceiveFromServer<T>>();
-                self.add_systems(
-                    Update,
-                    (receive_messages::<T>).run_if(client_connected()),
-                );
-            }
-            NetworkEventDirection::Both => {
-                self.add_event::<SendToServer<T>>();
-                self.add_event::<ReceiveFromServer<T>>();
-                self.add_systems(
-                    Update,
-                    (send_messages::<T>, receive_messages::<T>)
-                        .run_if(client_connected()),
-                );
-            }
-        };
-        self
+impl ProtocolRegistrar for ClientProtocolRegistrar<'_> {
+    fn register<T: NetworkMessage>(&mut self) {
+        let app = &mut *self.0;
+        if T::DIRECTION.sent_by_client() {
+            app.add_event::<SendToServer<T>>();
+            app.add_systems(Update, (send_messages::<T>).run_if(client_connected()));
+        }
+        if T::DIRECTION.sent_by_server() {
+            app.add_event::<ReceiveFromServer<T>>();
+            app.world
+                .resource_mut::<MessageRouter<()>>()
+                .add::<T, _>(|_, message| ReceiveFromServer { message });
+        }
     }
 }
 
-pub struct NetworkPlugin;
+pub struct NetworkPlugin {
+    // The account to ask the login service for a connect token for, and the secret it was
+    // claimed with
+    pub account: String,
+    pub secret: String,
+}
 
 impl Plugin for NetworkPlugin {
     fn build(&self, app: &mut App) {
-        let socket = UdpSocket::bind(CLIENT_SOCKET_ADDRESS).unwrap();
-        let current_time = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap();
-
-        let auth = ClientAuthentication::Unsecure {
-            protocol_id: PROTOCOL_ID,
-            client_id: current_time.as_millis() as u64,
-            server_addr: SERVER_SOCKET_ADDRESS,
-            user_data: None,
-        };
-
-        let transport = NetcodeClientTransport::new(current_time, auth, socket).unwrap();
-        let client = RenetClient::new(ConnectionConfig::default());
-
-        app.add_plugins((RenetClientPlugin, NetcodeClientPlugin));
-        app.insert_resource(client);
-        app.insert_resource(transport);
+        // Playing a session back needs nothing from a server. Otherwise the first login is made
+        // the same way as after losing the connection, so a login service that can't be reached
+        // yet is retried rather than fatal
+        if !app.world.contains_resource::<Playback>() {
+            let settings = app.world.resource::<NetworkSettings>();
+            if !settings.conditions.is_perfect() {
+                warn!("Simulating network conditions {:?}", settings.conditions);
+                let link = SimulatedLink::<()>::new(settings.conditions);
+                app.insert_resource(link);
+            }
+
+            app.add_plugins((RenetClientPlugin, NetcodeClientPlugin));
+            app.add_systems(
+                OnEnter(GameState::Loading),
+                start_login.run_if(not(resource_exists::<RenetClient>())),
+            );
+        }
+
         app.init_resource::<EntityMapper>();
+        app.init_resource::<MessageRouter<()>>();
+        app.init_resource::<ServerSequence>();
+        app.init_resource::<NetworkStats>();
+        app.insert_resource(Reconnection::new(self.account.clone(), self.secret.clone()));
+
+        register_protocol(&mut ClientProtocolRegistrar(app));
+        register_requests(&mut ClientRequestRegistrar(app));
+
+        register_replicated_components(app);
 
-        app.register_network_event::<PlayerInput>(NetworkEventDirection::Send)
-            .register_network_event::<EntityPosition>(NetworkEventDirection::Receive)
-            .register_network_event::<CreateEntity>(NetworkEventDirection::Receive)
-            .register_network_event::<DestroyEntity>(NetworkEventDirection::Receive)
-            .register_network_event::<GetWorldState>(NetworkEventDirection::Send)
-            .register_network_event::<GetPlayerEntity>(NetworkEventDirection::Send)
-            .register_network_event::<PlayerEntity>(NetworkEventDirection::Receive);
+        app.add_systems(
+            Update,
+            (
+                receive_messages.run_if(client_connected()),
+                play_back
+                    .run_if(resource_exists::<Playback>())
+                    .run_if(not(in_state(GameState::LoadConfigs))),
+                apply_replication,
+            )
+                .chain(),
+        );
+        app.add_systems(
+            Update,
+            (
+                send_handshake.run_if(resource_exists::<RenetClient>()),
+                receive_handshake_reply,
+            )
+                .run_if(in_state(GameState::Loading)),
+        );
+        app.add_systems(
+            Update,
+            detect_disconnect
+                .run_if(resource_exists::<RenetClient>())
+                .run_if(in_state(GameState::Loading).or_else(in_state(GameState::Gameplay))),
+        );
+        app.add_systems(
+            Update,
+            flush_recording
+                .run_if(resource_exists::<MessageRecorder>())
+                .run_if(on_timer(RECORDING_FLUSH_INTERVAL)),
+        );
+        app.add_systems(
+            OnEnter(GameState::Reconnecting),
+            (forget_server_state, schedule_reconnect),
+        );
+        app.add_systems(Update, reconnect.run_if(in_state(GameState::Reconnecting)));
+        app.add_systems(OnEnter(GameState::Gameplay), reset_reconnection);
     }
 }
 
-fn send_messages<T: Display + Serialize + for<'a> Deserialize<'a> + Sync + Send + 'static>(
+// Each connection gets a socket of its own, so nothing meant for an old one can arrive on it
+pub fn connect(
+    settings: &NetworkSettings,
+    connect_token: ConnectToken,
+) -> Result<NetcodeClientTransport, NetcodeTransportError> {
+    let socket = UdpSocket::bind(settings.client_bind_address())?;
+    let current_time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    let auth = ClientAuthentication::Secure { connect_token };
+
+    Ok(NetcodeClientTransport::new(current_time, auth, socket)?)
+}
+
+// Everything the client knows about the world came over the old connection. The server sends it
+// all again after reconnecting, under entities of its own and from a tick of its own, which is
+// back at 0 if it restarted. A restarted server also puts the player back in the default world,
+// and one that kept the player tells the client which world it's in when asked for its entity
+fn forget_server_state(
+    mut mapper: ResMut<EntityMapper>,
+    mut incoming: ResMut<ServerSequence>,
+    mut server_time: ResMut<ServerTime>,
+    mut snapshots: ResMut<ReceivedSnapshots>,
+    mut current_world: ResMut<CurrentWorld>,
+    link: Option<ResMut<SimulatedLink<()>>>,
+    mut commands: Commands,
+) {
+    for (_, local_entity) in mapper.0.drain() {
+        commands.entity(local_entity).despawn_recursive();
+    }
+    *incoming = ServerSequence::default();
+    *server_time = ServerTime::default();
+    *snapshots = ReceivedSnapshots::default();
+    *current_world = CurrentWorld::default();
+    if let Some(mut link) = link {
+        link.clear();
+    }
+}
+
+fn send_messages<T: NetworkMessage>(
     mut client: ResMut<RenetClient>,
     mut reader: EventReader<SendToServer<T>>,
+    mut sequence: Local<OutgoingSequence>,
+    mut stats: ResMut<NetworkStats>,
 ) {
-    reader.read().for_each(move |event| {       
-        let serialisation_result = bincode::serde::encode_to_vec(&event.message, bincode::config::standard());
+    reader.read().for_each(move |event| {
+        let serialisation_result = encode_message(&event.message).map(|message| {
+            if T::CHANNEL.is_sequenced() {
+                write_sequenced(sequence.advance(), message)
+            } else {
+                message
+            }
+        });
         match serialisation_result {
             Ok(serialised_message) => {
                 debug!("Sent a message ({}) (encoded as {:#?})", event.message, serialised_message);
-                client.send_message(DefaultChannel::ReliableOrdered, serialised_message);
+                stats.traffic.record_sent(T::TYPE_ID, serialised_message.len());
+                client.send_message(T::CHANNEL, serialised_message);
             }
             Err(serialisation_error) => {
                 warn!(
@@ -139,25 +226,57 @@ fn send_messages<T: Display + Serialize + for<'a> Deserialize<'a> + Sync + Send
     })
 }
 
-fn receive_messages<T: Display + Serialize + for<'a> Deserialize<'a> + Sync + Send + 'static>(
-    mut client: ResMut<RenetClient>,
-    mut writer: EventWriter<ReceiveFromServer<T>>,
-) {
-    while let Some(message) = client.receive_message(DefaultChannel::ReliableOrdered) {
-        let deserialisation_result = bincode::serde::decode_from_slice(&message, bincode::config::standard());
-        match deserialisation_result {
-            Ok((deserialised_message, _)) => {
-                debug!("Received a message ({})", deserialised_message);
-                writer.send(ReceiveFromServer {
-                    message: deserialised_message,
-                });
-            }
-            Err(deserialisation_error) => {
-                warn!(
-                    "Failed to deserialise a message from the server ({})",
-                    deserialisation_error
-                );
-            }
+fn receive_messages(world: &mut World) {
+    let now = world.resource::<Time>().elapsed_seconds_f64();
+    let mut received = Vec::new();
+    let mut client = world.resource_mut::<RenetClient>();
+    for channel in MessageChannel::ALL {
+        while let Some(bytes) = client.receive_message(channel) {
+            received.push(((), channel, bytes.to_vec()));
         }
     }
+    if let Some(mut link) = world.get_resource_mut::<SimulatedLink<()>>() {
+        received = link.simulate(now, received);
+    }
+
+    world.resource_scope(|world, router: Mut<MessageRouter<()>>| {
+        world.resource_scope(|world, mut incoming: Mut<ServerSequence>| {
+            for ((), channel, bytes) in received {
+                let message = if channel.is_sequenced() {
+                    match read_sequenced(&bytes) {
+                        Some((sequence, message)) if incoming.0.accept(sequence, message) => {
+                            message
+                        }
+                        Some(_) => {
+                            debug!("Dropped a stale message");
+                            continue;
+                        }
+                        None => {
+                            warn!("Received a sequenced message without a sequence");
+                            continue;
+                        }
+                    }
+                } else {
+                    &bytes[..]
+                };
+
+                if let Some(mut recorder) = world.get_resource_mut::<MessageRecorder>() {
+                    recorder.record(now, message);
+                }
+
+                match router.dispatch(world, (), message) {
+                    Ok(message_type) => {
+                        debug!("Received a message of type {}", message_type);
+                        world
+                            .resource_mut::<NetworkStats>()
+                            .traffic
+                            .record_received(message_type, message.len());
+                    }
+                    Err(error) => {
+                        warn!("Failed to receive a message from the server ({})", error);
+                    }
+                }
+            }
+        });
+    });
 }
@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    app::{App, FixedUpdate, Plugin, Update},
+    ecs::{
+        component::Component,
+        event::{EventReader, EventWriter},
+        query::With,
+        schedule::{common_conditions::in_state, IntoSystemConfigs, OnEnter},
+        system::{Query, Res, ResMut, Resource},
+    },
+    math::Vec3,
+    time::{Fixed, Time},
+    transform::components::Transform,
+};
+use bevy_renet::client_connected;
+use common::{
+    movement::apply_input,
+    network::events::{InputAcknowledged, InputSequence, PlayerInput},
+};
+
+use crate::GameState;
+
+use super::{ReceiveFromServer, SendToServer};
+
+const MAX_PENDING_INPUTS: usize = 64;
+
+pub struct PredictionPlugin;
+
+impl Plugin for PredictionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingInputs>()
+            .add_systems(
+                FixedUpdate,
+                predict_movement
+                    .run_if(client_connected())
+                    .run_if(in_state(GameState::Gameplay)),
+            )
+            .add_systems(Update, reconcile.run_if(in_state(GameState::Gameplay)))
+            .add_systems(OnEnter(GameState::Reconnecting), forget_pending_inputs);
+    }
+}
+
+// The player's own entity, which is moved locally as soon as input happens rather than waiting
+// for the server
+#[derive(Component)]
+pub struct Predicted;
+
+// Inputs sent to the server that it hasn't acknowledged yet
+#[derive(Resource, Default)]
+struct PendingInputs {
+    last_sequence: InputSequence,
+    inputs: VecDeque<PlayerInput>,
+}
+
+fn predict_movement(
+    time: Res<Time<Fixed>>,
+    input: Res<PlayerInput>,
+    mut pending: ResMut<PendingInputs>,
+    mut players: Query<&mut Transform, With<Predicted>>,
+    mut input_event_writer: EventWriter<SendToServer<PlayerInput>>,
+) {
+    pending.last_sequence += 1;
+    let input = PlayerInput {
+        sequence: pending.last_sequence,
+        ..*input
+    };
+
+    for mut transform in &mut players {
+        transform.translation = apply_input(&input, time.delta_seconds(), transform.translation);
+    }
+
+    pending.inputs.push_back(input);
+    if pending.inputs.len() > MAX_PENDING_INPUTS {
+        pending.inputs.pop_front();
+    }
+
+    input_event_writer.send(SendToServer { message: input });
+}
+
+// Starts again from where the server says the player is and replays everything it hasn't seen yet
+fn reconcile(
+    time: Res<Time<Fixed>>,
+    mut acknowledged_event_reader: EventReader<ReceiveFromServer<InputAcknowledged>>,
+    mut pending: ResMut<PendingInputs>,
+    mut players: Query<&mut Transform, With<Predicted>>,
+) {
+    let Some(acknowledged) = acknowledged_event_reader
+        .read()
+        .map(|event| event.message)
+        .max_by_key(|message| message.sequence)
+    else {
+        return;
+    };
+
+    pending
+        .inputs
+        .retain(|input| input.sequence > acknowledged.sequence);
+
+    let mut translation = Vec3::new(acknowledged.x, acknowledged.y, acknowledged.z);
+    for input in &pending.inputs {
+        translation = apply_input(input, time.timestep().as_secs_f32(), translation);
+    }
+
+    for mut transform in &mut players {
+        transform.translation = translation;
+    }
+}
+
+// The server numbers inputs again from the start for a new connection
+fn forget_pending_inputs(mut pending: ResMut<PendingInputs>) {
+    *pending = PendingInputs::default();
+}
@@ -0,0 +1,135 @@
+use bevy::{
+    ecs::{
+        schedule::NextState,
+        system::{Commands, Res, ResMut, Resource},
+    },
+    log::{info, warn},
+    tasks::{block_on, AsyncComputeTaskPool, Task},
+    time::Time,
+};
+use bevy_renet::renet::{transport::ConnectToken, RenetClient};
+use common::network::{
+    channels::connection_config,
+    login::{request_connect_token, LoginError},
+    settings::NetworkSettings,
+};
+
+use crate::GameState;
+
+use super::network_plugin::connect;
+
+const FIRST_RETRY_SECONDS: f64 = 1.0;
+const MAX_RETRY_SECONDS: f64 = 30.0;
+
+#[derive(Resource)]
+pub struct Reconnection {
+    account: String,
+    secret: String,
+    // Attempts that have failed since the game was last joined, each doubling the wait
+    attempts: u32,
+    next_attempt: f64,
+    login: Option<Task<Result<ConnectToken, LoginError>>>,
+}
+
+impl Reconnection {
+    pub fn new(account: String, secret: String) -> Self {
+        Self {
+            account,
+            secret,
+            attempts: 0,
+            next_attempt: 0.0,
+            login: None,
+        }
+    }
+
+    fn delay(&self) -> f64 {
+        (FIRST_RETRY_SECONDS * 2f64.powi(self.attempts as i32)).min(MAX_RETRY_SECONDS)
+    }
+
+    fn retry_later(&mut self, now: f64) {
+        let delay = self.delay();
+        self.next_attempt = now + delay;
+        self.attempts += 1;
+        info!("Reconnecting in {:.0}s", delay);
+    }
+}
+
+pub fn detect_disconnect(client: Res<RenetClient>, mut state: ResMut<NextState<GameState>>) {
+    if client.is_disconnected() {
+        warn!("Lost the connection to the server ({:?})", client.disconnect_reason());
+        state.set(GameState::Reconnecting);
+    }
+}
+
+// The client starts without a connection and logs in for the first one the same way it does
+// after losing one
+pub fn start_login(mut state: ResMut<NextState<GameState>>) {
+    state.set(GameState::Reconnecting);
+}
+
+// The first login is tried straight away, there's only a wait after losing a connection
+pub fn schedule_reconnect(
+    time: Res<Time>,
+    client: Option<Res<RenetClient>>,
+    mut reconnection: ResMut<Reconnection>,
+) {
+    if client.is_some() {
+        reconnection.retry_later(time.elapsed_seconds_f64());
+    }
+}
+
+pub fn reset_reconnection(mut reconnection: ResMut<Reconnection>) {
+    reconnection.attempts = 0;
+}
+
+// Logging in again blocks on the login service, so it's done off the main thread. The old
+// connection can't be revived, so a successful login replaces it with a new one
+pub fn reconnect(
+    time: Res<Time>,
+    settings: Res<NetworkSettings>,
+    mut reconnection: ResMut<Reconnection>,
+    mut state: ResMut<NextState<GameState>>,
+    mut commands: Commands,
+) {
+    let now = time.elapsed_seconds_f64();
+    let Some(login) = reconnection.login.as_ref() else {
+        if now >= reconnection.next_attempt {
+            let address = settings.login_public_address();
+            let account = reconnection.account.clone();
+            let secret = reconnection.secret.clone();
+            reconnection.login = Some(
+                AsyncComputeTaskPool::get()
+                    .spawn(async move { request_connect_token(address, &account, &secret) }),
+            );
+        }
+        return;
+    };
+    if !login.is_finished() {
+        return;
+    }
+    let Some(login) = reconnection.login.take() else {
+        return;
+    };
+    let result = block_on(login);
+
+    let connect_token = match result {
+        Ok(connect_token) => connect_token,
+        Err(error) => {
+            warn!("Couldn't log in as {} ({})", reconnection.account, error);
+            reconnection.retry_later(now);
+            return;
+        }
+    };
+    match connect(&settings, connect_token) {
+        Ok(transport) => {
+            info!("Connecting to the server");
+            commands.insert_resource(RenetClient::new(connection_config()));
+            commands.insert_resource(transport);
+            state.set(GameState::Loading);
+        }
+        Err(error) => {
+            warn!("Couldn't reconnect to the server ({})", error);
+            reconnection.retry_later(now);
+        }
+    }
+}
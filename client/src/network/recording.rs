@@ -0,0 +1,116 @@
+use std::{collections::VecDeque, path::Path, time::Duration};
+
+use bevy::{
+    ecs::{
+        system::{ResMut, Resource},
+        world::{Mut, World},
+    },
+    log::{info, warn},
+    time::Time,
+};
+use common::network::{
+    message_router::MessageRouter,
+    recording::{read_recording, RecordingError, RecordingWriter},
+};
+use serde::{Deserialize, Serialize};
+
+// At most this much of a recording is lost if the client dies without closing it
+pub const RECORDING_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordingHeader {
+    pub account: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReceivedMessage {
+    pub seconds: f64,
+    pub bytes: Vec<u8>,
+}
+
+// Keeps every message from the server that made it past sequencing, so a session can be watched
+// again without one
+#[derive(Resource)]
+pub struct MessageRecorder {
+    writer: RecordingWriter,
+}
+
+impl MessageRecorder {
+    pub fn create(path: &Path, account: &str) -> Result<Self, RecordingError> {
+        info!("Recording messages from the server to {}", path.display());
+        let header = RecordingHeader {
+            account: account.to_string(),
+        };
+        Ok(Self {
+            writer: RecordingWriter::create(path, &header)?,
+        })
+    }
+
+    pub fn record(&mut self, seconds: f64, bytes: &[u8]) {
+        let message = ReceivedMessage {
+            seconds,
+            bytes: bytes.to_vec(),
+        };
+        if let Err(error) = self.writer.write(&message) {
+            warn!("Failed to record a message ({})", error);
+        }
+    }
+}
+
+pub fn flush_recording(mut recorder: ResMut<MessageRecorder>) {
+    if let Err(error) = recorder.writer.flush() {
+        warn!("Failed to flush the recording ({})", error);
+    }
+}
+
+// Stands in for the server, handing out the recorded messages with the same gaps between them as
+// when they were received
+#[derive(Resource)]
+pub struct Playback {
+    messages: VecDeque<ReceivedMessage>,
+    // Added to the recorded times to get the times to dispatch at, set by the first message
+    offset: Option<f64>,
+}
+
+impl Playback {
+    pub fn load(path: &Path) -> Result<(RecordingHeader, Self), RecordingError> {
+        let (header, messages): (RecordingHeader, Vec<ReceivedMessage>) = read_recording(path)?;
+        info!(
+            "Playing back {} messages received by {}",
+            messages.len(),
+            header.account
+        );
+        let playback = Self {
+            messages: messages.into(),
+            offset: None,
+        };
+        Ok((header, playback))
+    }
+}
+
+pub fn play_back(world: &mut World) {
+    let now = world.resource::<Time>().elapsed_seconds_f64();
+    world.resource_scope(|world, mut playback: Mut<Playback>| {
+        let Some(first) = playback.messages.front() else {
+            return;
+        };
+        let offset = now - first.seconds;
+        let offset = *playback.offset.get_or_insert(offset);
+
+        world.resource_scope(|world, router: Mut<MessageRouter<()>>| {
+            while playback
+                .messages
+                .front()
+                .is_some_and(|message| message.seconds + offset <= now)
+            {
+                let message = playback.messages.pop_front().unwrap();
+                if let Err(error) = router.dispatch(world, (), &message.bytes) {
+                    warn!("Failed to play back a message ({})", error);
+                }
+                if playback.messages.is_empty() {
+                    info!("Playback finished");
+                }
+            }
+        });
+    });
+}
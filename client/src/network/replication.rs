@@ -0,0 +1,81 @@
+use bevy::{
+    ecs::{event::Events, world::{Mut, World}},
+    hierarchy::DespawnRecursiveExt,
+    log::{debug, warn},
+    render::prelude::SpatialBundle,
+};
+use common::network::{
+    events::{ReplicationChange, ReplicationUpdate},
+    message::MessageError,
+    replication::ReplicationRegistry,
+};
+
+use super::{EntityMapper, ReceiveFromServer, SnapshotBuffer};
+
+fn apply_change(
+    world: &mut World,
+    registry: &ReplicationRegistry,
+    mapper: &mut EntityMapper,
+    change: ReplicationChange,
+) -> Result<(), MessageError> {
+    match change {
+        ReplicationChange::Spawn(remote_entity) => {
+            if !mapper.0.contains_key(&remote_entity) {
+                let local_entity = world
+                    .spawn((SpatialBundle::default(), SnapshotBuffer::default()))
+                    .id();
+                debug!("Spawned {:?} for {:?}", local_entity, remote_entity);
+                mapper.0.insert(remote_entity, local_entity);
+            }
+        }
+        ReplicationChange::Despawn(remote_entity) => {
+            if let Some(local_entity) = mapper.0.remove(&remote_entity) {
+                debug!("Despawned {:?} for {:?}", local_entity, remote_entity);
+                world.entity_mut(local_entity).despawn_recursive();
+            }
+        }
+        ReplicationChange::Insert {
+            entity,
+            component,
+            data,
+        }
+        | ReplicationChange::Update {
+            entity,
+            component,
+            data,
+        } => match mapper.0.get(&entity) {
+            Some(local_entity) => registry.write(world, *local_entity, component, &data)?,
+            None => warn!("Received component {} for unknown entity {:?}", component, entity),
+        },
+        ReplicationChange::Remove { entity, component } => match mapper.0.get(&entity) {
+            Some(local_entity) => registry.remove(world, *local_entity, component)?,
+            None => warn!("Removed component {} from unknown entity {:?}", component, entity),
+        },
+    }
+    Ok(())
+}
+
+// Updates arrive in order on a reliable channel, so they can be applied as they come and an
+// entity's spawn is always seen before anything else about it
+pub fn apply_replication(world: &mut World) {
+    let updates: Vec<ReplicationUpdate> = world
+        .resource_mut::<Events<ReceiveFromServer<ReplicationUpdate>>>()
+        .drain()
+        .map(|event| event.message)
+        .collect();
+    if updates.is_empty() {
+        return;
+    }
+
+    world.resource_scope(|world, registry: Mut<ReplicationRegistry>| {
+        world.resource_scope(|world, mut mapper: Mut<EntityMapper>| {
+            for update in updates {
+                for change in update.changes {
+                    if let Err(error) = apply_change(world, &registry, &mut mapper, change) {
+                        warn!("Failed to apply replication at tick {} ({})", update.tick, error);
+                    }
+                }
+            }
+        });
+    });
+}
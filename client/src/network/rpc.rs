@@ -0,0 +1,112 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    app::{App, Update},
+    ecs::{
+        event::{Event, EventReader, EventWriter},
+        schedule::{IntoSystemConfigs, OnEnter},
+        system::{Res, ResMut, SystemParam},
+    },
+    log::{debug, warn},
+    time::Time,
+};
+use common::network::{
+    protocol::RequestRegistrar,
+    rpc::{PendingRequests, Request, RequestError, RequestID, Response},
+};
+
+use crate::GameState;
+
+use super::{ReceiveFromServer, SendToServer};
+
+// The answer to a request sent with a `Requester`
+#[derive(Event)]
+pub struct ResponseReceived<T: Request> {
+    pub request: RequestID,
+    pub response: T::Response,
+}
+
+// Sent instead of a response when none is coming
+#[derive(Event)]
+pub struct RequestFailed<T: Request> {
+    pub request: RequestID,
+    pub error: RequestError,
+    marker: PhantomData<T>,
+}
+
+// Sends requests to the server. Each is given its ID here, which is returned so the response or
+// failure can be told apart from those of other requests
+#[derive(SystemParam)]
+pub struct Requester<'w, T: Request> {
+    time: Res<'w, Time>,
+    pending: ResMut<'w, PendingRequests<T>>,
+    request_event_writer: EventWriter<'w, SendToServer<T>>,
+}
+
+impl<T: Request> Requester<'_, T> {
+    pub fn send(&mut self, mut request: T) -> RequestID {
+        let id = self.pending.start(&mut request, self.time.elapsed_seconds_f64());
+        self.request_event_writer.send(SendToServer { message: request });
+        id
+    }
+}
+
+pub struct ClientRequestRegistrar<'a>(pub &'a mut App);
+
+impl RequestRegistrar for ClientRequestRegistrar<'_> {
+    fn register<T: Request>(&mut self) {
+        self.0
+            .init_resource::<PendingRequests<T>>()
+            .add_event::<ResponseReceived<T>>()
+            .add_event::<RequestFailed<T>>()
+            .add_systems(Update, (match_responses::<T>, expire_requests::<T>).chain())
+            .add_systems(OnEnter(GameState::Reconnecting), cancel_requests::<T>);
+    }
+}
+
+fn match_responses<T: Request>(
+    mut response_event_reader: EventReader<ReceiveFromServer<T::Response>>,
+    mut pending: ResMut<PendingRequests<T>>,
+    mut response_event_writer: EventWriter<ResponseReceived<T>>,
+) {
+    for event in response_event_reader.read() {
+        let response = &event.message;
+        if !pending.finish(response) {
+            debug!("Dropped a response to a request that was no longer waiting ({})", response);
+            continue;
+        }
+
+        response_event_writer.send(ResponseReceived {
+            request: response.request_id(),
+            response: event.message.clone(),
+        });
+    }
+}
+
+fn expire_requests<T: Request>(
+    time: Res<Time>,
+    mut pending: ResMut<PendingRequests<T>>,
+    mut failed_event_writer: EventWriter<RequestFailed<T>>,
+) {
+    for request in pending.expire(time.elapsed_seconds_f64()) {
+        warn!("Request {} ({}) timed out", request, std::any::type_name::<T>());
+        failed_event_writer.send(RequestFailed {
+            request,
+            error: RequestError::TimedOut,
+            marker: PhantomData,
+        });
+    }
+}
+
+fn cancel_requests<T: Request>(
+    mut pending: ResMut<PendingRequests<T>>,
+    mut failed_event_writer: EventWriter<RequestFailed<T>>,
+) {
+    for request in pending.cancel_all() {
+        failed_event_writer.send(RequestFailed {
+            request,
+            error: RequestError::Disconnected,
+            marker: PhantomData,
+        });
+    }
+}
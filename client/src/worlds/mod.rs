@@ -0,0 +1,3 @@
+mod world_plugin;
+
+pub use world_plugin::{CurrentWorld, WorldPlugin};
@@ -0,0 +1,84 @@
+use bevy::{
+    app::{Plugin, Update},
+    ecs::{
+        event::{EventReader, EventWriter},
+        schedule::{common_conditions::in_state, IntoSystemConfigs},
+        system::{Res, ResMut, Resource},
+    },
+    input::{keyboard::KeyCode, Input},
+    log::{info, warn},
+};
+use common::network::{
+    configuration::{WorldID, DEFAULT_WORLD},
+    events::{JoinWorld, JoinWorldFailed, WorldJoined},
+};
+
+use crate::{
+    network::{ReceiveFromServer, SendToServer},
+    GameState,
+};
+
+// The number keys ask to join the world with that number, starting from the default world on 1
+const WORLD_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+pub struct WorldPlugin;
+
+impl Plugin for WorldPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<CurrentWorld>().add_systems(
+            Update,
+            (request_world_change, update_current_world).run_if(in_state(GameState::Gameplay)),
+        );
+    }
+}
+
+#[derive(Resource)]
+pub struct CurrentWorld(pub WorldID);
+
+impl Default for CurrentWorld {
+    fn default() -> Self {
+        Self(DEFAULT_WORLD)
+    }
+}
+
+fn request_world_change(
+    keyboard: Res<Input<KeyCode>>,
+    current_world: Res<CurrentWorld>,
+    mut join_world_event_writer: EventWriter<SendToServer<JoinWorld>>,
+) {
+    for (index, key) in WORLD_KEYS.iter().enumerate() {
+        let world = DEFAULT_WORLD + index as WorldID;
+        if keyboard.just_released(*key) && world != current_world.0 {
+            join_world_event_writer.send(SendToServer {
+                message: JoinWorld { world },
+            });
+        }
+    }
+}
+
+// The server despawns everything from the old world and spawns the new one through replication,
+// so all that's left to do here is remember where the player is
+fn update_current_world(
+    mut world_joined_events: EventReader<ReceiveFromServer<WorldJoined>>,
+    mut join_world_failed_events: EventReader<ReceiveFromServer<JoinWorldFailed>>,
+    mut current_world: ResMut<CurrentWorld>,
+) {
+    for event in world_joined_events.read() {
+        info!("{}", event.message);
+        current_world.0 = event.message.world;
+    }
+
+    for event in join_world_failed_events.read() {
+        warn!("{}", event.message);
+    }
+}
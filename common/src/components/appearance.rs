@@ -0,0 +1,9 @@
+use bevy::ecs::component::Component;
+use serde::{Deserialize, Serialize};
+
+// How an entity is drawn, set by the server and replicated to clients
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Appearance {
+    pub colour: [f32; 4],
+    pub size: [f32; 2],
+}
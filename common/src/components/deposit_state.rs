@@ -0,0 +1,13 @@
+use bevy::ecs::component::Component;
+use serde::{Deserialize, Serialize};
+
+use crate::{materials::MaterialID, units::Mass};
+
+// What clients are told about an ore deposit, kept up to date by the server as it's mined
+#[derive(Component, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DepositState {
+    pub material: MaterialID,
+    pub ratios: Vec<f32>,
+    pub total: Mass,
+    pub remaining: Mass,
+}
@@ -0,0 +1,5 @@
+mod appearance;
+mod deposit_state;
+
+pub use appearance::Appearance;
+pub use deposit_state::DepositState;
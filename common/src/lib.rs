@@ -1,7 +1,10 @@
 use pkg_version::{pkg_version_major, pkg_version_minor, pkg_version_patch};
 
+pub mod components;
 pub mod loaders;
+pub mod market;
 pub mod materials;
+pub mod movement;
 pub mod network;
 pub mod units;
 
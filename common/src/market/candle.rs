@@ -0,0 +1,88 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::units::Mass;
+
+use super::Price;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CandleResolution {
+    OneMinute,
+    FifteenMinutes,
+    OneHour,
+    OneDay,
+}
+
+impl CandleResolution {
+    pub const ALL: [CandleResolution; 4] = [
+        CandleResolution::OneMinute,
+        CandleResolution::FifteenMinutes,
+        CandleResolution::OneHour,
+        CandleResolution::OneDay,
+    ];
+
+    // Length of a candle in seconds of game time
+    pub const fn seconds(self) -> u64 {
+        match self {
+            CandleResolution::OneMinute => 60,
+            CandleResolution::FifteenMinutes => 15 * 60,
+            CandleResolution::OneHour => 60 * 60,
+            CandleResolution::OneDay => 24 * 60 * 60,
+        }
+    }
+
+    pub const fn candle_start(self, time: u64) -> u64 {
+        time - time % self.seconds()
+    }
+}
+
+impl Display for CandleResolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CandleResolution::OneMinute => f.write_str("1m"),
+            CandleResolution::FifteenMinutes => f.write_str("15m"),
+            CandleResolution::OneHour => f.write_str("1h"),
+            CandleResolution::OneDay => f.write_str("1d"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Candle {
+    pub start: u64,
+    pub open: Price,
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
+    pub volume: Mass,
+}
+
+impl Candle {
+    pub fn new(start: u64, price: Price, quantity: Mass) -> Self {
+        Self {
+            start,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: quantity,
+        }
+    }
+
+    pub fn record(&mut self, price: Price, quantity: Mass) {
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+        self.volume += quantity;
+    }
+}
+
+impl Display for Candle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "[{}] O {} H {} L {} C {} V {}",
+            self.start, self.open, self.high, self.low, self.close, self.volume
+        ))
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    fmt::Display,
+    iter::Sum,
+    ops::{Add, AddAssign, Neg, Sub, SubAssign},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::units::UnitT;
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Default, Hash, Serialize, Deserialize)]
+pub struct Credits(UnitT);
+
+impl Credits {
+    pub const fn new(value: UnitT) -> Self {
+        Self(value)
+    }
+
+    pub const fn value(&self) -> UnitT {
+        self.0
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Credits)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Credits)
+    }
+}
+
+// Overflowing credits would quietly create or destroy money in release builds, so the operators
+// panic instead. Anything working with amounts a client picked uses the checked versions
+impl Add for Credits {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        self.checked_add(rhs).expect("Credits overflowed")
+    }
+}
+
+impl AddAssign for Credits {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Credits {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.checked_sub(rhs).expect("Credits overflowed")
+    }
+}
+
+impl SubAssign for Credits {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl Neg for Credits {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Credits(-self.0)
+    }
+}
+
+impl Sum for Credits {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Credits::default(), |total, credits| total + credits)
+    }
+}
+
+impl Display for Credits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}cr", self.0))
+    }
+}
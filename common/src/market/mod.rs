@@ -0,0 +1,11 @@
+mod candle;
+mod credits;
+mod order_side;
+mod price;
+
+pub use self::candle::{Candle, CandleResolution};
+pub use self::credits::Credits;
+pub use self::order_side::OrderSide;
+pub use self::price::{is_whole_lots, Price, ORDER_LOT};
+
+pub type OrderID = u64;
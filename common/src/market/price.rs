@@ -0,0 +1,95 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::units::{Mass, UnitT};
+
+use super::Credits;
+
+// Orders are for whole kilograms, the unit prices are quoted in, so every fill costs exactly its
+// price times its quantity and nothing traded is ever rounded in anyone's favour
+pub const ORDER_LOT: Mass = Mass::from_kilograms(1);
+
+pub fn is_whole_lots(quantity: Mass) -> bool {
+    quantity.as_milligrams() % ORDER_LOT.as_milligrams() == 0
+}
+
+// Prices are quoted in credits per kilogram, but costs are worked out in milligrams so that
+// fractions of a kilogram are still paid for
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Default, Hash, Serialize, Deserialize)]
+pub struct Price(Credits);
+
+impl Price {
+    pub const fn per_kilogram(credits: Credits) -> Self {
+        Self(credits)
+    }
+
+    pub const fn as_credits_per_kilogram(&self) -> Credits {
+        self.0
+    }
+
+    // Rounded up, so however little is bought it's never free. None if the cost doesn't fit in
+    // Credits
+    pub fn cost(&self, mass: Mass) -> Option<Credits> {
+        let value = self.0.value() as i128 * mass.as_milligrams() as i128;
+        let kilogram = Mass::from_kilograms(1).as_milligrams() as i128;
+        let mut cost = value / kilogram;
+        if value % kilogram > 0 {
+            cost += 1;
+        }
+        UnitT::try_from(cost).ok().map(Credits::new)
+    }
+
+    // Never scales a price below one credit per kilogram, since the market rejects free orders
+    pub fn scaled(&self, factor: f32) -> Self {
+        let credits = (self.0.value() as f64 * factor as f64).round() as UnitT;
+        Self(Credits::new(credits.max(1)))
+    }
+}
+
+impl Display for Price {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}/kg", self.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::units::{Mass, UnitT};
+
+    use super::{is_whole_lots, Credits, Price};
+
+    #[test]
+    fn costs_are_worked_out_per_milligram() {
+        let price = Price::per_kilogram(Credits::new(1_000));
+        assert_eq!(price.cost(Mass::from_grams(1)), Some(Credits::new(1)));
+        assert_eq!(price.cost(Mass::from_kilograms(3)), Some(Credits::new(3_000)));
+    }
+
+    #[test]
+    fn costs_of_less_than_a_credit_round_up() {
+        let price = Price::per_kilogram(Credits::new(1));
+        assert_eq!(price.cost(Mass::from_grams(999)), Some(Credits::new(1)));
+        assert_eq!(price.cost(Mass::from_grams(1_001)), Some(Credits::new(2)));
+        let price = Price::per_kilogram(Credits::new(100));
+        assert_eq!(price.cost(Mass::from_grams(9)), Some(Credits::new(1)));
+        assert_eq!(price.cost(Mass::from_milligrams(1)), Some(Credits::new(1)));
+        assert_eq!(price.cost(Mass::default()), Some(Credits::new(0)));
+    }
+
+    #[test]
+    fn orders_are_for_whole_kilograms() {
+        assert!(is_whole_lots(Mass::from_kilograms(3)));
+        assert!(!is_whole_lots(Mass::from_grams(999)));
+        assert!(!is_whole_lots(Mass::from_grams(1_001)));
+    }
+
+    #[test]
+    fn costs_too_large_for_credits_are_none() {
+        let price = Price::per_kilogram(Credits::new(UnitT::MAX));
+        assert_eq!(price.cost(Mass::from_kilograms(1)), Some(Credits::new(UnitT::MAX)));
+        assert_eq!(price.cost(Mass::from_kilograms(2)), None);
+        assert_eq!(Credits::new(UnitT::MAX).checked_add(Credits::new(1)), None);
+        assert_eq!(Credits::new(UnitT::MIN).checked_sub(Credits::new(1)), None);
+    }
+}
@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use crate::units::Density;
+use crate::{market::Price, units::Density};
 
 use super::ThermalProperties;
 
@@ -8,15 +8,17 @@ pub struct BasicMaterialProperties {
     pub name: String,
     pub density: Density,
     pub thermal_properties: ThermalProperties,
+    pub base_value: Price,
 }
 
 impl Display for BasicMaterialProperties {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
-            "Name:\t\t{}\nDensity:\t{}\nThermals:\t{}\n",
+            "Name:\t\t{}\nDensity:\t{}\nThermals:\t{}\nValue:\t\t{}\n",
             self.name.as_str(),
             self.density,
-            self.thermal_properties
+            self.thermal_properties,
+            self.base_value
         ))
     }
 }
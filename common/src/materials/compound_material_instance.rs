@@ -27,7 +27,7 @@ impl CompoundMaterialInstance {
 
 fn calculate_compound_density(
     properties: &MaterialID,
-    ratios: &Vec<f32>,
+    ratios: &[f32],
     material_manager: &MaterialManager,
 ) -> Density {
     let properties = material_manager
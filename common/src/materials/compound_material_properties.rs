@@ -1,5 +1,7 @@
 use std::fmt::Display;
 
+use crate::market::Price;
+
 use super::material_manager::MaterialID;
 
 pub struct ComposingMaterial {
@@ -20,11 +22,16 @@ impl Display for ComposingMaterial {
 pub struct CompoundMaterialProperties {
     pub name: String,
     pub composition: Vec<ComposingMaterial>,
+    pub base_value: Price,
+    pub hardness: f32,
 }
 
 impl Display for CompoundMaterialProperties {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let _ = f.write_fmt(format_args!("Name: {}\nComposition: [\n", self.name));
+        let _ = f.write_fmt(format_args!(
+            "Name: {}\nValue: {}\nHardness: {:.1}\nComposition: [\n",
+            self.name, self.base_value, self.hardness
+        ));
         for component in &self.composition {
             let _ = f.write_fmt(format_args!("\t{}\n", component));
         }
@@ -1,16 +1,31 @@
 use std::{collections::HashMap, fmt::Debug, fmt::Display};
 
 use bevy::ecs::system::Resource;
-use rand::{distributions::Standard, thread_rng, Rng};
+use rand::{thread_rng, Rng};
 use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
 
 use super::{
     BasicMaterialInstance, CompoundMaterialInstance, MaterialInstance, MaterialProperties,
 };
 
-#[derive(Default, Hash, PartialEq, Eq, Clone, Copy)]
+#[derive(Default, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
 pub struct MaterialID(u64);
 
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+impl MaterialID {
+    // IDs are derived from the material name (FNV-1a) rather than picked at random so that the
+    // client and server agree on them without having to exchange a table
+    pub fn from_name(name: &str) -> Self {
+        MaterialID(fnv1a(FNV_OFFSET_BASIS, name.as_bytes()))
+    }
+}
+
 impl Display for MaterialID {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!("MATERIAL_ID({:04x})", self.0 >> 48))
@@ -23,27 +38,22 @@ impl Debug for MaterialID {
     }
 }
 
-impl Distribution<MaterialID> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> MaterialID {
-        MaterialID(rng.gen())
-    }
-}
-
 #[derive(Default, Resource)]
 pub struct MaterialManager {
     materials: HashMap<MaterialID, MaterialProperties>,
+    content_hash: Option<u64>,
 }
 
 impl MaterialManager {
     pub fn register_material(&mut self, material: MaterialProperties) {
+        // Registering a material with the same name again replaces the old definition
         let id = {
-            let mut id: MaterialID;
-            let mut random = thread_rng();
-            loop {
-                id = random.gen::<MaterialID>();
-                if !self.materials.contains_key(&id) {
+            let mut id = MaterialID::from_name(material.name());
+            while let Some(existing) = self.materials.get(&id) {
+                if existing.name() == material.name() {
                     break;
                 }
+                id = MaterialID(id.0.wrapping_add(1));
             }
             id
         };
@@ -51,43 +61,59 @@ impl MaterialManager {
         self.materials.insert(id, material);
     }
 
+    // Clients and servers with different material definitions would disagree about what every
+    // material ID means, so the handshake checks this matches. Only what was parsed is hashed, in
+    // ID order, so formatting, comments and the order of the definitions don't matter
+    pub fn update_content_hash(&mut self) {
+        let mut ids: Vec<&MaterialID> = self.materials.keys().collect();
+        ids.sort();
+        let hash = ids.into_iter().fold(FNV_OFFSET_BASIS, |hash, id| {
+            let hash = fnv1a(hash, &id.0.to_le_bytes());
+            fnv1a(hash, &definition_bytes(&self.materials[id]))
+        });
+        self.content_hash = Some(hash);
+    }
+
+    pub fn content_hash(&self) -> Option<u64> {
+        self.content_hash
+    }
+
     pub fn get_material(&self, id: MaterialID) -> Option<&MaterialProperties> {
         self.materials.get(&id)
     }
 
     pub fn get_material_id(&self, name: &str) -> Option<MaterialID> {
-        for (id, material) in &self.materials {
-            match material {
-                MaterialProperties::Basic(mat) => {
-                    if mat.name == name {
-                        return Some(*id);
-                    }
-                }
-                MaterialProperties::Compound(mat) => {
-                    if mat.name == name {
-                        return Some(*id);
-                    }
-                }
-            }
-        }
+        self.materials
+            .iter()
+            .find(|(_, material)| material.name() == name)
+            .map(|(id, _)| *id)
+    }
 
-        None
+    pub fn materials(&self) -> impl Iterator<Item = (&MaterialID, &MaterialProperties)> {
+        self.materials.iter()
     }
 
     pub fn generate_material_instance(&self, id: MaterialID) -> Option<MaterialInstance> {
+        self.generate_material_instance_with_rng(id, &mut thread_rng())
+    }
+
+    pub fn generate_material_instance_with_rng<R: Rng + ?Sized>(
+        &self,
+        id: MaterialID,
+        random: &mut R,
+    ) -> Option<MaterialInstance> {
         if let Some(material) = self.materials.get(&id) {
             match material {
                 MaterialProperties::Basic(_) => {
                     Some(MaterialInstance::Basic(BasicMaterialInstance(id)))
                 }
                 MaterialProperties::Compound(mat) => {
-                    let mut random = thread_rng();
                     let ratios: Vec<f32> = mat
                         .composition
                         .iter()
                         .map(|component| {
                             let distribution = Normal::new(component.mean, component.sd).unwrap();
-                            distribution.sample(&mut random)
+                            distribution.sample(random)
                         })
                         .collect();
 
@@ -105,6 +131,38 @@ impl MaterialManager {
     }
 }
 
+fn definition_bytes(material: &MaterialProperties) -> Vec<u8> {
+    // The name is ended with a byte no name contains, so it can't run into the values after it
+    let mut bytes = material.name().as_bytes().to_vec();
+    bytes.push(0);
+    bytes.extend(material.base_value().as_credits_per_kilogram().value().to_le_bytes());
+    match material {
+        MaterialProperties::Basic(mat) => {
+            let thermal_properties = &mat.thermal_properties;
+            let values = [
+                mat.density.mass.as_milligrams(),
+                mat.density.volume.as_cubic_millimetres(),
+                thermal_properties.heat_capacity.energy.as_microjoules(),
+                thermal_properties.heat_capacity.mass.as_milligrams(),
+                thermal_properties.melting_point.as_kelvin(),
+                thermal_properties.boiling_point.as_kelvin(),
+            ];
+            for value in values {
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+        MaterialProperties::Compound(mat) => {
+            bytes.extend(mat.hardness.to_le_bytes());
+            for component in &mat.composition {
+                bytes.extend(component.id.0.to_le_bytes());
+                bytes.extend(component.mean.to_le_bytes());
+                bytes.extend(component.sd.to_le_bytes());
+            }
+        }
+    }
+    bytes
+}
+
 impl Display for MaterialManager {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let _ = f.write_str("Material Manager Contents: \n");
@@ -123,3 +181,50 @@ impl Display for MaterialManager {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        market::{Credits, Price},
+        materials::{BasicMaterialProperties, ThermalProperties},
+        units::{Density, Energy, HeatCapacity, Mass, Temperature, Volume},
+    };
+
+    use super::{MaterialManager, MaterialProperties};
+
+    fn basic(name: &str, melting_point: i64) -> MaterialProperties {
+        MaterialProperties::Basic(BasicMaterialProperties {
+            name: String::from(name),
+            density: Density {
+                mass: Mass::from_kilograms(4_000),
+                volume: Volume::from_cubic_metres(1),
+            },
+            thermal_properties: ThermalProperties {
+                heat_capacity: HeatCapacity {
+                    energy: Energy::from_joules(800),
+                    mass: Mass::from_kilograms(1),
+                },
+                melting_point: Temperature::from_kelvin(melting_point),
+                boiling_point: Temperature::from_kelvin(3_000),
+            },
+            base_value: Price::per_kilogram(Credits::new(10)),
+        })
+    }
+
+    fn content_hash(materials: Vec<MaterialProperties>) -> u64 {
+        let mut material_manager = MaterialManager::default();
+        for material in materials {
+            material_manager.register_material(material);
+        }
+        material_manager.update_content_hash();
+        material_manager.content_hash().unwrap()
+    }
+
+    #[test]
+    fn content_hash_covers_definitions_in_any_order() {
+        let hash = content_hash(vec![basic("Iron", 1_800), basic("Nickel", 1_700)]);
+        assert_eq!(hash, content_hash(vec![basic("Nickel", 1_700), basic("Iron", 1_800)]));
+        assert_ne!(hash, content_hash(vec![basic("Iron", 1_801), basic("Nickel", 1_700)]));
+        assert_ne!(hash, content_hash(vec![basic("Iron", 1_800)]));
+    }
+}
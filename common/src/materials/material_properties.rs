@@ -1,6 +1,24 @@
+use crate::market::Price;
+
 use super::{BasicMaterialProperties, CompoundMaterialProperties};
 
 pub enum MaterialProperties {
     Basic(BasicMaterialProperties),
     Compound(CompoundMaterialProperties),
 }
+
+impl MaterialProperties {
+    pub fn name(&self) -> &str {
+        match self {
+            MaterialProperties::Basic(mat) => mat.name.as_str(),
+            MaterialProperties::Compound(mat) => mat.name.as_str(),
+        }
+    }
+
+    pub fn base_value(&self) -> Price {
+        match self {
+            MaterialProperties::Basic(mat) => mat.base_value,
+            MaterialProperties::Compound(mat) => mat.base_value,
+        }
+    }
+}
@@ -1,13 +1,17 @@
 use bevy::{
-    app::{Plugin, PostStartup},
-    asset::{self, Assets, UntypedHandle},
-    ecs::system::{Res, ResMut, Resource},
+    app::{Plugin, Update},
+    asset::{Assets, UntypedHandle},
+    ecs::{
+        schedule::{common_conditions::resource_changed, IntoSystemConfigs},
+        system::{Res, ResMut, Resource},
+    },
     log::warn,
 };
 use bevy_asset_loader::asset_collection::AssetCollection;
 use kdl::KdlNode;
 
 use crate::loaders::KdlAsset;
+use crate::market::{Credits, Price};
 use crate::units::{Density, Energy, HeatCapacity, Mass, Temperature, Volume};
 
 use super::{
@@ -21,7 +25,10 @@ impl Plugin for MaterialsPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.init_resource::<MaterialManager>();
         app.init_resource::<MaterialConfigs>();
-        app.add_systems(PostStartup, load_materials);
+        app.add_systems(
+            Update,
+            load_materials.run_if(resource_changed::<MaterialConfigs>()),
+        );
     }
 }
 
@@ -46,6 +53,24 @@ fn create_compound_material_from_config(
             .as_string()
             .unwrap();
 
+        let base_value = children
+            .get("base_value")
+            .unwrap()
+            .get(0)
+            .unwrap()
+            .value()
+            .as_i64()
+            .unwrap();
+
+        let hardness = children
+            .get("hardness")
+            .unwrap()
+            .get(0)
+            .unwrap()
+            .value()
+            .as_f64()
+            .unwrap() as f32;
+
         let composition = children.get("composition").unwrap().children().unwrap();
         let composing_materials: Vec<ComposingMaterial> =
             composition.nodes().iter().filter_map(|node| {
@@ -79,6 +104,8 @@ fn create_compound_material_from_config(
             CompoundMaterialProperties {
                 name: String::from(name),
                 composition: composing_materials,
+                base_value: Price::per_kilogram(Credits::new(base_value)),
+                hardness,
             },
         ))
     }
@@ -108,6 +135,15 @@ fn create_basic_material_from_config(
             .as_i64()
             .unwrap();
 
+        let base_value = children
+            .get("base_value")
+            .unwrap()
+            .get(0)
+            .unwrap()
+            .value()
+            .as_i64()
+            .unwrap();
+
         let thermal_properties = children
             .get("thermal_properties")
             .unwrap()
@@ -155,19 +191,22 @@ fn create_basic_material_from_config(
                 melting_point: Temperature::from_kelvin(melting_point),
                 boiling_point: Temperature::from_kelvin(boiling_point),
             },
+            base_value: Price::per_kilogram(Credits::new(base_value)),
         }))
     }
 }
 
-fn load_materials(
+pub fn load_materials(
     loaded_files: Res<MaterialConfigs>,
     config_assets: Res<Assets<KdlAsset>>,
     mut material_manager: ResMut<MaterialManager>,
 ) {
+    let mut loaded = false;
     for handle in &loaded_files.configs {
         let opt_asset = config_assets.get(handle);
         if let Some(asset) = opt_asset {
             let document = &asset.0;
+            loaded = true;
 
             for node in document.nodes() {
                 let node_name = node.name().to_string();
@@ -185,9 +224,8 @@ fn load_materials(
             }
         }
     }
-}
 
-fn test_materials(material_manager: Res<MaterialManager>) {
-    let ilmenite = material_manager.get_material_id("Ilmenite").unwrap();
-    let instance = material_manager.generate_material_instance(ilmenite);
+    if loaded {
+        material_manager.update_content_hash();
+    }
 }
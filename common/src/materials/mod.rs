@@ -17,6 +17,7 @@ pub use self::material_instance::MaterialInstance;
 pub use self::material_manager::MaterialID;
 pub use self::material_manager::MaterialManager;
 pub use self::material_properties::MaterialProperties;
+pub use self::materials_plugin::load_materials;
 pub use self::materials_plugin::MaterialConfigs;
 pub use self::materials_plugin::MaterialsPlugin;
 pub use self::thermal_properties::ThermalProperties;
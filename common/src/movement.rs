@@ -0,0 +1,25 @@
+use bevy::math::Vec3;
+
+use crate::network::events::PlayerInput;
+
+pub const PLAYER_SPEED: f32 = 50.0;
+
+// The server and the client's prediction both move players with this, so the same inputs always
+// end up in the same place
+pub fn apply_input(input: &PlayerInput, delta_seconds: f32, translation: Vec3) -> Vec3 {
+    let mut direction = Vec3::ZERO;
+    if input.left > 0 {
+        direction.x -= 1.0;
+    }
+    if input.right > 0 {
+        direction.x += 1.0;
+    }
+    if input.backward > 0 {
+        direction.y -= 1.0;
+    }
+    if input.forward > 0 {
+        direction.y += 1.0;
+    }
+
+    translation + direction * PLAYER_SPEED * delta_seconds
+}
@@ -0,0 +1,184 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy_renet::renet::{ChannelConfig, ConnectionConfig, SendType};
+
+use super::message::{Envelope, MessageTypeID};
+
+pub const MAX_CHANNEL_MEMORY: usize = 5 * 1024 * 1024;
+// How long a reliable message waits to be acknowledged before it's sent again
+pub const RESEND_TIME: Duration = Duration::from_millis(300);
+const AVAILABLE_BYTES_PER_TICK: u64 = 60_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageChannel {
+    // Fire and forget, for messages where every copy is still worth having
+    Unreliable,
+    // Fire and forget, but anything older than the newest message already received is dropped. Use
+    // this for state that's resent whenever it changes, like positions
+    UnreliableSequenced,
+    ReliableUnordered,
+    ReliableOrdered,
+}
+
+impl MessageChannel {
+    pub const ALL: [MessageChannel; 4] = [
+        MessageChannel::Unreliable,
+        MessageChannel::UnreliableSequenced,
+        MessageChannel::ReliableUnordered,
+        MessageChannel::ReliableOrdered,
+    ];
+
+    pub const fn is_sequenced(self) -> bool {
+        matches!(self, MessageChannel::UnreliableSequenced)
+    }
+
+    fn send_type(self) -> SendType {
+        match self {
+            MessageChannel::Unreliable | MessageChannel::UnreliableSequenced => {
+                SendType::Unreliable
+            }
+            MessageChannel::ReliableUnordered => SendType::ReliableUnordered {
+                resend_time: RESEND_TIME,
+            },
+            MessageChannel::ReliableOrdered => SendType::ReliableOrdered {
+                resend_time: RESEND_TIME,
+            },
+        }
+    }
+}
+
+impl From<MessageChannel> for u8 {
+    fn from(channel: MessageChannel) -> Self {
+        channel as u8
+    }
+}
+
+// Both sides have to be created with the same config or the connection will be rejected
+pub fn connection_config() -> ConnectionConfig {
+    let channels: Vec<ChannelConfig> = MessageChannel::ALL
+        .iter()
+        .map(|channel| ChannelConfig {
+            channel_id: (*channel).into(),
+            max_memory_usage_bytes: MAX_CHANNEL_MEMORY,
+            send_type: channel.send_type(),
+        })
+        .collect();
+
+    ConnectionConfig {
+        available_bytes_per_tick: AVAILABLE_BYTES_PER_TICK,
+        server_channels_config: channels.clone(),
+        client_channels_config: channels,
+    }
+}
+
+pub type Sequence = u16;
+
+const SEQUENCE_LENGTH: usize = std::mem::size_of::<Sequence>();
+
+// Handles wrap around, so a sequence just after the wrap is still newer than one just before it
+pub fn sequence_is_newer(sequence: Sequence, than: Sequence) -> bool {
+    sequence != than && sequence.wrapping_sub(than) < Sequence::MAX / 2
+}
+
+// Numbers the messages of one type sent to one peer. Each is one more than the last however long
+// ago that was sent, so only a message overtaken by a later one of its type is ever stale
+#[derive(Default)]
+pub struct OutgoingSequence(Sequence);
+
+impl OutgoingSequence {
+    pub fn advance(&mut self) -> Sequence {
+        self.0 = self.0.wrapping_add(1);
+        self.0
+    }
+}
+
+// Remembers the newest sequence received from one peer for each type of message. Each type is
+// resent on its own schedule, so a newer message of one type doesn't make an older message of
+// another type stale
+#[derive(Default)]
+pub struct IncomingSequence {
+    latest: HashMap<MessageTypeID, Sequence>,
+}
+
+impl IncomingSequence {
+    // A message too short to have a type is let through for the dispatcher to reject
+    pub fn accept(&mut self, sequence: Sequence, message: &[u8]) -> bool {
+        let Ok(envelope) = Envelope::read(message) else {
+            return true;
+        };
+        match self.latest.get(&envelope.message_type) {
+            Some(latest) if !sequence_is_newer(sequence, *latest) => false,
+            _ => {
+                self.latest.insert(envelope.message_type, sequence);
+                true
+            }
+        }
+    }
+}
+
+pub fn write_sequenced(sequence: Sequence, message: Vec<u8>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(SEQUENCE_LENGTH + message.len());
+    bytes.extend_from_slice(&sequence.to_le_bytes());
+    bytes.extend(message);
+    bytes
+}
+
+pub fn read_sequenced(bytes: &[u8]) -> Option<(Sequence, &[u8])> {
+    if bytes.len() < SEQUENCE_LENGTH {
+        return None;
+    }
+    let (sequence, message) = bytes.split_at(SEQUENCE_LENGTH);
+    Some((Sequence::from_le_bytes([sequence[0], sequence[1]]), message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_sequenced, write_sequenced, IncomingSequence, OutgoingSequence, Sequence};
+
+    const SNAPSHOT: [u8; 2] = [1, 0];
+    const ACKNOWLEDGEMENT: [u8; 2] = [2, 0];
+
+    #[test]
+    fn stale_sequences_are_dropped() {
+        let mut incoming = IncomingSequence::default();
+        assert!(incoming.accept(5, &SNAPSHOT));
+        assert!(!incoming.accept(5, &SNAPSHOT));
+        assert!(incoming.accept(7, &SNAPSHOT));
+        assert!(!incoming.accept(6, &SNAPSHOT));
+        assert!(incoming.accept(8, &SNAPSHOT));
+    }
+
+    #[test]
+    fn each_message_type_is_sequenced_on_its_own() {
+        let mut incoming = IncomingSequence::default();
+        assert!(incoming.accept(7, &ACKNOWLEDGEMENT));
+        assert!(incoming.accept(5, &SNAPSHOT));
+        assert!(!incoming.accept(6, &ACKNOWLEDGEMENT));
+        assert!(incoming.accept(6, &SNAPSHOT));
+        assert!(incoming.accept(0, &[]));
+    }
+
+    #[test]
+    fn sequences_wrap_around() {
+        let mut incoming = IncomingSequence::default();
+        assert!(incoming.accept(Sequence::MAX, &SNAPSHOT));
+        assert!(incoming.accept(0, &SNAPSHOT));
+        assert!(!incoming.accept(Sequence::MAX - 1, &SNAPSHOT));
+    }
+
+    #[test]
+    fn every_message_sent_in_order_is_accepted() {
+        let mut outgoing = OutgoingSequence::default();
+        let mut incoming = IncomingSequence::default();
+        for _ in 0..3 * Sequence::MAX as u32 {
+            assert!(incoming.accept(outgoing.advance(), &SNAPSHOT));
+        }
+    }
+
+    #[test]
+    fn sequenced_messages_round_trip() {
+        let bytes = write_sequenced(300, vec![1, 2, 3]);
+        assert_eq!(read_sequenced(&bytes), Some((300, &[1u8, 2, 3][..])));
+        assert_eq!(read_sequenced(&[1]), None);
+    }
+}
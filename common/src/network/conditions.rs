@@ -0,0 +1,194 @@
+use bevy::ecs::system::Resource;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::channels::{MessageChannel, RESEND_TIME};
+
+// How a simulated connection treats the messages arriving over it. Latency and jitter are in
+// seconds, loss and duplicates are the chance of it happening to each message
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NetworkConditions {
+    pub latency: f64,
+    pub jitter: f64,
+    pub loss: f64,
+    pub duplicates: f64,
+}
+
+impl NetworkConditions {
+    pub fn profile(name: &str) -> Option<Self> {
+        let (latency, jitter, loss, duplicates) = match name {
+            "none" => (0.0, 0.0, 0.0, 0.0),
+            "lan" => (0.002, 0.001, 0.0, 0.0),
+            "broadband" => (0.03, 0.005, 0.005, 0.0),
+            "wifi" => (0.04, 0.02, 0.02, 0.002),
+            "mobile" => (0.1, 0.04, 0.05, 0.01),
+            "terrible" => (0.3, 0.1, 0.2, 0.05),
+            _ => return None,
+        };
+        Some(Self {
+            latency,
+            jitter,
+            loss,
+            duplicates,
+        })
+    }
+
+    pub fn is_perfect(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+struct DelayedMessage<C> {
+    deliver_at: f64,
+    sender: C,
+    channel: MessageChannel,
+    bytes: Vec<u8>,
+}
+
+// Holds back messages as they're received to make a loopback connection behave like a real one.
+// Only messages are simulated, not packets, so the reliable channels still get everything once:
+// a lost message there costs a resend instead, and the ordered one is held up behind it
+#[derive(Resource)]
+pub struct SimulatedLink<C: Send + Sync + 'static> {
+    conditions: NetworkConditions,
+    queue: Vec<DelayedMessage<C>>,
+    random: StdRng,
+}
+
+impl<C: Copy + PartialEq + Send + Sync + 'static> SimulatedLink<C> {
+    pub fn new(conditions: NetworkConditions) -> Self {
+        Self {
+            conditions,
+            queue: Vec::new(),
+            random: StdRng::from_entropy(),
+        }
+    }
+
+    // Takes the messages that have just arrived and returns the ones that are due, oldest first
+    pub fn simulate(
+        &mut self,
+        now: f64,
+        received: Vec<(C, MessageChannel, Vec<u8>)>,
+    ) -> Vec<(C, MessageChannel, Vec<u8>)> {
+        for (sender, channel, bytes) in received {
+            self.receive(now, sender, channel, bytes);
+        }
+
+        self.queue.sort_by(|first, second| first.deliver_at.total_cmp(&second.deliver_at));
+        let due = self
+            .queue
+            .iter()
+            .take_while(|message| message.deliver_at <= now)
+            .count();
+        self.queue
+            .drain(..due)
+            .map(|message| (message.sender, message.channel, message.bytes))
+            .collect()
+    }
+
+    // Messages still held back when a connection ends would otherwise arrive on the next one
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+
+    // For when one sender's connection ends and the rest carry on
+    pub fn clear_sender(&mut self, sender: C) {
+        self.queue.retain(|message| message.sender != sender);
+    }
+
+    fn receive(&mut self, now: f64, sender: C, channel: MessageChannel, bytes: Vec<u8>) {
+        let mut deliver_at = now + self.delay();
+        match channel {
+            MessageChannel::Unreliable | MessageChannel::UnreliableSequenced => {
+                if self.random.gen_bool(self.conditions.loss) {
+                    return;
+                }
+                if self.random.gen_bool(self.conditions.duplicates) {
+                    let deliver_at = now + self.delay();
+                    self.queue.push(DelayedMessage {
+                        deliver_at,
+                        sender,
+                        channel,
+                        bytes: bytes.clone(),
+                    });
+                }
+            }
+            MessageChannel::ReliableUnordered | MessageChannel::ReliableOrdered => {
+                while self.random.gen_bool(self.conditions.loss) {
+                    deliver_at += RESEND_TIME.as_secs_f64();
+                }
+            }
+        }
+
+        if channel == MessageChannel::ReliableOrdered {
+            deliver_at = self
+                .queue
+                .iter()
+                .filter(|message| message.sender == sender && message.channel == channel)
+                .map(|message| message.deliver_at)
+                .fold(deliver_at, f64::max);
+        }
+
+        self.queue.push(DelayedMessage {
+            deliver_at,
+            sender,
+            channel,
+            bytes,
+        });
+    }
+
+    fn delay(&mut self) -> f64 {
+        let jitter = match self.conditions.jitter {
+            jitter if jitter > 0.0 => self.random.gen_range(-jitter..jitter),
+            _ => 0.0,
+        };
+        (self.conditions.latency + jitter).max(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NetworkConditions, SimulatedLink};
+    use crate::network::channels::MessageChannel;
+
+    #[test]
+    fn ordered_messages_stay_in_order() {
+        let conditions = NetworkConditions {
+            latency: 0.1,
+            jitter: 0.09,
+            loss: 0.5,
+            duplicates: 0.5,
+        };
+        let mut link = SimulatedLink::<u8>::new(conditions);
+
+        let received = (0..50u8)
+            .map(|index| (0, MessageChannel::ReliableOrdered, vec![index]))
+            .collect();
+        assert!(link.simulate(0.0, received).is_empty());
+
+        let delivered: Vec<u8> = link
+            .simulate(1000.0, Vec::new())
+            .into_iter()
+            .map(|(_, _, bytes)| bytes[0])
+            .collect();
+        assert_eq!(delivered, (0..50).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn clearing_a_sender_leaves_everyone_else_queued() {
+        let conditions = NetworkConditions::profile("lan").unwrap();
+        let mut link = SimulatedLink::<u8>::new(conditions);
+
+        let received = vec![
+            (1, MessageChannel::ReliableOrdered, vec![1]),
+            (2, MessageChannel::ReliableOrdered, vec![2]),
+        ];
+        assert!(link.simulate(0.0, received).is_empty());
+        link.clear_sender(1);
+
+        let delivered = link.simulate(1.0, Vec::new());
+        assert_eq!(
+            delivered,
+            vec![(2, MessageChannel::ReliableOrdered, vec![2])]
+        );
+    }
+}
@@ -1,11 +1,30 @@
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::net::{IpAddr, Ipv4Addr};
 
 pub const LOCAL_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 pub const SERVER_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 pub const SERVER_TO_CLIENT_PORT: u16 = 0;
 pub const CLIENT_TO_SERVER_PORT: u16 = 43735;
 
-pub const SERVER_SOCKET_ADDRESS: SocketAddr = SocketAddr::new(LOCAL_ADDRESS, CLIENT_TO_SERVER_PORT);
-pub const CLIENT_SOCKET_ADDRESS: SocketAddr = SocketAddr::new(LOCAL_ADDRESS, SERVER_TO_CLIENT_PORT);
+// Only tells netcode which game a connection is for. It stays the same across versions so that
+// mismatched builds still connect and the handshake can explain what's wrong
+pub const PROTOCOL_ID: u64 = 0x6f72_6562_656c_7401;
 
-pub const PROTOCOL_ID: u64 = crate::version_num() as u64;
+// Snapshots keep each client within a bandwidth budget, so this is limited by the server's uplink
+// rather than by how many entities there are
+pub const MAX_CLIENTS: usize = 256;
+
+
+// The server simulates in fixed steps of this length unless told otherwise, and state updates are
+// stamped with the number of steps it has run
+pub const TICK_SECONDS: f64 = 0.1;
+
+pub type Tick = u64;
+
+// The server can host several separate worlds at once. Players start off in the default one
+pub type WorldID = u64;
+
+pub const DEFAULT_WORLD: WorldID = 0;
+
+// Clients ask the login service on this port for a connect token before connecting to the game
+// server
+pub const LOGIN_PORT: u16 = 43736;
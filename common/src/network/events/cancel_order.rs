@@ -0,0 +1,16 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::market::OrderID;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct CancelOrder {
+    pub order: OrderID,
+}
+
+impl Display for CancelOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("CancelOrder {}", self.order))
+    }
+}
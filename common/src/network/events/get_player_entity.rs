@@ -2,9 +2,12 @@ use std::fmt::Display;
 
 use serde::{Deserialize, Serialize};
 
+use crate::network::{configuration::WorldID, rpc::RequestID};
+
 #[derive(Serialize, Deserialize, Clone, Copy)]
 pub struct GetPlayerEntity {
-    pub world: u64,
+    pub request: RequestID,
+    pub world: WorldID,
 }
 
 impl Display for GetPlayerEntity {
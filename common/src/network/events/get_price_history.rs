@@ -0,0 +1,22 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{market::CandleResolution, materials::MaterialID, network::rpc::RequestID};
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct GetPriceHistory {
+    pub request: RequestID,
+    pub material: MaterialID,
+    pub resolution: CandleResolution,
+    pub count: u32,
+}
+
+impl Display for GetPriceHistory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "GetPriceHistory for {} ({} x {})",
+            self.material, self.count, self.resolution
+        ))
+    }
+}
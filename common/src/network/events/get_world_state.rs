@@ -1,9 +1,11 @@
 use serde::{Serialize, Deserialize};
 use std::fmt::Display;
 
+use crate::network::configuration::WorldID;
+
 #[derive(Serialize, Deserialize, Clone, Copy)]
 pub struct GetWorldState {
-    pub world: u64,
+    pub world: WorldID,
 }
 
 impl Display for GetWorldState {
@@ -0,0 +1,24 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::handshake::Version;
+
+// The first thing a client sends once connected. Nothing else it sends is looked at until the
+// server has accepted this
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Handshake {
+    pub client_version: Version,
+    pub common_version: Version,
+    pub protocol_version: u32,
+    pub content_hash: u64,
+}
+
+impl Display for Handshake {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "Handshake from client v{} (common v{}, protocol {}, content {:016x})",
+            self.client_version, self.common_version, self.protocol_version, self.content_hash
+        ))
+    }
+}
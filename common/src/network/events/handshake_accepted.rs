@@ -0,0 +1,21 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::handshake::Version;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct HandshakeAccepted {
+    pub server_version: Version,
+    // Clients simulate at the server's tick rate whatever they were configured with
+    pub tick_rate: f64,
+}
+
+impl Display for HandshakeAccepted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "HandshakeAccepted by server v{} ({} ticks per second)",
+            self.server_version, self.tick_rate
+        ))
+    }
+}
@@ -0,0 +1,38 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::handshake::Version;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandshakeRejection {
+    ProtocolMismatch { server: u32, client: u32 },
+    IncompatibleVersion { server: Version, client: Version },
+    ContentMismatch,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct HandshakeRejected {
+    pub reason: HandshakeRejection,
+}
+
+// Written to be shown to the player as it is
+impl Display for HandshakeRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.reason {
+            HandshakeRejection::ProtocolMismatch { server, client } => f.write_fmt(format_args!(
+                "The server speaks protocol version {} but this client speaks version {}",
+                server, client
+            )),
+            HandshakeRejection::IncompatibleVersion { server, client } => {
+                f.write_fmt(format_args!(
+                    "The server runs version {} which can't play with version {}, update to {}.{}",
+                    server, client, server.major, server.minor
+                ))
+            }
+            HandshakeRejection::ContentMismatch => f.write_str(
+                "The server's game data doesn't match this client's, check both are up to date",
+            ),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use super::InputSequence;
+
+// Sent to a player after the server has processed their inputs, with where those inputs left them
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct InputAcknowledged {
+    pub sequence: InputSequence,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Display for InputAcknowledged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "InputAcknowledged {} = {{ {}, {}, {} }}",
+            self.sequence, self.x, self.y, self.z
+        ))
+    }
+}
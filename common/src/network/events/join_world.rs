@@ -0,0 +1,17 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::configuration::WorldID;
+
+// Moves the player's entity into another world hosted by the server
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct JoinWorld {
+    pub world: WorldID,
+}
+
+impl Display for JoinWorld {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("JoinWorld {}", self.world))
+    }
+}
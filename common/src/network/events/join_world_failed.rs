@@ -0,0 +1,23 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::configuration::WorldID;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinWorldFailure {
+    UnknownWorld,
+    AlreadyInWorld,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct JoinWorldFailed {
+    pub world: WorldID,
+    pub reason: JoinWorldFailure,
+}
+
+impl Display for JoinWorldFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("JoinWorldFailed for {} ({:?})", self.world, self.reason))
+    }
+}
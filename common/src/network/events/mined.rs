@@ -0,0 +1,24 @@
+use std::fmt::Display;
+
+use bevy::ecs::entity::Entity;
+use serde::{Deserialize, Serialize};
+
+use crate::{materials::MaterialID, units::Mass};
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Mined {
+    pub deposit: Entity,
+    pub material: MaterialID,
+    pub extracted: Mass,
+    pub held: Mass,
+    pub remaining: Mass,
+}
+
+impl Display for Mined {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "Mined {} of {} from {:?} ({} held, {} remaining)",
+            self.extracted, self.material, self.deposit, self.held, self.remaining
+        ))
+    }
+}
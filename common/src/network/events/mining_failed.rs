@@ -0,0 +1,20 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MiningFailure {
+    NoDepositInRange,
+    DepositDepleted,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct MiningFailed {
+    pub reason: MiningFailure,
+}
+
+impl Display for MiningFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("MiningFailed ({:?})", self.reason))
+    }
+}
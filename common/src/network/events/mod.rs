@@ -1,28 +1,88 @@
-mod entity_position;
 mod player_input;
-mod create_entity;
-mod destroy_entity;
 mod get_world_state;
 mod get_player_entity;
 mod player_entity;
+mod place_order;
+mod cancel_order;
+mod order_placed;
+mod order_rejected;
+mod order_filled;
+mod order_cancelled;
+mod top_of_book;
+mod get_price_history;
+mod price_history;
+mod mined;
+mod mining_failed;
+mod input_acknowledged;
+mod replication_update;
+mod snapshot;
+mod snapshot_acknowledged;
+mod join_world;
+mod world_joined;
+mod join_world_failed;
+mod handshake;
+mod handshake_accepted;
+mod handshake_rejected;
 
-pub use entity_position::EntityPosition;
-pub use player_input::PlayerInput;
-pub use create_entity::CreateEntity;
-pub use destroy_entity::DestroyEntity;
+pub use player_input::{InputSequence, PlayerInput};
 pub use get_world_state::GetWorldState;
 pub use get_player_entity::GetPlayerEntity;
-pub use player_entity::PlayerEntity;
+pub use player_entity::{PlayerEntity, PlayerEntityFailure};
+pub use place_order::{PlaceOrder, MAX_ORDER_PRICE, MAX_ORDER_QUANTITY};
+pub use cancel_order::CancelOrder;
+pub use order_placed::OrderPlaced;
+pub use order_rejected::{OrderRejected, OrderRejectionReason};
+pub use order_filled::OrderFilled;
+pub use order_cancelled::OrderCancelled;
+pub use top_of_book::TopOfBook;
+pub use get_price_history::GetPriceHistory;
+pub use price_history::PriceHistory;
+pub use mined::Mined;
+pub use mining_failed::{MiningFailed, MiningFailure};
+pub use input_acknowledged::InputAcknowledged;
+pub use replication_update::{ReplicationChange, ReplicationUpdate};
+pub use snapshot::{EntitySnapshot, Snapshot};
+pub use snapshot_acknowledged::SnapshotAcknowledged;
+pub use join_world::JoinWorld;
+pub use world_joined::WorldJoined;
+pub use join_world_failed::{JoinWorldFailed, JoinWorldFailure};
+pub use handshake::Handshake;
+pub use handshake_accepted::HandshakeAccepted;
+pub use handshake_rejected::{HandshakeRejected, HandshakeRejection};
 
 pub enum Events {
     PlayerInput(PlayerInput),
-    EntityPosition(EntityPosition),
 
-    CreateEntity(CreateEntity),
-    DestroyEntity(DestroyEntity),
 
     GetWorldState(GetWorldState),
     
     GetPlayerEntity(GetPlayerEntity),
     PlayerEntity(PlayerEntity),
+
+    PlaceOrder(PlaceOrder),
+    CancelOrder(CancelOrder),
+    OrderPlaced(OrderPlaced),
+    OrderRejected(OrderRejected),
+    OrderFilled(OrderFilled),
+    OrderCancelled(OrderCancelled),
+    TopOfBook(TopOfBook),
+
+    GetPriceHistory(GetPriceHistory),
+    PriceHistory(PriceHistory),
+
+    Mined(Mined),
+    MiningFailed(MiningFailed),
+
+    InputAcknowledged(InputAcknowledged),
+    ReplicationUpdate(ReplicationUpdate),
+    Snapshot(Snapshot),
+    SnapshotAcknowledged(SnapshotAcknowledged),
+
+    JoinWorld(JoinWorld),
+    WorldJoined(WorldJoined),
+    JoinWorldFailed(JoinWorldFailed),
+
+    Handshake(Handshake),
+    HandshakeAccepted(HandshakeAccepted),
+    HandshakeRejected(HandshakeRejected),
 }
\ No newline at end of file
@@ -0,0 +1,20 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{market::OrderID, units::Mass};
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct OrderCancelled {
+    pub order: OrderID,
+    pub remaining: Mass,
+}
+
+impl Display for OrderCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "OrderCancelled {} ({} unfilled)",
+            self.order, self.remaining
+        ))
+    }
+}
@@ -0,0 +1,28 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    market::{OrderID, OrderSide, Price},
+    materials::MaterialID,
+    units::Mass,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct OrderFilled {
+    pub order: OrderID,
+    pub material: MaterialID,
+    pub side: OrderSide,
+    pub price: Price,
+    pub quantity: Mass,
+    pub remaining: Mass,
+}
+
+impl Display for OrderFilled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "OrderFilled {} ({} {} {} @ {}, {} remaining)",
+            self.order, self.side, self.quantity, self.material, self.price, self.remaining
+        ))
+    }
+}
@@ -0,0 +1,32 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::materials::MaterialID;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum OrderRejectionReason {
+    InvalidQuantity,
+    InvalidPrice,
+    InsufficientCredits,
+    InsufficientMaterial,
+    UnknownOrder,
+    UnknownMaterial,
+    // The order's total cost doesn't fit in Credits
+    ValueTooLarge,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct OrderRejected {
+    pub material: MaterialID,
+    pub reason: OrderRejectionReason,
+}
+
+impl Display for OrderRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "OrderRejected for {} ({:?})",
+            self.material, self.reason
+        ))
+    }
+}
@@ -0,0 +1,47 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    market::{is_whole_lots, Credits, OrderSide, Price},
+    materials::MaterialID,
+    network::message::MessageError,
+    units::Mass,
+};
+
+// At these limits an order costs at most 10^18 credits, so its cost always fits in Credits
+pub const MAX_ORDER_PRICE: Price = Price::per_kilogram(Credits::new(1_000_000_000));
+pub const MAX_ORDER_QUANTITY: Mass = Mass::from_kilotonnes(1_000);
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct PlaceOrder {
+    pub material: MaterialID,
+    pub side: OrderSide,
+    pub price: Price,
+    pub quantity: Mass,
+}
+
+impl Display for PlaceOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "PlaceOrder {} {} {} @ {}",
+            self.side, self.quantity, self.material, self.price
+        ))
+    }
+}
+
+impl PlaceOrder {
+    // Whether the material exists is up to the server, as it depends on the content it loaded
+    pub fn validate_order(&self) -> Result<(), MessageError> {
+        if self.price <= Price::default() || self.price > MAX_ORDER_PRICE {
+            return Err(MessageError::Invalid("price is out of range"));
+        }
+        if self.quantity <= Mass::default() || self.quantity > MAX_ORDER_QUANTITY {
+            return Err(MessageError::Invalid("quantity is out of range"));
+        }
+        if !is_whole_lots(self.quantity) {
+            return Err(MessageError::Invalid("quantity isn't a whole number of lots"));
+        }
+        Ok(())
+    }
+}
@@ -3,13 +3,26 @@ use std::fmt::Display;
 use serde::{Serialize, Deserialize};
 use bevy::ecs::entity::Entity;
 
+use crate::network::{configuration::WorldID, rpc::RequestID};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayerEntityFailure {
+    // The player is in another world, named so the client can ask about that one instead
+    InOtherWorld(WorldID),
+    NoEntity,
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy)]
 pub struct PlayerEntity {
-    pub entity: Entity
+    pub request: RequestID,
+    pub entity: Result<Entity, PlayerEntityFailure>
 }
 
 impl Display for PlayerEntity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("PlayerEntity {:?}", self.entity))
+        match self.entity {
+            Ok(entity) => f.write_fmt(format_args!("PlayerEntity {:?}", entity)),
+            Err(reason) => f.write_fmt(format_args!("PlayerEntity failed ({:?})", reason)),
+        }
     }
-}
\ No newline at end of file
+}
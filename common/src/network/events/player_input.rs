@@ -3,8 +3,14 @@ use std::fmt::Display;
 use bevy::ecs::{component::Component, system::Resource};
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Serialize, Deserialize, Component, Resource, Clone, Copy, PartialEq, Eq)]
+use crate::network::message::MessageError;
+
+// Numbers each input the client sends so the server can say which ones it has applied
+pub type InputSequence = u32;
+
+#[derive(Default, Serialize, Deserialize, Component, Resource, Clone, Copy, PartialEq, Eq, Debug)]
 pub struct PlayerInput {
+    pub sequence: InputSequence,
     pub left: u8,
     pub right: u8,
     pub forward: u8,
@@ -18,7 +24,8 @@ pub struct PlayerInput {
 impl Display for PlayerInput {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
-            "Input {:03} {:03} {:03} {:03} {:03} {:03} {:03} {:03}",
+            "Input {} = {:03} {:03} {:03} {:03} {:03} {:03} {:03} {:03}",
+            self.sequence,
             self.left,
             self.right,
             self.forward,
@@ -32,6 +39,25 @@ impl Display for PlayerInput {
 }
 
 impl PlayerInput {
+    // Each button is either held or not
+    pub fn validate_buttons(&self) -> Result<(), MessageError> {
+        let buttons = [
+            self.left,
+            self.right,
+            self.forward,
+            self.backward,
+            self.jump,
+            self.crouch,
+            self.primary,
+            self.secondary,
+        ];
+        if buttons.iter().all(|button| *button <= 1) {
+            Ok(())
+        } else {
+            Err(MessageError::Invalid("buttons must be 0 or 1"))
+        }
+    }
+
     pub fn any(self) -> bool {
         self.left != 0 || self.right != 0 || self.forward != 0 || self.backward != 0 || self.jump != 0 || self.crouch != 0 || self.primary != 0 || self.secondary != 0
     }
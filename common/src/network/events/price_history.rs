@@ -0,0 +1,28 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    market::{Candle, CandleResolution},
+    materials::MaterialID,
+    network::rpc::RequestID,
+};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PriceHistory {
+    pub request: RequestID,
+    pub material: MaterialID,
+    pub resolution: CandleResolution,
+    pub candles: Vec<Candle>,
+}
+
+impl Display for PriceHistory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "PriceHistory for {} ({} x {})",
+            self.material,
+            self.candles.len(),
+            self.resolution
+        ))
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt::Display;
+
+use bevy::ecs::entity::Entity;
+use serde::{Deserialize, Serialize};
+
+use crate::network::{configuration::Tick, replication::ComponentID};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ReplicationChange {
+    Spawn(Entity),
+    Despawn(Entity),
+    Insert {
+        entity: Entity,
+        component: ComponentID,
+        data: Vec<u8>,
+    },
+    Update {
+        entity: Entity,
+        component: ComponentID,
+        data: Vec<u8>,
+    },
+    Remove {
+        entity: Entity,
+        component: ComponentID,
+    },
+}
+
+// Everything about the replicated entities that changed for one client since the last update
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ReplicationUpdate {
+    pub tick: Tick,
+    pub changes: Vec<ReplicationChange>,
+}
+
+impl Display for ReplicationUpdate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "ReplicationUpdate at tick {} ({} changes)",
+            self.tick,
+            self.changes.len()
+        ))
+    }
+}
@@ -0,0 +1,40 @@
+use std::fmt::Display;
+
+use bevy::ecs::entity::Entity;
+use serde::{Deserialize, Serialize};
+
+use crate::network::{configuration::Tick, snapshot::QuantisedPosition};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum EntitySnapshot {
+    Full {
+        entity: Entity,
+        position: QuantisedPosition,
+    },
+    Delta {
+        entity: Entity,
+        delta: QuantisedPosition,
+    },
+}
+
+// The positions that changed for one client in a tick, relative to the last snapshot it
+// acknowledged. Entities that are in `baseline` and aren't listed haven't moved
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Snapshot {
+    pub tick: Tick,
+    pub baseline: Option<Tick>,
+    pub entities: Vec<EntitySnapshot>,
+    pub removed: Vec<Entity>,
+}
+
+impl Display for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "Snapshot at tick {} against {:?} ({} entities, {} removed)",
+            self.tick,
+            self.baseline,
+            self.entities.len(),
+            self.removed.len()
+        ))
+    }
+}
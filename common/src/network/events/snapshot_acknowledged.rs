@@ -0,0 +1,16 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::configuration::Tick;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct SnapshotAcknowledged {
+    pub tick: Tick,
+}
+
+impl Display for SnapshotAcknowledged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("SnapshotAcknowledged {}", self.tick))
+    }
+}
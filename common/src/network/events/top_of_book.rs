@@ -0,0 +1,25 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{market::Price, materials::MaterialID, units::Mass};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct TopOfBook {
+    pub material: MaterialID,
+    pub bid: Option<Price>,
+    pub bid_quantity: Mass,
+    pub ask: Option<Price>,
+    pub ask_quantity: Mass,
+}
+
+impl Display for TopOfBook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bid = self.bid.map_or(String::from("-"), |price| price.to_string());
+        let ask = self.ask.map_or(String::from("-"), |price| price.to_string());
+        f.write_fmt(format_args!(
+            "TopOfBook {} = {{ {} x {}, {} x {} }}",
+            self.material, bid, self.bid_quantity, ask, self.ask_quantity
+        ))
+    }
+}
@@ -0,0 +1,16 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::configuration::WorldID;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct WorldJoined {
+    pub world: WorldID,
+}
+
+impl Display for WorldJoined {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("WorldJoined {}", self.world))
+    }
+}
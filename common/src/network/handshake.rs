@@ -0,0 +1,112 @@
+use std::fmt::Display;
+
+use pkg_version::{pkg_version_major, pkg_version_minor, pkg_version_patch};
+use serde::{Deserialize, Serialize};
+
+use super::events::{Handshake, HandshakeRejection};
+
+// Bumped whenever a message or the way messages are framed changes in a way older builds can't
+// read. The handshake messages themselves must never change, so that any two builds can at least
+// tell each other why they can't play together
+pub const PROTOCOL_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    // The version of the shared code, which is what decides whether a client and server agree
+    pub const fn common() -> Self {
+        Self {
+            major: pkg_version_major!(),
+            minor: pkg_version_minor!(),
+            patch: pkg_version_patch!(),
+        }
+    }
+
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.split('.').map(|part| part.parse().ok());
+        Some(Self {
+            major: parts.next()??,
+            minor: parts.next()??,
+            patch: parts.next()??,
+        })
+    }
+
+    // Patch releases only fix things, so they can play with each other
+    pub fn is_compatible_with(&self, other: &Version) -> bool {
+        self.major == other.major && self.minor == other.minor
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}.{}.{}", self.major, self.minor, self.patch))
+    }
+}
+
+// What the server checks a client's handshake against, in order of what's most useful to report
+pub fn check_handshake(handshake: &Handshake, content_hash: u64) -> Result<(), HandshakeRejection> {
+    if handshake.protocol_version != PROTOCOL_VERSION {
+        return Err(HandshakeRejection::ProtocolMismatch {
+            server: PROTOCOL_VERSION,
+            client: handshake.protocol_version,
+        });
+    }
+
+    let server_version = Version::common();
+    if !server_version.is_compatible_with(&handshake.common_version) {
+        return Err(HandshakeRejection::IncompatibleVersion {
+            server: server_version,
+            client: handshake.common_version,
+        });
+    }
+
+    if handshake.content_hash != content_hash {
+        return Err(HandshakeRejection::ContentMismatch);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_handshake, Version, PROTOCOL_VERSION};
+    use crate::network::events::{Handshake, HandshakeRejection};
+
+    fn handshake(common_version: Version) -> Handshake {
+        Handshake {
+            client_version: common_version,
+            common_version,
+            protocol_version: PROTOCOL_VERSION,
+            content_hash: 7,
+        }
+    }
+
+    #[test]
+    fn patch_differences_are_compatible() {
+        let current = Version::common();
+        let patched = Version {
+            patch: current.patch + 1,
+            ..current
+        };
+        let next_minor = Version {
+            minor: current.minor + 1,
+            ..current
+        };
+
+        assert!(check_handshake(&handshake(patched), 7).is_ok());
+        assert!(matches!(
+            check_handshake(&handshake(next_minor), 7),
+            Err(HandshakeRejection::IncompatibleVersion { .. })
+        ));
+        assert!(matches!(
+            check_handshake(&handshake(current), 8),
+            Err(HandshakeRejection::ContentMismatch)
+        ));
+        assert_eq!(Version::parse("1.20.3"), Some(Version { major: 1, minor: 20, patch: 3 }));
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream},
+    path::Path,
+    time::Duration,
+};
+
+use bevy::utils::thiserror::Error;
+use bevy_renet::renet::transport::{ConnectToken, NetcodeError, NETCODE_USER_DATA_BYTES};
+use bincode::error::{DecodeError, EncodeError};
+use rand::{thread_rng, Rng};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub const MAX_ACCOUNT_NAME_LENGTH: usize = 32;
+pub const MAX_SECRET_LENGTH: usize = 64;
+// Nothing sent to or from the login service comes close to this, so anything longer is garbage
+const MAX_FRAME_LENGTH: u32 = 4096;
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub account: String,
+    pub secret: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoginRejection {
+    InvalidAccountName,
+    InvalidSecret,
+    // The account was claimed with a different secret
+    WrongSecret,
+    TokenUnavailable,
+    // Too many logins are already being handled from the same address
+    TooManyConnections,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum LoginResponse {
+    Accepted { token: Vec<u8> },
+    Rejected(LoginRejection),
+}
+
+#[derive(Error, Debug)]
+pub enum LoginError {
+    #[error("Failed to talk to the login service ({0})")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to encode a login frame ({0})")]
+    Encode(#[from] EncodeError),
+    #[error("Failed to decode a login frame ({0})")]
+    Decode(#[from] DecodeError),
+    #[error("Login frame of {0} bytes is too long")]
+    FrameTooLong(u32),
+    #[error("Login was rejected ({0:?})")]
+    Rejected(LoginRejection),
+    #[error("Login service sent an invalid connect token ({0})")]
+    InvalidToken(#[from] NetcodeError),
+}
+
+pub fn is_valid_account_name(account: &str) -> bool {
+    !account.is_empty()
+        && account.len() <= MAX_ACCOUNT_NAME_LENGTH
+        && account
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || "_-".contains(character))
+}
+
+pub fn is_valid_secret(secret: &str) -> bool {
+    !secret.is_empty() && secret.len() <= MAX_SECRET_LENGTH
+}
+
+// A secret is made up the first time an account is played from a machine and kept next to the
+// client, so only that machine can log in as the account afterwards
+pub fn load_or_create_secret(path: &Path) -> io::Result<String> {
+    match std::fs::read_to_string(path) {
+        Ok(secret) => return Ok(secret.trim().to_string()),
+        Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+        Err(_) => {}
+    }
+
+    let bytes: [u8; 16] = thread_rng().gen();
+    let secret: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    std::fs::write(path, &secret)?;
+    Ok(secret)
+}
+
+// The account a token was issued for travels inside it as the netcode user data, so the game server
+// knows who connected without having to ask the login service
+pub fn account_user_data(account: &str) -> [u8; NETCODE_USER_DATA_BYTES] {
+    let mut user_data = [0; NETCODE_USER_DATA_BYTES];
+    let length = account.len().min(MAX_ACCOUNT_NAME_LENGTH);
+    user_data[0] = length as u8;
+    user_data[1..=length].copy_from_slice(&account.as_bytes()[..length]);
+    user_data
+}
+
+pub fn account_from_user_data(user_data: &[u8; NETCODE_USER_DATA_BYTES]) -> Option<String> {
+    let length = user_data[0] as usize;
+    if length == 0 || length > MAX_ACCOUNT_NAME_LENGTH {
+        return None;
+    }
+    String::from_utf8(user_data[1..=length].to_vec()).ok()
+}
+
+// Frames on the login connection are a little-endian length followed by the bincode encoded value
+pub fn write_frame<T: Serialize>(stream: &mut impl Write, value: &T) -> Result<(), LoginError> {
+    let bytes = bincode::serde::encode_to_vec(value, bincode::config::standard())?;
+    stream.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    stream.write_all(&bytes)?;
+    Ok(())
+}
+
+pub fn read_frame<T: DeserializeOwned>(stream: &mut impl Read) -> Result<T, LoginError> {
+    let mut header = [0; 4];
+    stream.read_exact(&mut header)?;
+    let length = u32::from_le_bytes(header);
+    if length > MAX_FRAME_LENGTH {
+        return Err(LoginError::FrameTooLong(length));
+    }
+
+    let mut bytes = vec![0; length as usize];
+    stream.read_exact(&mut bytes)?;
+    let (value, _) = bincode::serde::decode_from_slice(&bytes, bincode::config::standard())?;
+    Ok(value)
+}
+
+pub fn request_connect_token(
+    address: SocketAddr,
+    account: &str,
+    secret: &str,
+) -> Result<ConnectToken, LoginError> {
+    let mut stream = TcpStream::connect_timeout(&address, LOGIN_TIMEOUT)?;
+    stream.set_read_timeout(Some(LOGIN_TIMEOUT))?;
+    stream.set_write_timeout(Some(LOGIN_TIMEOUT))?;
+
+    write_frame(
+        &mut stream,
+        &LoginRequest {
+            account: account.to_string(),
+            secret: secret.to_string(),
+        },
+    )?;
+
+    match read_frame(&mut stream)? {
+        LoginResponse::Accepted { token } => Ok(ConnectToken::read(&mut token.as_slice())?),
+        LoginResponse::Rejected(reason) => Err(LoginError::Rejected(reason)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_renet::renet::transport::NETCODE_USER_DATA_BYTES;
+
+    use super::{account_from_user_data, account_user_data};
+
+    #[test]
+    fn account_survives_user_data() {
+        let user_data = account_user_data("miner_49");
+        assert_eq!(account_from_user_data(&user_data).as_deref(), Some("miner_49"));
+        assert_eq!(account_from_user_data(&[0; NETCODE_USER_DATA_BYTES]), None);
+    }
+}
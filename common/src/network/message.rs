@@ -0,0 +1,75 @@
+use std::fmt::Display;
+
+use bevy::utils::thiserror::Error;
+use bincode::error::{DecodeError, EncodeError};
+use serde::{Deserialize, Serialize};
+
+use super::{channels::MessageChannel, protocol::MessageDirection, replication::ComponentID};
+
+pub type MessageTypeID = u16;
+
+// Every message sent over the network has a fixed ID so the receiver knows how to decode it.
+// These are all declared in `protocol` and an ID must never be reused for a different type
+pub trait NetworkMessage:
+    Display + Serialize + for<'a> Deserialize<'a> + Sync + Send + 'static
+{
+    const TYPE_ID: MessageTypeID;
+    const DIRECTION: MessageDirection;
+    const CHANNEL: MessageChannel;
+
+    // Checks what the type system can't, so a message with values the receiver never expects is
+    // rejected along with ones that can't be decoded
+    fn validate(&self) -> Result<(), MessageError> {
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum MessageError {
+    #[error("Message is too short to contain a header")]
+    MissingHeader,
+    #[error("No handler is registered for message type {0}")]
+    UnknownType(MessageTypeID),
+    #[error("No replicated component is registered with ID {0}")]
+    UnknownComponent(ComponentID),
+    #[error("Failed to encode message ({0})")]
+    Encode(#[from] EncodeError),
+    #[error("Failed to decode message ({0})")]
+    Decode(#[from] DecodeError),
+    #[error("Message is invalid ({0})")]
+    Invalid(&'static str),
+}
+
+const HEADER_LENGTH: usize = std::mem::size_of::<MessageTypeID>();
+
+// A message on the wire is the little-endian type ID followed by the bincode encoded payload
+pub struct Envelope<'a> {
+    pub message_type: MessageTypeID,
+    pub payload: &'a [u8],
+}
+
+impl<'a> Envelope<'a> {
+    pub fn read(bytes: &'a [u8]) -> Result<Self, MessageError> {
+        if bytes.len() < HEADER_LENGTH {
+            return Err(MessageError::MissingHeader);
+        }
+
+        let (header, payload) = bytes.split_at(HEADER_LENGTH);
+        Ok(Self {
+            message_type: MessageTypeID::from_le_bytes([header[0], header[1]]),
+            payload,
+        })
+    }
+
+    pub fn decode<T: NetworkMessage>(&self) -> Result<T, MessageError> {
+        let (message, _) =
+            bincode::serde::decode_from_slice(self.payload, bincode::config::standard())?;
+        Ok(message)
+    }
+}
+
+pub fn encode_message<T: NetworkMessage>(message: &T) -> Result<Vec<u8>, MessageError> {
+    let mut bytes = T::TYPE_ID.to_le_bytes().to_vec();
+    bytes.extend(bincode::serde::encode_to_vec(message, bincode::config::standard())?);
+    Ok(bytes)
+}
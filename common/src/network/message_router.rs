@@ -0,0 +1,181 @@
+use bevy::{
+    ecs::{event::Event, system::Resource, world::World},
+    utils::HashMap,
+};
+
+use super::message::{Envelope, MessageError, MessageTypeID, NetworkMessage};
+
+type Handler<C> = Box<dyn Fn(&mut World, C, &Envelope) -> Result<(), MessageError> + Send + Sync>;
+
+// Decodes incoming messages by their type ID and sends them on as the matching event. `C` is
+// whatever context the side needs to attach to the event, e.g. the sending client on the server
+#[derive(Resource)]
+pub struct MessageRouter<C: 'static> {
+    handlers: HashMap<MessageTypeID, Handler<C>>,
+}
+
+impl<C: 'static> Default for MessageRouter<C> {
+    fn default() -> Self {
+        Self {
+            handlers: HashMap::default(),
+        }
+    }
+}
+
+impl<C: 'static> MessageRouter<C> {
+    pub fn add<T: NetworkMessage, E: Event>(&mut self, into_event: fn(C, T) -> E) {
+        let handler: Handler<C> = Box::new(move |world, context, envelope| {
+            let message = envelope.decode::<T>()?;
+            message.validate()?;
+            world.send_event(into_event(context, message));
+            Ok(())
+        });
+
+        if self.handlers.insert(T::TYPE_ID, handler).is_some() {
+            panic!(
+                "Message type {} is registered more than once ({})",
+                T::TYPE_ID,
+                std::any::type_name::<T>()
+            );
+        }
+    }
+
+    pub fn dispatch(
+        &self,
+        world: &mut World,
+        context: C,
+        bytes: &[u8],
+    ) -> Result<MessageTypeID, MessageError> {
+        let envelope = Envelope::read(bytes)?;
+        let handler = self
+            .handlers
+            .get(&envelope.message_type)
+            .ok_or(MessageError::UnknownType(envelope.message_type))?;
+
+        handler(world, context, &envelope)?;
+        Ok(envelope.message_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::{
+        event::{Event, Events},
+        world::World,
+    };
+
+    use super::MessageRouter;
+    use crate::network::{
+        events::{GetWorldState, PlayerInput},
+        message::{encode_message, MessageError, NetworkMessage},
+    };
+
+    #[derive(Event)]
+    struct Received<T: NetworkMessage> {
+        client: u64,
+        message: T,
+    }
+
+    fn setup() -> (World, MessageRouter<u64>) {
+        let mut world = World::new();
+        world.init_resource::<Events<Received<PlayerInput>>>();
+        world.init_resource::<Events<Received<GetWorldState>>>();
+
+        let mut router = MessageRouter::<u64>::default();
+        router.add::<PlayerInput, _>(|client, message| Received { client, message });
+        router.add::<GetWorldState, _>(|client, message| Received { client, message });
+
+        (world, router)
+    }
+
+    #[test]
+    fn routes_interleaved_message_types() {
+        let (mut world, router) = setup();
+
+        let forward = PlayerInput {
+            forward: 1,
+            ..Default::default()
+        };
+        let left = PlayerInput {
+            left: 1,
+            ..Default::default()
+        };
+
+        let messages = [
+            (1, encode_message(&forward).unwrap()),
+            (2, encode_message(&GetWorldState { world: 7 }).unwrap()),
+            (2, encode_message(&left).unwrap()),
+            (1, encode_message(&GetWorldState { world: 3 }).unwrap()),
+        ];
+        for (client, bytes) in &messages {
+            router.dispatch(&mut world, *client, bytes).unwrap();
+        }
+
+        let inputs: Vec<(u64, PlayerInput)> = world
+            .resource::<Events<Received<PlayerInput>>>()
+            .iter_current_update_events()
+            .map(|event| (event.client, event.message))
+            .collect();
+        assert_eq!(inputs, vec![(1, forward), (2, left)]);
+
+        let world_states: Vec<(u64, u64)> = world
+            .resource::<Events<Received<GetWorldState>>>()
+            .iter_current_update_events()
+            .map(|event| (event.client, event.message.world))
+            .collect();
+        assert_eq!(world_states, vec![(2, 7), (1, 3)]);
+    }
+
+    #[test]
+    fn rejects_unknown_message_types() {
+        let (mut world, router) = setup();
+
+        let mut bytes = encode_message(&GetWorldState { world: 0 }).unwrap();
+        bytes[0] = 0xff;
+        bytes[1] = 0xff;
+
+        assert!(matches!(
+            router.dispatch(&mut world, 1, &bytes),
+            Err(MessageError::UnknownType(0xffff))
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_messages() {
+        let (mut world, router) = setup();
+
+        assert!(matches!(
+            router.dispatch(&mut world, 1, &[0x01]),
+            Err(MessageError::MissingHeader)
+        ));
+
+        let bytes = encode_message(&GetWorldState { world: u64::MAX }).unwrap();
+        assert!(matches!(
+            router.dispatch(&mut world, 1, &bytes[..3]),
+            Err(MessageError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_messages() {
+        let (mut world, router) = setup();
+
+        let input = PlayerInput {
+            forward: 2,
+            ..Default::default()
+        };
+        let bytes = encode_message(&input).unwrap();
+        assert!(matches!(
+            router.dispatch(&mut world, 1, &bytes),
+            Err(MessageError::Invalid(_))
+        ));
+        assert!(world.resource::<Events<Received<PlayerInput>>>().is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_duplicate_registrations() {
+        let (_, mut router) = setup();
+        router.add::<PlayerInput, _>(|client, message| Received { client, message });
+    }
+}
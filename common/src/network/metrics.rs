@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+
+use super::{
+    channels::{MessageChannel, MAX_CHANNEL_MEMORY},
+    message::MessageTypeID,
+    protocol::message_name,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TrafficCounter {
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+impl TrafficCounter {
+    fn add(&mut self, bytes: usize) {
+        self.messages += 1;
+        self.bytes += bytes as u64;
+    }
+}
+
+// Messages of each type sent and received over one connection. Bytes are the encoded message, not
+// what went over the wire, which renet's own statistics cover
+#[derive(Clone, Debug, Default)]
+pub struct MessageTraffic {
+    pub sent: BTreeMap<MessageTypeID, TrafficCounter>,
+    pub received: BTreeMap<MessageTypeID, TrafficCounter>,
+}
+
+impl MessageTraffic {
+    pub fn record_sent(&mut self, message_type: MessageTypeID, bytes: usize) {
+        self.sent.entry(message_type).or_default().add(bytes);
+    }
+
+    pub fn record_received(&mut self, message_type: MessageTypeID, bytes: usize) {
+        self.received.entry(message_type).or_default().add(bytes);
+    }
+
+    // The types that have used the most bytes in either direction, biggest first
+    pub fn busiest(&self, count: usize) -> Vec<(&'static str, TrafficCounter, TrafficCounter)> {
+        let mut types: Vec<MessageTypeID> =
+            self.sent.keys().chain(self.received.keys()).copied().collect();
+        types.sort_unstable();
+        types.dedup();
+
+        let mut busiest: Vec<_> = types
+            .into_iter()
+            .map(|message_type| {
+                (
+                    message_name(message_type).unwrap_or("Unknown"),
+                    self.sent.get(&message_type).copied().unwrap_or_default(),
+                    self.received.get(&message_type).copied().unwrap_or_default(),
+                )
+            })
+            .collect();
+        busiest.sort_by_key(|(_, sent, received)| std::cmp::Reverse(sent.bytes + received.bytes));
+        busiest.truncate(count);
+        busiest
+    }
+}
+
+// Bytes waiting in each channel's send queue, from the memory renet says the channel has left
+pub fn queued_bytes(available: impl Fn(MessageChannel) -> usize) -> [usize; 4] {
+    MessageChannel::ALL.map(|channel| MAX_CHANNEL_MEMORY.saturating_sub(available(channel)))
+}
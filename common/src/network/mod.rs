@@ -1,2 +1,15 @@
+pub mod channels;
+pub mod conditions;
 pub mod configuration;
 pub mod events;
+pub mod handshake;
+pub mod login;
+pub mod message;
+pub mod message_router;
+pub mod metrics;
+pub mod protocol;
+pub mod recording;
+pub mod replication;
+pub mod rpc;
+pub mod settings;
+pub mod snapshot;
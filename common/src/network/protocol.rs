@@ -0,0 +1,196 @@
+use super::{
+    channels::MessageChannel,
+    events::{
+        CancelOrder, GetPlayerEntity, GetPriceHistory, GetWorldState, Handshake,
+        HandshakeAccepted, HandshakeRejected, InputAcknowledged, JoinWorld, JoinWorldFailed,
+        Mined, MiningFailed, OrderCancelled, OrderFilled, OrderPlaced, OrderRejected, PlaceOrder,
+        PlayerEntity, PlayerInput, PriceHistory, ReplicationUpdate, Snapshot,
+        SnapshotAcknowledged, TopOfBook, WorldJoined,
+    },
+    message::{MessageError, MessageTypeID, NetworkMessage},
+    rpc::{Request, RequestID, Response},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageDirection {
+    ClientToServer,
+    ServerToClient,
+    Both,
+}
+
+impl MessageDirection {
+    pub const fn sent_by_client(self) -> bool {
+        matches!(self, MessageDirection::ClientToServer | MessageDirection::Both)
+    }
+
+    pub const fn sent_by_server(self) -> bool {
+        matches!(self, MessageDirection::ServerToClient | MessageDirection::Both)
+    }
+}
+
+// Implemented by each side to turn the protocol into the events and systems it needs
+pub trait ProtocolRegistrar {
+    fn register<T: NetworkMessage>(&mut self);
+}
+
+macro_rules! protocol {
+    ($($message:ty = $id:expr => $direction:ident, $channel:ident $(, $validate:path)?;)*) => {
+        $(
+            impl NetworkMessage for $message {
+                const TYPE_ID: MessageTypeID = $id;
+                const DIRECTION: MessageDirection = MessageDirection::$direction;
+                const CHANNEL: MessageChannel = MessageChannel::$channel;
+
+                $(
+                    fn validate(&self) -> Result<(), MessageError> {
+                        $validate(self)
+                    }
+                )?
+            }
+        )*
+
+        pub fn register_protocol<R: ProtocolRegistrar>(registrar: &mut R) {
+            $(
+                registrar.register::<$message>();
+            )*
+        }
+
+        pub fn message_name(message_type: MessageTypeID) -> Option<&'static str> {
+            match message_type {
+                $(
+                    $id => Some(stringify!($message)),
+                )*
+                _ => None,
+            }
+        }
+    };
+}
+
+// Implemented by each side to track and answer the requests declared in the protocol
+pub trait RequestRegistrar {
+    fn register<T: Request>(&mut self);
+}
+
+// Every request and response has a `request` field holding the ID that pairs them up
+macro_rules! requests {
+    ($($request:ty => $response:ty;)*) => {
+        $(
+            impl Request for $request {
+                type Response = $response;
+
+                fn request_id(&self) -> RequestID {
+                    self.request
+                }
+
+                fn set_request_id(&mut self, request: RequestID) {
+                    self.request = request;
+                }
+            }
+
+            impl Response for $response {
+                fn request_id(&self) -> RequestID {
+                    self.request
+                }
+            }
+        )*
+
+        pub fn register_requests<R: RequestRegistrar>(registrar: &mut R) {
+            $(
+                registrar.register::<$request>();
+            )*
+        }
+    };
+}
+
+// Append new messages to the end, the IDs of existing messages must stay the same. Retired IDs
+// aren't reused: 2 was EntityPosition, 3 and 4 were CreateEntity and DestroyEntity, and 17 was
+// DepositInfo. A validator can follow the channel to reject messages with values that decode but
+// make no sense
+protocol! {
+    PlayerInput = 1 => ClientToServer, ReliableOrdered, PlayerInput::validate_buttons;
+    GetWorldState = 5 => ClientToServer, ReliableOrdered;
+    GetPlayerEntity = 6 => ClientToServer, ReliableOrdered;
+    PlayerEntity = 7 => ServerToClient, ReliableOrdered;
+    PlaceOrder = 8 => ClientToServer, ReliableOrdered, PlaceOrder::validate_order;
+    CancelOrder = 9 => ClientToServer, ReliableOrdered;
+    OrderPlaced = 10 => ServerToClient, ReliableOrdered;
+    OrderRejected = 11 => ServerToClient, ReliableOrdered;
+    OrderFilled = 12 => ServerToClient, ReliableOrdered;
+    OrderCancelled = 13 => ServerToClient, ReliableOrdered;
+    TopOfBook = 14 => ServerToClient, ReliableOrdered;
+    GetPriceHistory = 15 => ClientToServer, ReliableOrdered;
+    PriceHistory = 16 => ServerToClient, ReliableUnordered;
+    Mined = 18 => ServerToClient, ReliableOrdered;
+    MiningFailed = 19 => ServerToClient, ReliableOrdered;
+    InputAcknowledged = 20 => ServerToClient, UnreliableSequenced;
+    ReplicationUpdate = 21 => ServerToClient, ReliableOrdered;
+    Snapshot = 22 => ServerToClient, UnreliableSequenced;
+    SnapshotAcknowledged = 23 => ClientToServer, UnreliableSequenced;
+    JoinWorld = 24 => ClientToServer, ReliableOrdered;
+    WorldJoined = 25 => ServerToClient, ReliableOrdered;
+    JoinWorldFailed = 26 => ServerToClient, ReliableOrdered;
+    Handshake = 27 => ClientToServer, ReliableOrdered;
+    HandshakeAccepted = 28 => ServerToClient, ReliableOrdered;
+    HandshakeRejected = 29 => ServerToClient, ReliableOrdered;
+}
+
+requests! {
+    GetPlayerEntity => PlayerEntity;
+    GetPriceHistory => PriceHistory;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{register_protocol, ProtocolRegistrar};
+    use crate::{
+        market::{Credits, OrderSide, Price},
+        materials::MaterialID,
+        network::{
+            events::{PlaceOrder, MAX_ORDER_PRICE, MAX_ORDER_QUANTITY},
+            message::{MessageTypeID, NetworkMessage},
+        },
+        units::Mass,
+    };
+
+    #[derive(Default)]
+    struct CollectIDs(Vec<MessageTypeID>);
+
+    impl ProtocolRegistrar for CollectIDs {
+        fn register<T: NetworkMessage>(&mut self) {
+            self.0.push(T::TYPE_ID);
+        }
+    }
+
+    #[test]
+    fn message_ids_are_unique() {
+        let mut ids = CollectIDs::default();
+        register_protocol(&mut ids);
+
+        let unique: HashSet<_> = ids.0.iter().collect();
+        assert_eq!(unique.len(), ids.0.len());
+    }
+
+    #[test]
+    fn orders_must_be_within_the_limits() {
+        let order = PlaceOrder {
+            material: MaterialID::from_name("ore"),
+            side: OrderSide::Bid,
+            price: MAX_ORDER_PRICE,
+            quantity: MAX_ORDER_QUANTITY,
+        };
+        assert!(order.validate().is_ok());
+
+        let above_price =
+            Price::per_kilogram(MAX_ORDER_PRICE.as_credits_per_kilogram() + Credits::new(1));
+        for price in [Price::default(), Price::per_kilogram(Credits::new(-1)), above_price] {
+            assert!(PlaceOrder { price, ..order }.validate().is_err());
+        }
+        let above_quantity = MAX_ORDER_QUANTITY + Mass::from_milligrams(1);
+        let part_lot = Mass::from_grams(999);
+        for quantity in [Mass::default(), Mass::from_grams(-1), above_quantity, part_lot] {
+            assert!(PlaceOrder { quantity, ..order }.validate().is_err());
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+};
+
+use bevy::utils::thiserror::Error;
+use bincode::error::{DecodeError, EncodeError};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::handshake::PROTOCOL_VERSION;
+
+// The messages in a recording are kept as they came off the wire, so only a build speaking the
+// same protocol can make sense of them
+#[derive(Error, Debug)]
+pub enum RecordingError {
+    #[error("Failed to access the recording ({0})")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to encode a recording entry ({0})")]
+    Encode(#[from] EncodeError),
+    #[error("Failed to decode a recording entry ({0})")]
+    Decode(#[from] DecodeError),
+    #[error("Recording was made with protocol {recorded} but this build speaks {current}")]
+    ProtocolMismatch { recorded: u32, current: u32 },
+}
+
+// A recording is the protocol version, a header and then one length prefixed entry after another,
+// each written as it happens
+pub struct RecordingWriter {
+    writer: BufWriter<File>,
+}
+
+impl RecordingWriter {
+    pub fn create<H: Serialize>(path: &Path, header: &H) -> Result<Self, RecordingError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&PROTOCOL_VERSION.to_le_bytes())?;
+        write_entry(&mut writer, header)?;
+        Ok(Self { writer })
+    }
+
+    pub fn write<E: Serialize>(&mut self, entry: &E) -> Result<(), RecordingError> {
+        write_entry(&mut self.writer, entry)
+    }
+
+    pub fn flush(&mut self) -> Result<(), RecordingError> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+// Reads everything up to the end of the file. A recording cut short by a crash ends part way
+// through an entry, so everything before it is still returned
+pub fn read_recording<H: DeserializeOwned, E: DeserializeOwned>(
+    path: &Path,
+) -> Result<(H, Vec<E>), RecordingError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut version = [0; 4];
+    reader.read_exact(&mut version)?;
+    let recorded = u32::from_le_bytes(version);
+    if recorded != PROTOCOL_VERSION {
+        return Err(RecordingError::ProtocolMismatch {
+            recorded,
+            current: PROTOCOL_VERSION,
+        });
+    }
+
+    let header = read_entry(&mut reader)?;
+    let mut entries = Vec::new();
+    loop {
+        match read_entry(&mut reader) {
+            Ok(entry) => entries.push(entry),
+            Err(RecordingError::Io(error)) if error.kind() == ErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(error),
+        }
+    }
+    Ok((header, entries))
+}
+
+fn write_entry<T: Serialize>(writer: &mut impl Write, value: &T) -> Result<(), RecordingError> {
+    let bytes = bincode::serde::encode_to_vec(value, bincode::config::standard())?;
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+fn read_entry<T: DeserializeOwned>(reader: &mut impl Read) -> Result<T, RecordingError> {
+    let mut length = [0; 4];
+    reader.read_exact(&mut length)?;
+    let mut bytes = vec![0; u32::from_le_bytes(length) as usize];
+    reader.read_exact(&mut bytes)?;
+    let (value, _) = bincode::serde::decode_from_slice(&bytes, bincode::config::standard())?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+
+    use super::{read_recording, RecordingWriter};
+
+    #[test]
+    fn recordings_cut_short_keep_their_whole_entries() {
+        let path = std::env::temp_dir().join(format!("recording-{}.bin", std::process::id()));
+        let mut writer = RecordingWriter::create(&path, &7u64).unwrap();
+        for entry in 0..3u32 {
+            writer.write(&(entry, vec![entry as u8; 8])).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        let length = std::fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(length - 4).unwrap();
+
+        let (header, entries): (u64, Vec<(u32, Vec<u8>)>) = read_recording(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(header, 7);
+        assert_eq!(entries, vec![(0, vec![0; 8]), (1, vec![1; 8])]);
+    }
+}
@@ -0,0 +1,146 @@
+use bevy::{
+    app::App,
+    ecs::{
+        change_detection::DetectChanges, component::Component, entity::Entity, system::Resource,
+        world::World,
+    },
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::components::{Appearance, DepositState};
+
+use super::message::MessageError;
+
+pub type ComponentID = u16;
+
+type SerialiseFn = fn(&World, Entity) -> Result<Option<Vec<u8>>, MessageError>;
+type WriteFn = fn(&mut World, Entity, &[u8]) -> Result<(), MessageError>;
+type RemoveFn = fn(&mut World, Entity);
+type ChangedFn = fn(&World, Entity, bool) -> bool;
+
+struct ReplicatedComponent {
+    serialise: SerialiseFn,
+    changed: ChangedFn,
+    write: WriteFn,
+    remove: RemoveFn,
+}
+
+fn serialise_component<T: Component + Serialize>(
+    world: &World,
+    entity: Entity,
+) -> Result<Option<Vec<u8>>, MessageError> {
+    match world.get::<T>(entity) {
+        Some(component) => Ok(Some(bincode::serde::encode_to_vec(
+            component,
+            bincode::config::standard(),
+        )?)),
+        None => Ok(None),
+    }
+}
+
+fn component_changed<T: Component>(world: &World, entity: Entity, was_present: bool) -> bool {
+    match world.entity(entity).get_ref::<T>() {
+        Some(component) => !was_present || component.is_changed(),
+        None => was_present,
+    }
+}
+
+fn write_component<T: Component + DeserializeOwned>(
+    world: &mut World,
+    entity: Entity,
+    data: &[u8],
+) -> Result<(), MessageError> {
+    let (component, _): (T, usize) =
+        bincode::serde::decode_from_slice(data, bincode::config::standard())?;
+    world.entity_mut(entity).insert(component);
+    Ok(())
+}
+
+fn remove_component<T: Component>(world: &mut World, entity: Entity) {
+    world.entity_mut(entity).remove::<T>();
+}
+
+// Component types are identified on the wire by the order they were registered in, so both sides
+// have to register the same types in the same order (see `register_replicated_components`)
+#[derive(Resource, Default)]
+pub struct ReplicationRegistry {
+    components: Vec<ReplicatedComponent>,
+}
+
+impl ReplicationRegistry {
+    pub fn register<T: Component + Serialize + DeserializeOwned>(&mut self) {
+        self.components.push(ReplicatedComponent {
+            serialise: serialise_component::<T>,
+            changed: component_changed::<T>,
+            write: write_component::<T>,
+            remove: remove_component::<T>,
+        });
+    }
+
+    // The state of every replicated component on an entity, indexed by `ComponentID`
+    pub fn serialise(
+        &self,
+        world: &World,
+        entity: Entity,
+    ) -> Result<Vec<Option<Vec<u8>>>, MessageError> {
+        self.components
+            .iter()
+            .map(|component| (component.serialise)(world, entity))
+            .collect()
+    }
+
+    // Whether any replicated component has been added, changed or removed since `state` was
+    // serialised, going by when the system calling this last ran
+    pub fn changed(&self, world: &World, entity: Entity, state: &[Option<Vec<u8>>]) -> bool {
+        self.components
+            .iter()
+            .zip(state)
+            .any(|(component, data)| (component.changed)(world, entity, data.is_some()))
+    }
+
+    pub fn write(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        component: ComponentID,
+        data: &[u8],
+    ) -> Result<(), MessageError> {
+        let replicated = self
+            .components
+            .get(component as usize)
+            .ok_or(MessageError::UnknownComponent(component))?;
+        (replicated.write)(world, entity, data)
+    }
+
+    pub fn remove(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        component: ComponentID,
+    ) -> Result<(), MessageError> {
+        let replicated = self
+            .components
+            .get(component as usize)
+            .ok_or(MessageError::UnknownComponent(component))?;
+        (replicated.remove)(world, entity);
+        Ok(())
+    }
+}
+
+pub trait ReplicationAppExt {
+    fn replicate<T: Component + Serialize + DeserializeOwned>(&mut self) -> &mut Self;
+}
+
+impl ReplicationAppExt for App {
+    fn replicate<T: Component + Serialize + DeserializeOwned>(&mut self) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(ReplicationRegistry::default)
+            .register::<T>();
+        self
+    }
+}
+
+// Append new components to the end so the IDs of existing ones stay the same
+pub fn register_replicated_components(app: &mut App) {
+    app.replicate::<Appearance>().replicate::<DepositState>();
+}
@@ -0,0 +1,126 @@
+use std::{collections::HashMap, fmt::Display, marker::PhantomData};
+
+use bevy::ecs::system::Resource;
+
+use super::message::NetworkMessage;
+
+// Chosen by whoever sends the request and copied into the response, so the two can be matched up
+pub type RequestID = u32;
+
+// A message that's answered by exactly one response. Both carry the request's ID, set by the
+// side sending the request and copied across by the side answering it
+pub trait Request: NetworkMessage {
+    type Response: Response;
+    // How long to wait for the response before giving up on it
+    const TIMEOUT_SECONDS: f64 = 10.0;
+
+    fn request_id(&self) -> RequestID;
+    fn set_request_id(&mut self, request: RequestID);
+}
+
+pub trait Response: NetworkMessage + Clone {
+    fn request_id(&self) -> RequestID;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestError {
+    TimedOut,
+    // The connection went before the response arrived, and responses are never sent again
+    Disconnected,
+}
+
+impl Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::TimedOut => f.write_str("no response arrived in time"),
+            RequestError::Disconnected => f.write_str("the connection was lost"),
+        }
+    }
+}
+
+// The requests of one type still waiting for a response, and when each of them times out
+#[derive(Resource)]
+pub struct PendingRequests<T: Request> {
+    next_id: RequestID,
+    deadlines: HashMap<RequestID, f64>,
+    request_type: PhantomData<fn() -> T>,
+}
+
+impl<T: Request> Default for PendingRequests<T> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            deadlines: HashMap::new(),
+            request_type: PhantomData,
+        }
+    }
+}
+
+impl<T: Request> PendingRequests<T> {
+    // Gives the request the next ID and starts waiting for its response
+    pub fn start(&mut self, request: &mut T, now: f64) -> RequestID {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        request.set_request_id(id);
+        self.deadlines.insert(id, now + T::TIMEOUT_SECONDS);
+        id
+    }
+
+    // Whether the response was still being waited for. Ones that arrive after their request timed
+    // out aren't
+    pub fn finish(&mut self, response: &T::Response) -> bool {
+        self.deadlines.remove(&response.request_id()).is_some()
+    }
+
+    pub fn expire(&mut self, now: f64) -> Vec<RequestID> {
+        let expired: Vec<RequestID> = self
+            .deadlines
+            .iter()
+            .filter(|(_, deadline)| now >= **deadline)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &expired {
+            self.deadlines.remove(id);
+        }
+        expired
+    }
+
+    pub fn cancel_all(&mut self) -> Vec<RequestID> {
+        self.deadlines.drain().map(|(id, _)| id).collect()
+    }
+
+    pub fn is_pending(&self, request: RequestID) -> bool {
+        self.deadlines.contains_key(&request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::entity::Entity;
+
+    use super::{PendingRequests, Request};
+    use crate::network::events::{GetPlayerEntity, PlayerEntity};
+
+    #[test]
+    fn responses_are_matched_once_and_requests_expire() {
+        let mut pending = PendingRequests::<GetPlayerEntity>::default();
+        let mut first = GetPlayerEntity { request: 0, world: 0 };
+        let mut second = GetPlayerEntity { request: 0, world: 1 };
+        let first_id = pending.start(&mut first, 0.0);
+        let second_id = pending.start(&mut second, 1.0);
+        assert_ne!(first_id, second_id);
+        assert_eq!(second.request, second_id);
+
+        let response = PlayerEntity {
+            request: second_id,
+            entity: Ok(Entity::PLACEHOLDER),
+        };
+        assert!(pending.finish(&response));
+        assert!(!pending.finish(&response));
+
+        let timeout = GetPlayerEntity::TIMEOUT_SECONDS;
+        assert!(pending.expire(timeout - 1.0).is_empty());
+        assert_eq!(pending.expire(timeout), vec![first_id]);
+        assert!(!pending.is_pending(first_id));
+    }
+}
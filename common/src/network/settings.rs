@@ -0,0 +1,285 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::Path,
+};
+
+use bevy::{ecs::system::Resource, utils::thiserror::Error};
+use kdl::{KdlDocument, KdlError, KdlValue};
+
+use super::{
+    conditions::NetworkConditions,
+    configuration::{
+        CLIENT_TO_SERVER_PORT, LOCAL_ADDRESS, LOGIN_PORT, MAX_CLIENTS, SERVER_ADDRESS,
+        SERVER_TO_CLIENT_PORT, TICK_SECONDS,
+    },
+};
+
+#[derive(Error, Debug)]
+pub enum SettingsError {
+    #[error("Failed to read config file ({0})")]
+    Read(#[from] std::io::Error),
+    #[error("Invalid KDL in config file ({0})")]
+    Parse(#[from] KdlError),
+    #[error("No value given for {0}")]
+    MissingValue(String),
+    #[error("Unknown setting {0}")]
+    UnknownSetting(String),
+    #[error("Invalid value {value:?} for {name}")]
+    InvalidValue { name: String, value: String },
+}
+
+// The constants in `configuration` are only the defaults, everything here can be changed from a
+// KDL config file or from the command line. The server listens on `bind_address` and puts
+// `public_address` in connect tokens, clients connect to `public_address` from `client_address`,
+// or from any local address when that isn't set. Clients only use their tick rate until the
+// server's handshake tells them its own
+#[derive(Resource, Clone, Debug)]
+pub struct NetworkSettings {
+    pub bind_address: IpAddr,
+    pub public_address: IpAddr,
+    pub client_address: Option<IpAddr>,
+    pub port: u16,
+    pub login_port: u16,
+    pub max_clients: usize,
+    pub tick_rate: f64,
+    // Applied to every message this process receives, for testing on one machine. `simulate`
+    // picks a named profile and the `simulated_` settings change one part of it, in milliseconds
+    // for latency and jitter
+    pub conditions: NetworkConditions,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            bind_address: LOCAL_ADDRESS,
+            public_address: SERVER_ADDRESS,
+            client_address: None,
+            port: CLIENT_TO_SERVER_PORT,
+            login_port: LOGIN_PORT,
+            max_clients: MAX_CLIENTS,
+            tick_rate: 1.0 / TICK_SECONDS,
+            conditions: NetworkConditions::default(),
+        }
+    }
+}
+
+impl NetworkSettings {
+    // Settings from the file given with --config are applied first, then any other flags
+    pub fn from_args(args: &[String]) -> Result<Self, SettingsError> {
+        let mut settings = Self::default();
+        if let Some(index) = args.iter().position(|arg| arg == "--config") {
+            let path = args
+                .get(index + 1)
+                .ok_or_else(|| SettingsError::MissingValue(String::from("--config")))?;
+            settings.apply_file(Path::new(path))?;
+        }
+        settings.apply_args(args)?;
+        Ok(settings)
+    }
+
+    pub fn tick_seconds(&self) -> f64 {
+        1.0 / self.tick_rate
+    }
+
+    pub fn server_bind_address(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_address, self.port)
+    }
+
+    pub fn server_public_address(&self) -> SocketAddr {
+        SocketAddr::new(self.public_address, self.port)
+    }
+
+    // The unspecified address has to be the same kind as the server's for it to be reachable
+    pub fn client_bind_address(&self) -> SocketAddr {
+        let address = self.client_address.unwrap_or(match self.public_address {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        });
+        SocketAddr::new(address, SERVER_TO_CLIENT_PORT)
+    }
+
+    pub fn login_bind_address(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_address, self.login_port)
+    }
+
+    pub fn login_public_address(&self) -> SocketAddr {
+        SocketAddr::new(self.public_address, self.login_port)
+    }
+
+    // A config file is one node per setting, named as the flag without the dashes:
+    // `public_address "203.0.113.7"` or `tick_rate 20`
+    pub fn apply_file(&mut self, path: &Path) -> Result<(), SettingsError> {
+        let document: KdlDocument = std::fs::read_to_string(path)?.parse()?;
+        for node in document.nodes() {
+            let name = node.name().value();
+            let value = node
+                .get(0)
+                .and_then(|entry| value_text(entry.value()))
+                .ok_or_else(|| SettingsError::MissingValue(name.to_string()))?;
+            self.set(name, &value)?;
+        }
+        Ok(())
+    }
+
+    // Only flags this knows about are applied, anything else is left for the binary to handle
+    pub fn apply_args(&mut self, args: &[String]) -> Result<(), SettingsError> {
+        for (index, arg) in args.iter().enumerate() {
+            let Some(name) = arg.strip_prefix("--") else {
+                continue;
+            };
+            let name = name.replace('-', "_");
+            if !Self::is_setting(&name) {
+                continue;
+            }
+
+            let value = args
+                .get(index + 1)
+                .ok_or_else(|| SettingsError::MissingValue(arg.clone()))?;
+            self.set(&name, value)?;
+        }
+        Ok(())
+    }
+
+    fn is_setting(name: &str) -> bool {
+        matches!(
+            name,
+            "bind_address"
+                | "public_address"
+                | "client_address"
+                | "port"
+                | "login_port"
+                | "max_clients"
+                | "tick_rate"
+                | "simulate"
+                | "simulated_latency"
+                | "simulated_jitter"
+                | "simulated_loss"
+                | "simulated_duplicates"
+        )
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> Result<(), SettingsError> {
+        let invalid = || SettingsError::InvalidValue {
+            name: name.to_string(),
+            value: value.to_string(),
+        };
+
+        match name {
+            "bind_address" => self.bind_address = value.parse().map_err(|_| invalid())?,
+            "public_address" => self.public_address = value.parse().map_err(|_| invalid())?,
+            "client_address" => {
+                self.client_address = Some(value.parse().map_err(|_| invalid())?)
+            }
+            "port" => self.port = value.parse().map_err(|_| invalid())?,
+            "login_port" => self.login_port = value.parse().map_err(|_| invalid())?,
+            "max_clients" => {
+                self.max_clients = value
+                    .parse()
+                    .ok()
+                    .filter(|max_clients| *max_clients > 0)
+                    .ok_or_else(invalid)?
+            }
+            "tick_rate" => {
+                self.tick_rate = value
+                    .parse()
+                    .ok()
+                    .filter(|tick_rate: &f64| tick_rate.is_finite() && *tick_rate > 0.0)
+                    .ok_or_else(invalid)?
+            }
+            "simulate" => self.conditions = NetworkConditions::profile(value).ok_or_else(invalid)?,
+            "simulated_latency" => {
+                self.conditions.latency = milliseconds(value).ok_or_else(invalid)?
+            }
+            "simulated_jitter" => self.conditions.jitter = milliseconds(value).ok_or_else(invalid)?,
+            // Anything always lost would never get through on the reliable channels
+            "simulated_loss" => {
+                self.conditions.loss = chance(value)
+                    .filter(|loss| *loss < 1.0)
+                    .ok_or_else(invalid)?
+            }
+            "simulated_duplicates" => {
+                self.conditions.duplicates = chance(value).ok_or_else(invalid)?
+            }
+            _ => return Err(SettingsError::UnknownSetting(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+fn milliseconds(value: &str) -> Option<f64> {
+    value
+        .parse()
+        .ok()
+        .filter(|milliseconds: &f64| milliseconds.is_finite() && *milliseconds >= 0.0)
+        .map(|milliseconds| milliseconds / 1000.0)
+}
+
+fn chance(value: &str) -> Option<f64> {
+    value
+        .parse()
+        .ok()
+        .filter(|chance: &f64| (0.0..=1.0).contains(chance))
+}
+
+fn value_text(value: &KdlValue) -> Option<String> {
+    value
+        .as_string()
+        .map(str::to_string)
+        .or_else(|| value.as_i64().map(|value| value.to_string()))
+        .or_else(|| value.as_f64().map(|value| value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NetworkSettings;
+    use crate::network::conditions::NetworkConditions;
+
+    #[test]
+    fn flags_override_defaults() {
+        let args: Vec<String> = ["server", "--public-address", "10.0.0.2", "--tick-rate", "20"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let settings = NetworkSettings::from_args(&args).unwrap();
+
+        assert_eq!(settings.server_public_address().to_string(), "10.0.0.2:43735");
+        assert_eq!(settings.tick_seconds(), 0.05);
+        assert!(NetworkSettings::from_args(&[String::from("--port"), String::from("x")]).is_err());
+    }
+
+    #[test]
+    fn clients_bind_to_any_address_unless_told_otherwise() {
+        let settings = NetworkSettings::default();
+        assert!(settings.client_bind_address().ip().is_unspecified());
+        assert_ne!(settings.client_bind_address().ip(), settings.bind_address);
+
+        let args: Vec<String> = ["client", "--public-address", "::1"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let settings = NetworkSettings::from_args(&args).unwrap();
+        assert_eq!(settings.client_bind_address().to_string(), "[::]:0");
+
+        let args: Vec<String> = ["client", "--client-address", "192.168.1.4"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let settings = NetworkSettings::from_args(&args).unwrap();
+        assert_eq!(settings.client_bind_address().ip().to_string(), "192.168.1.4");
+    }
+
+    #[test]
+    fn simulated_settings_change_the_profile() {
+        let args: Vec<String> = ["client", "--simulate", "mobile", "--simulated-latency", "250"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let settings = NetworkSettings::from_args(&args).unwrap();
+
+        let mobile = NetworkConditions::profile("mobile").unwrap();
+        assert_eq!(settings.conditions.latency, 0.25);
+        assert_eq!(settings.conditions.loss, mobile.loss);
+        let always_lost = [String::from("--simulated-loss"), String::from("1")];
+        assert!(NetworkSettings::from_args(&always_lost).is_err());
+    }
+}
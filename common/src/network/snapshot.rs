@@ -0,0 +1,171 @@
+use std::collections::{HashMap, VecDeque};
+
+use bevy::{ecs::entity::Entity, math::Vec3};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    configuration::Tick,
+    events::{EntitySnapshot, Snapshot},
+};
+
+// Positions are sent as whole multiples of 1 / POSITION_SCALE units
+pub const POSITION_SCALE: f32 = 16.0;
+// How many snapshots each side keeps to decode deltas against, about 3 seconds of ticks
+pub const SNAPSHOT_HISTORY_LENGTH: usize = 32;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct QuantisedPosition {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl QuantisedPosition {
+    pub fn from_vec3(position: Vec3) -> Self {
+        Self {
+            x: (position.x * POSITION_SCALE).round() as i32,
+            y: (position.y * POSITION_SCALE).round() as i32,
+            z: (position.z * POSITION_SCALE).round() as i32,
+        }
+    }
+
+    pub fn to_vec3(self) -> Vec3 {
+        Vec3::new(self.x as f32, self.y as f32, self.z as f32) / POSITION_SCALE
+    }
+
+    fn difference(self, from: Self) -> Self {
+        Self {
+            x: self.x.wrapping_sub(from.x),
+            y: self.y.wrapping_sub(from.y),
+            z: self.z.wrapping_sub(from.z),
+        }
+    }
+
+    fn offset(self, by: Self) -> Self {
+        Self {
+            x: self.x.wrapping_add(by.x),
+            y: self.y.wrapping_add(by.y),
+            z: self.z.wrapping_add(by.z),
+        }
+    }
+}
+
+// Everything one client has been told about positions as of a snapshot. The server and the client
+// build these the same way from the same snapshots, so a delta made against one on the server can
+// be decoded against the same one on the client
+#[derive(Clone, Default)]
+pub struct SnapshotState {
+    positions: HashMap<Entity, QuantisedPosition>,
+}
+
+impl SnapshotState {
+    pub fn get(&self, entity: Entity) -> Option<QuantisedPosition> {
+        self.positions.get(&entity).copied()
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.positions.keys().copied()
+    }
+
+    // Entities that have moved are sent relative to where they were, anything else is sent in full
+    pub fn encode(&self, entity: Entity, position: QuantisedPosition) -> EntitySnapshot {
+        match self.get(entity) {
+            Some(previous) => EntitySnapshot::Delta {
+                entity,
+                delta: position.difference(previous),
+            },
+            None => EntitySnapshot::Full { entity, position },
+        }
+    }
+
+    // The state after `snapshot`, assuming this is the state it was made against
+    pub fn apply(&self, snapshot: &Snapshot) -> SnapshotState {
+        let mut next = self.clone();
+        for entity in &snapshot.removed {
+            next.positions.remove(entity);
+        }
+        for entity_snapshot in &snapshot.entities {
+            match *entity_snapshot {
+                EntitySnapshot::Full { entity, position } => {
+                    next.positions.insert(entity, position);
+                }
+                EntitySnapshot::Delta { entity, delta } => {
+                    let previous = self.get(entity).unwrap_or_default();
+                    next.positions.insert(entity, previous.offset(delta));
+                }
+            }
+        }
+        next
+    }
+}
+
+#[derive(Default)]
+pub struct SnapshotHistory {
+    snapshots: VecDeque<(Tick, SnapshotState)>,
+}
+
+impl SnapshotHistory {
+    pub fn insert(&mut self, tick: Tick, state: SnapshotState) {
+        self.snapshots.push_back((tick, state));
+        while self.snapshots.len() > SNAPSHOT_HISTORY_LENGTH {
+            self.snapshots.pop_front();
+        }
+    }
+
+    pub fn get(&self, tick: Tick) -> Option<&SnapshotState> {
+        self.snapshots
+            .iter()
+            .find(|(snapshot_tick, _)| *snapshot_tick == tick)
+            .map(|(_, state)| state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::entity::Entity, math::Vec3};
+
+    use super::{QuantisedPosition, SnapshotState};
+    use crate::network::events::{EntitySnapshot, Snapshot};
+
+    #[test]
+    fn deltas_decode_against_the_same_baseline() {
+        let moving = Entity::from_raw(1);
+        let removed = Entity::from_raw(2);
+        let spawned = Entity::from_raw(3);
+
+        let baseline = SnapshotState::default().apply(&Snapshot {
+            tick: 1,
+            baseline: None,
+            entities: vec![
+                EntitySnapshot::Full {
+                    entity: moving,
+                    position: QuantisedPosition::from_vec3(Vec3::new(1.0, 2.0, 0.0)),
+                },
+                EntitySnapshot::Full {
+                    entity: removed,
+                    position: QuantisedPosition::default(),
+                },
+            ],
+            removed: Vec::new(),
+        });
+
+        let moved = QuantisedPosition::from_vec3(Vec3::new(1.5, -2.0, 0.0));
+        let snapshot = Snapshot {
+            tick: 2,
+            baseline: Some(1),
+            entities: vec![
+                baseline.encode(moving, moved),
+                baseline.encode(spawned, moved),
+            ],
+            removed: vec![removed],
+        };
+        assert!(matches!(snapshot.entities[0], EntitySnapshot::Delta { .. }));
+        assert!(matches!(snapshot.entities[1], EntitySnapshot::Full { .. }));
+
+        let state = baseline.apply(&snapshot);
+        assert_eq!(state.get(moving), Some(moved));
+        assert_eq!(state.get(spawned), Some(moved));
+        assert_eq!(state.get(removed), None);
+        assert_eq!(moved.to_vec3(), Vec3::new(1.5, -2.0, 0.0));
+    }
+}
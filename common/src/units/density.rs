@@ -42,6 +42,8 @@ impl Sub for Density {
 impl Div<UnitT> for Density {
     type Output = Density;
 
+    // The same mass spread over that many times the volume
+    #[allow(clippy::suspicious_arithmetic_impl)]
     fn div(self, rhs: UnitT) -> Self::Output {
         Self {
             mass: self.mass,
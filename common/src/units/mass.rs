@@ -1,8 +1,13 @@
-use std::ops::{Add, AddAssign, Div, Mul, Sub, SubAssign};
+use std::{
+    fmt::Display,
+    ops::{Add, AddAssign, Div, Mul, Sub, SubAssign},
+};
+
+use serde::{Deserialize, Serialize};
 
 use super::{UnitT, GRAM, KILOGRAM, KILOTONNE, MILLIGRAM, TONNE};
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct Mass(UnitT);
 impl Mass {
     pub const fn from_kilotonnes(value: UnitT) -> Self {
@@ -97,3 +102,9 @@ impl Div<UnitT> for Mass {
         Mass(self.0 / rhs)
     }
 }
+
+impl Display for Mass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}g", self.as_grams()))
+    }
+}
@@ -0,0 +1,30 @@
+use bevy::{ecs::component::Component, utils::HashMap};
+use common::{materials::MaterialID, units::Mass};
+
+#[derive(Component, Default)]
+pub struct Inventory {
+    pub materials: HashMap<MaterialID, Mass>,
+}
+
+impl Inventory {
+    pub fn get(&self, material: MaterialID) -> Mass {
+        self.materials.get(&material).copied().unwrap_or_default()
+    }
+
+    pub fn add(&mut self, material: MaterialID, mass: Mass) {
+        *self.materials.entry(material).or_default() += mass;
+    }
+
+    pub fn remove(&mut self, material: MaterialID, mass: Mass) -> bool {
+        match self.materials.get_mut(&material) {
+            Some(stored) if *stored >= mass => {
+                *stored -= mass;
+                if *stored == Mass::default() {
+                    self.materials.remove(&material);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
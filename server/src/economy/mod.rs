@@ -0,0 +1,5 @@
+mod inventory;
+mod wallet;
+
+pub use inventory::Inventory;
+pub use wallet::{Wallet, STARTING_CREDITS};
@@ -0,0 +1,40 @@
+use bevy::ecs::component::Component;
+use common::market::Credits;
+
+pub const STARTING_CREDITS: Credits = Credits::new(10_000);
+
+#[derive(Component, Default)]
+pub struct Wallet {
+    pub credits: Credits,
+}
+
+impl Wallet {
+    pub fn new(credits: Credits) -> Self {
+        Self { credits }
+    }
+
+    // Leaves the wallet untouched if it can't hold any more
+    pub fn deposit(&mut self, amount: Credits) -> bool {
+        match self.credits.checked_add(amount) {
+            Some(credits) => {
+                self.credits = credits;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn withdraw(&mut self, amount: Credits) -> bool {
+        if self.credits < amount {
+            return false;
+        }
+
+        match self.credits.checked_sub(amount) {
+            Some(credits) => {
+                self.credits = credits;
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use bevy::{
+    ecs::system::{Res, ResMut, Resource},
+    time::Time,
+};
+use common::network::configuration::Tick;
+
+// Seconds of game time since the world was first created. This carries on from the value saved
+// with the market history rather than resetting each time the server starts
+#[derive(Resource, Default)]
+pub struct GameClock {
+    pub seconds: f64,
+}
+
+impl GameClock {
+    pub fn now(&self) -> u64 {
+        self.seconds as u64
+    }
+}
+
+pub fn advance_game_clock(time: Res<Time>, mut clock: ResMut<GameClock>) {
+    clock.seconds += time.delta_seconds_f64();
+}
+
+// Fixed steps the server has simulated since it started, used to stamp state sent to clients
+#[derive(Resource, Default)]
+pub struct ServerTick(pub Tick);
+
+pub fn advance_server_tick(mut tick: ResMut<ServerTick>) {
+    tick.0 += 1;
+}
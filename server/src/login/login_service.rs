@@ -0,0 +1,291 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use bevy::log::{info, warn};
+use bevy_renet::renet::transport::{ConnectToken, NETCODE_KEY_BYTES};
+use common::network::{
+    configuration::PROTOCOL_ID,
+    login::{
+        account_user_data, is_valid_account_name, is_valid_secret, read_frame, write_frame,
+        LoginError, LoginRejection, LoginRequest, LoginResponse,
+    },
+    settings::NetworkSettings,
+};
+
+// The game server and any login services it trusts share this, as 64 hex digits
+const PRIVATE_KEY_VARIABLE: &str = "NETCODE_PRIVATE_KEY";
+// How long a client has to use a token after it's issued
+const TOKEN_EXPIRY_SECONDS: u64 = 30;
+// How long a connection can go without hearing from the other side before it's dropped
+const CONNECTION_TIMEOUT_SECONDS: i32 = 15;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+// Each connection is handled on a thread of its own, so one address can only have this many open
+const MAX_CONNECTIONS_PER_ADDRESS: usize = 4;
+
+pub fn private_key_from_environment() -> Option<[u8; NETCODE_KEY_BYTES]> {
+    let hex = std::env::var(PRIVATE_KEY_VARIABLE).ok()?;
+    if hex.len() != NETCODE_KEY_BYTES * 2 {
+        warn!("{} must be {} hex digits", PRIVATE_KEY_VARIABLE, NETCODE_KEY_BYTES * 2);
+        return None;
+    }
+
+    let mut key = [0; NETCODE_KEY_BYTES];
+    for (index, byte) in key.iter_mut().enumerate() {
+        let Ok(value) = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16) else {
+            warn!("{} isn't valid hex", PRIVATE_KEY_VARIABLE);
+            return None;
+        };
+        *byte = value;
+    }
+    Some(key)
+}
+
+// Client IDs are derived from the account name (FNV-1a), so they're the same whichever login
+// service issued the token and however often it's restarted
+fn client_id(account: &str) -> u64 {
+    account.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// Compares every byte whatever the first difference, so timing doesn't give away how much of a
+// guessed secret was right
+fn secrets_match(claimed: &str, given: &str) -> bool {
+    claimed.len() == given.len()
+        && claimed
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+// Hands out netcode connect tokens. The first login for an account claims it with the secret it
+// gave and later logins have to give the same one, so nobody else can take over the account or
+// the session it left behind. Claims last as long as the service runs
+pub struct LoginService {
+    private_key: [u8; NETCODE_KEY_BYTES],
+    server_addresses: Vec<SocketAddr>,
+    secrets: Mutex<HashMap<String, String>>,
+    connections: Mutex<HashMap<IpAddr, usize>>,
+}
+
+// Counts towards its address's connections until it's dropped
+struct ConnectionSlot {
+    service: Arc<LoginService>,
+    address: IpAddr,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        let mut connections = self.service.connections.lock().unwrap();
+        if let Some(count) = connections.get_mut(&self.address) {
+            *count -= 1;
+            if *count == 0 {
+                connections.remove(&self.address);
+            }
+        }
+    }
+}
+
+impl LoginService {
+    pub fn new(private_key: [u8; NETCODE_KEY_BYTES], server_addresses: Vec<SocketAddr>) -> Self {
+        Self {
+            private_key,
+            server_addresses,
+            secrets: Mutex::new(HashMap::new()),
+            connections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn issue(&self, account: &str, secret: &str) -> Result<ConnectToken, LoginRejection> {
+        if !is_valid_account_name(account) {
+            return Err(LoginRejection::InvalidAccountName);
+        }
+        if !is_valid_secret(secret) {
+            return Err(LoginRejection::InvalidSecret);
+        }
+        let mut secrets = self.secrets.lock().unwrap();
+        match secrets.get(account) {
+            Some(claimed) if !secrets_match(claimed, secret) => {
+                return Err(LoginRejection::WrongSecret);
+            }
+            Some(_) => {}
+            None => {
+                info!("Account {} claimed", account);
+                secrets.insert(account.to_string(), secret.to_string());
+            }
+        }
+        drop(secrets);
+
+        let current_time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        ConnectToken::generate(
+            current_time,
+            PROTOCOL_ID,
+            TOKEN_EXPIRY_SECONDS,
+            client_id(account),
+            CONNECTION_TIMEOUT_SECONDS,
+            self.server_addresses.clone(),
+            Some(&account_user_data(account)),
+            &self.private_key,
+        )
+        .map_err(|error| {
+            warn!("Failed to generate a connect token for {} ({})", account, error);
+            LoginRejection::TokenUnavailable
+        })
+    }
+
+    fn reserve(self: &Arc<Self>, address: IpAddr) -> Option<ConnectionSlot> {
+        let mut connections = self.connections.lock().unwrap();
+        let count = connections.entry(address).or_default();
+        if *count >= MAX_CONNECTIONS_PER_ADDRESS {
+            return None;
+        }
+        *count += 1;
+        Some(ConnectionSlot {
+            service: self.clone(),
+            address,
+        })
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> Result<(), LoginError> {
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+        let request: LoginRequest = read_frame(&mut stream)?;
+        let response = match self.issue(&request.account, &request.secret) {
+            Ok(token) => {
+                let mut bytes = Vec::new();
+                token.write(&mut bytes)?;
+                info!("Issued a connect token for {}", request.account);
+                LoginResponse::Accepted { token: bytes }
+            }
+            Err(reason) => {
+                info!("Rejected a login for {:?} ({:?})", request.account, reason);
+                LoginResponse::Rejected(reason)
+            }
+        };
+        write_frame(&mut stream, &response)
+    }
+
+    fn refuse(mut stream: TcpStream) -> Result<(), LoginError> {
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+        let response = LoginResponse::Rejected(LoginRejection::TooManyConnections);
+        write_frame(&mut stream, &response)
+    }
+
+    fn accept(self: &Arc<Self>, stream: TcpStream) -> Result<(), LoginError> {
+        let address = stream.peer_addr()?.ip();
+        let Some(slot) = self.reserve(address) else {
+            info!("Refused a login from {} with too many open", address);
+            return Self::refuse(stream);
+        };
+        thread::spawn(move || {
+            if let Err(error) = slot.service.respond(stream) {
+                warn!("Failed to handle a login ({})", error);
+            }
+        });
+        Ok(())
+    }
+
+    // A client that's slow to send its request only holds up its own connection
+    pub fn run(self, listener: TcpListener) {
+        let service = Arc::new(self);
+        for stream in listener.incoming() {
+            let result = stream
+                .map_err(LoginError::from)
+                .and_then(|stream| service.accept(stream));
+            if let Err(error) = result {
+                warn!("Failed to handle a login ({})", error);
+            }
+        }
+    }
+}
+
+pub fn spawn_login_service(
+    private_key: [u8; NETCODE_KEY_BYTES],
+    settings: &NetworkSettings,
+) -> io::Result<JoinHandle<()>> {
+    let address = settings.login_bind_address();
+    let listener = TcpListener::bind(address)?;
+    info!("Login service listening on {}", address);
+    let service = LoginService::new(private_key, vec![settings.server_public_address()]);
+    Ok(thread::spawn(move || service.run(listener)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{TcpListener, TcpStream},
+        sync::Arc,
+        thread,
+        time::Duration,
+    };
+
+    use common::network::login::{
+        read_frame, write_frame, LoginRejection, LoginRequest, LoginResponse,
+    };
+
+    use super::{LoginService, MAX_CONNECTIONS_PER_ADDRESS};
+
+    fn service() -> LoginService {
+        LoginService::new([7; 32], vec!["127.0.0.1:5000".parse().unwrap()])
+    }
+
+    #[test]
+    fn accounts_belong_to_the_first_secret_used() {
+        let service = service();
+        assert!(service.issue("miner", "first").is_ok());
+        assert!(service.issue("miner", "first").is_ok());
+        assert!(matches!(
+            service.issue("miner", "second"),
+            Err(LoginRejection::WrongSecret)
+        ));
+        assert!(matches!(
+            service.issue("miner", ""),
+            Err(LoginRejection::InvalidSecret)
+        ));
+        assert!(service.issue("prospector", "second").is_ok());
+    }
+
+    #[test]
+    fn each_address_can_only_have_so_many_connections_open() {
+        let service = Arc::new(service());
+        let address = "10.0.0.3".parse().unwrap();
+        let mut slots: Vec<_> = (0..MAX_CONNECTIONS_PER_ADDRESS)
+            .map(|_| service.reserve(address).unwrap())
+            .collect();
+        assert!(service.reserve(address).is_none());
+        assert!(service.reserve("10.0.0.4".parse().unwrap()).is_some());
+
+        slots.pop();
+        assert!(service.reserve(address).is_some());
+    }
+
+    #[test]
+    fn a_silent_connection_does_not_hold_up_other_logins() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || service().run(listener));
+
+        let _silent = TcpStream::connect(address).unwrap();
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let request = LoginRequest {
+            account: String::from("miner"),
+            secret: String::from("secret"),
+        };
+        write_frame(&mut stream, &request).unwrap();
+        let response: LoginResponse = read_frame(&mut stream).unwrap();
+        assert!(matches!(response, LoginResponse::Accepted { .. }));
+    }
+}
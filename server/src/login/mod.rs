@@ -0,0 +1,3 @@
+mod login_service;
+
+pub use login_service::{private_key_from_environment, spawn_login_service};
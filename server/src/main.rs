@@ -3,17 +3,44 @@ extern crate bevy_asset_loader;
 extern crate bevy_renet;
 extern crate common;
 
+mod economy;
+mod game_clock;
+mod login;
+mod market;
+mod movement;
 mod network;
+mod npc;
+mod world;
 
-use std::time::Duration;
+#[cfg(test)]
+mod tests;
 
-use bevy::{log::LogPlugin, prelude::*, time::common_conditions::on_timer};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::{
+    asset::AssetPlugin,
+    log::LogPlugin,
+    prelude::*,
+    time::TimeUpdateStrategy,
+};
 use bevy_asset_loader::loading_state::{LoadingState, LoadingStateAppExt};
-use bevy_renet::client_connected;
-use common::network::events::{
-    CreateEntity, EntityPosition, GetPlayerEntity, GetWorldState, PlayerEntity, PlayerInput,
+use common::loaders::{KdlAsset, KdlLoader};
+use common::materials::{load_materials, MaterialConfigs, MaterialManager};
+use common::network::events::{GetPlayerEntity, PlayerEntity, PlayerEntityFailure};
+use common::network::settings::NetworkSettings;
+use game_clock::{advance_game_clock, advance_server_tick, GameClock, ServerTick};
+use login::{private_key_from_environment, spawn_login_service};
+use market::{export_market_history, MarketPlugin};
+use movement::{process_inputs, queue_inputs};
+use network::{
+    send_snapshots, update_interest, ClientEntityMapper, NetworkPlugin, ReceiveFromClient,
+    Recorder, RecordingHeader, Replay, SendToClient,
 };
-use network::{ClientEntityMapper, NetworkPlugin, ReceiveFromClient, SendToClient};
+use npc::{NpcConfigs, NpcPlugin};
+use world::{InWorld, WorldPlugin, WorldSeed};
 
 #[derive(Default, States, Clone, Debug, Hash, PartialEq, Eq)]
 pub enum ServerState {
@@ -36,144 +63,192 @@ pub fn generate_assets(mut state: ResMut<NextState<ServerState>>) {
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--export-market-history") {
+        let directory = PathBuf::from(args.get(index + 1).map_or(".", |arg| arg.as_str()));
+        App::new()
+            .add_plugins(LogPlugin::default())
+            .add_systems(Startup, move || export_market_history(&directory))
+            .run();
+        return;
+    }
+
+    let mut settings = match NetworkSettings::from_args(&args) {
+        Ok(settings) => settings,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    if args.iter().any(|arg| arg == "--login-service") {
+        App::new()
+            .add_plugins(LogPlugin::default())
+            .add_systems(Startup, move || run_login_service(&settings))
+            .run();
+        return;
+    }
+
     let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        LogPlugin {
+            level: bevy::log::Level::DEBUG,
+            filter: "server=debug,error".into()
+        },
+    ));
+
+    // A replay runs on the recorded world with the recorded tick rate, one tick per update and as
+    // fast as it can
+    if let Some(path) = flag_value(&args, "--replay") {
+        let (header, replay) = match Replay::load(Path::new(path)) {
+            Ok(replay) => replay,
+            Err(error) => {
+                eprintln!("Failed to read {} ({})", path, error);
+                std::process::exit(1);
+            }
+        };
+        settings.tick_rate = header.tick_rate;
+        app.insert_resource(WorldSeed(header.seed))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(
+                Duration::from_secs_f64(settings.tick_seconds()),
+            ))
+            .insert_resource(replay);
+    } else if let Some(path) = flag_value(&args, "--record") {
+        let seed = WorldSeed::default();
+        let header = RecordingHeader {
+            seed: seed.0,
+            tick_rate: settings.tick_rate,
+        };
+        match Recorder::create(Path::new(path), header) {
+            Ok(recorder) => {
+                app.insert_resource(seed).insert_resource(recorder);
+            }
+            Err(error) => {
+                eprintln!("Failed to start recording to {} ({})", path, error);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    build_server(
+        &mut app,
+        settings,
+        !args.iter().any(|arg| arg == "--external-login-service"),
+    );
+
+    app.run();
+}
+
+// Everything in the server but the plugins that drive it and log from it, which tests set up
+// themselves
+pub fn build_server(app: &mut App, settings: NetworkSettings, login_service: bool) {
     app.add_state::<ServerState>()
-        .add_plugins((
-            MinimalPlugins,
-            LogPlugin {
-                level: bevy::log::Level::DEBUG,
-                filter: "server=debug,error".into()
-            },
-        ))
-        .insert_resource(Time::<Fixed>::from_seconds(0.1))
+        // The game data is shared with the client rather than duplicated
+        .add_plugins(AssetPlugin {
+            file_path: "../client/assets".into(),
+            ..default()
+        })
+        .init_asset::<KdlAsset>()
+        .init_asset_loader::<KdlLoader>()
+        .init_resource::<MaterialManager>()
+        .insert_resource(Time::<Fixed>::from_seconds(settings.tick_seconds()))
+        .insert_resource(settings)
         .add_loading_state(
             LoadingState::new(ServerState::LoadingData)
                 .continue_to_state(ServerState::GeneratingAssets),
         )
+        .add_collection_to_loading_state::<_, MaterialConfigs>(ServerState::LoadingData)
+        .add_collection_to_loading_state::<_, NpcConfigs>(ServerState::LoadingData)
         .add_systems(OnEnter(ServerState::LoadingData), print_version)
-        .add_systems(OnEnter(ServerState::GeneratingAssets), generate_assets)
+        .add_systems(
+            OnEnter(ServerState::GeneratingAssets),
+            (load_materials, generate_assets).chain(),
+        )
         .add_systems(
             FixedUpdate,
-            (input_system).run_if(in_state(ServerState::Running)),
+            (advance_server_tick, process_inputs, update_interest, send_snapshots)
+                .chain()
+                .run_if(in_state(ServerState::Running)),
         )
         .add_systems(
             Update,
-            (send_player_entity, send_world_state, send_positions, update_input)
+            (
+                send_player_entity,
+                queue_inputs,
+                advance_game_clock,
+            )
                 .run_if(in_state(ServerState::Running)),
         )
-        .add_plugins(NetworkPlugin);
-
-    app.run();
+        .init_resource::<GameClock>()
+        .init_resource::<ServerTick>()
+        .add_plugins((
+            NetworkPlugin { login_service },
+            MarketPlugin,
+            NpcPlugin,
+            WorldPlugin,
+        ));
 }
 
-fn version() -> &'static str {
-    env!("CARGO_PKG_VERSION")
+// Runs a login service on its own, for a game server started with --external-login-service. Both
+// need the same private key in their environment
+fn run_login_service(settings: &NetworkSettings) {
+    let Some(private_key) = private_key_from_environment() else {
+        error!("A login service needs a private key to share with the game server");
+        return;
+    };
+
+    match spawn_login_service(private_key, settings) {
+        Ok(service) => {
+            let _ = service.join();
+        }
+        Err(error) => error!("Failed to start the login service ({})", error),
+    }
 }
 
-fn send_positions(
-    query: Query<(Entity, &Transform), Changed<Transform>>,
-    mut entity_position_event_writer: EventWriter<SendToClient<EntityPosition>>,
-) {
-    let events = query.iter().map(|(entity, transform)| SendToClient {
-        client: None,
-        message: EntityPosition {
-            entity,
-            x: transform.translation.x,
-            y: transform.translation.y,
-            z: transform.translation.z,
-        },
-    });
-    entity_position_event_writer.send_batch(events);
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let index = args.iter().position(|arg| arg == flag)?;
+    args.get(index + 1).map(|arg| arg.as_str())
 }
 
-fn input_system(time: Res<Time<Fixed>>, mut query: Query<(Entity, &PlayerInput, &mut Transform)>) {
-    for (entity, input, mut transform) in &mut query {
-        if !input.any() {
-            continue;
-        }
-
-        let delta_x = {
-            let mut x = 0.0;
-            if input.left > 0 {
-                x -= time.delta_seconds() * 50.0;
-            }
-            if input.right > 0 {
-                x += time.delta_seconds() * 50.0
-            }
-            x
-        };
-        let delta_y = {
-            let mut y = 0.0;
-            if input.backward > 0 {
-                y -= time.delta_seconds() * 50.0;
-            }
-            if input.forward > 0 {
-                y += time.delta_seconds() * 50.0
-            }
-            y
-        };
-
-        if delta_x.abs() != 0.0 || delta_y.abs() != 0.0 {
-            debug!("New position for {:?} is {:?}", entity, transform.translation);
-            transform.translation.x += delta_x;
-            transform.translation.y += delta_y;
-        }
-    }
+fn version() -> &'static str {
+    env!("CARGO_PKG_VERSION")
 }
 
+// Always answered, so the client finds out straight away rather than waiting for its request to
+// time out
 fn send_player_entity(
     mut get_player_entity_events: EventReader<ReceiveFromClient<GetPlayerEntity>>,
     mut send_player_entity_events: EventWriter<SendToClient<PlayerEntity>>,
     mapper: Res<ClientEntityMapper>,
+    players: Query<&InWorld>,
 ) {
     for event in get_player_entity_events.read() {
-        info!("Sending player entity to {}", event.client);
-        let server_entity = mapper.clients.get(&event.client.raw());
-        match server_entity {
-            Some(entity) => send_player_entity_events.send(SendToClient {
-                client: Some(event.client),
-                message: PlayerEntity {
-                    entity: entity.clone(),
-                },
-            }),
-            None => {
-                warn!("No entity mapped to client");
-            }
-        }
-    }
-}
-
-fn send_world_state(
-    mut get_world_state_events: EventReader<ReceiveFromClient<GetWorldState>>,
-    mut create_entity_events: EventWriter<SendToClient<CreateEntity>>,
-    entities: Query<Entity, With<Transform>>,
-) {
-    for event in get_world_state_events.read() {
-        for entity in &entities {
-            create_entity_events.send(SendToClient {
-                client: Some(event.client),
-                message: CreateEntity { entity },
-            });
-        }
-    }
-}
-
-fn update_input(mut input_events: EventReader<ReceiveFromClient<PlayerInput>>, mut query: Query<&mut PlayerInput>, mapper: Res<ClientEntityMapper>) {
-    for event in input_events.read() {
-        match mapper.clients.get(&event.client.raw()) {
-            Some (entity) => {
-                match query.get_component_mut::<PlayerInput>(*entity) {
-                    Ok (mut input) => {
-                        input.set_if_neq(event.message);
-                    },
-                    Err(error) => {
-                        warn!("Failed to get component PlayerInput on entity {:?} ({})", entity, error);
-                    }
+        let world = event.message.world;
+        let entity = match mapper.clients.get(&event.client.raw()) {
+            Some(entity) => match players.get(*entity) {
+                Ok(in_world) if in_world.0 != world => {
+                    warn!(
+                        "Client {} asked for its entity in world {} but is in {}",
+                        event.client, world, in_world.0
+                    );
+                    Err(PlayerEntityFailure::InOtherWorld(in_world.0))
                 }
+                _ => Ok(*entity),
             },
             None => {
-                warn!("Tried to update player input for client {} but they don't have an entity associated", event.client);
+                warn!("No entity mapped to client {}", event.client);
+                Err(PlayerEntityFailure::NoEntity)
+            }
+        };
+
+        info!("Sending player entity to {}", event.client);
+        send_player_entity_events.send(SendToClient {
+            client: Some(event.client),
+            message: PlayerEntity {
+                request: event.message.request,
+                entity,
             },
-        }
+        });
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,306 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use bevy::{
+    ecs::{
+        event::{EventReader, EventWriter},
+        system::{Res, ResMut, Resource},
+    },
+    log::{info, warn},
+};
+use common::{
+    market::{Candle, CandleResolution, Price},
+    materials::MaterialID,
+    network::events::{GetPriceHistory, PriceHistory},
+    units::Mass,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game_clock::GameClock,
+    network::{ReceiveFromClient, SendToClient},
+};
+
+use super::MarketTrade;
+
+pub const MARKET_HISTORY_PATH: &str = "market_history.bin";
+const MAX_CANDLES_PER_REQUEST: u32 = 500;
+// Raw trades are only kept for exporting recent activity, the candles cover everything older
+const TRADE_RETENTION_SECONDS: u64 = CandleResolution::OneDay.seconds();
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Trade {
+    pub time: u64,
+    pub price: Price,
+    pub quantity: Mass,
+}
+
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct MarketHistory {
+    pub game_time: f64,
+    trades: HashMap<MaterialID, VecDeque<Trade>>,
+    candles: HashMap<(MaterialID, CandleResolution), Vec<Candle>>,
+}
+
+impl MarketHistory {
+    pub fn record(&mut self, material: MaterialID, trade: Trade) {
+        let trades = self.trades.entry(material).or_default();
+        trades.push_back(trade);
+        while trades
+            .front()
+            .is_some_and(|oldest| oldest.time + TRADE_RETENTION_SECONDS < trade.time)
+        {
+            trades.pop_front();
+        }
+
+        for resolution in CandleResolution::ALL {
+            let start = resolution.candle_start(trade.time);
+            let candles = self.candles.entry((material, resolution)).or_default();
+            match candles.last_mut() {
+                Some(candle) if candle.start == start => candle.record(trade.price, trade.quantity),
+                _ => candles.push(Candle::new(start, trade.price, trade.quantity)),
+            }
+        }
+    }
+
+    pub fn candles(&self, material: MaterialID, resolution: CandleResolution) -> &[Candle] {
+        self.candles
+            .get(&(material, resolution))
+            .map_or(&[], |candles| candles.as_slice())
+    }
+
+    pub fn last_price(&self, material: MaterialID) -> Option<Price> {
+        self.candles(material, CandleResolution::OneMinute)
+            .last()
+            .map(|candle| candle.close)
+    }
+
+    pub fn load(path: &Path) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        let result: Result<Self, _> =
+            bincode::serde::decode_from_std_read(&mut BufReader::new(file), bincode::config::standard());
+        match result {
+            Ok(history) => Some(history),
+            Err(error) => {
+                warn!("Failed to read market history from {} ({})", path.display(), error);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        bincode::serde::encode_into_std_write(self, &mut writer, bincode::config::standard())
+            .map_err(std::io::Error::other)?;
+        writer.flush()
+    }
+
+    pub fn export_csv(&self, directory: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(directory)?;
+
+        let mut trades = BufWriter::new(File::create(directory.join("trades.csv"))?);
+        writeln!(trades, "material,time,price_per_kg,quantity_g")?;
+        for (material, material_trades) in &self.trades {
+            for trade in material_trades {
+                writeln!(
+                    trades,
+                    "{:?},{},{},{}",
+                    material,
+                    trade.time,
+                    trade.price.as_credits_per_kilogram().value(),
+                    trade.quantity.as_grams()
+                )?;
+            }
+        }
+        trades.flush()?;
+
+        let mut candles = BufWriter::new(File::create(directory.join("candles.csv"))?);
+        writeln!(candles, "material,resolution,start,open,high,low,close,volume_g")?;
+        for ((material, resolution), material_candles) in &self.candles {
+            for candle in material_candles {
+                writeln!(
+                    candles,
+                    "{:?},{},{},{},{},{},{},{}",
+                    material,
+                    resolution,
+                    candle.start,
+                    candle.open.as_credits_per_kilogram().value(),
+                    candle.high.as_credits_per_kilogram().value(),
+                    candle.low.as_credits_per_kilogram().value(),
+                    candle.close.as_credits_per_kilogram().value(),
+                    candle.volume.as_grams()
+                )?;
+            }
+        }
+        candles.flush()
+    }
+}
+
+// Run instead of the server when started with `--export-market-history <directory>`
+pub fn export_market_history(directory: &Path) {
+    match MarketHistory::load(Path::new(MARKET_HISTORY_PATH)) {
+        Some(history) => match history.export_csv(directory) {
+            Ok(()) => info!("Exported market history to {}", directory.display()),
+            Err(error) => warn!("Failed to export market history ({})", error),
+        },
+        None => warn!("No market history found at {}", MARKET_HISTORY_PATH),
+    }
+}
+
+pub fn load_market_history(mut history: ResMut<MarketHistory>, mut clock: ResMut<GameClock>) {
+    if let Some(loaded) = MarketHistory::load(Path::new(MARKET_HISTORY_PATH)) {
+        info!("Loaded market history at game time {}", loaded.game_time);
+        clock.seconds = loaded.game_time;
+        *history = loaded;
+    }
+}
+
+pub fn save_market_history(mut history: ResMut<MarketHistory>, clock: Res<GameClock>) {
+    history.game_time = clock.seconds;
+    if let Err(error) = history.save(Path::new(MARKET_HISTORY_PATH)) {
+        warn!("Failed to save market history ({})", error);
+    }
+}
+
+pub fn record_trades(
+    mut market_trade_events: EventReader<MarketTrade>,
+    mut history: ResMut<MarketHistory>,
+    clock: Res<GameClock>,
+) {
+    for event in market_trade_events.read() {
+        history.record(
+            event.material,
+            Trade {
+                time: clock.now(),
+                price: event.price,
+                quantity: event.quantity,
+            },
+        );
+    }
+}
+
+pub fn send_price_history(
+    mut get_price_history_events: EventReader<ReceiveFromClient<GetPriceHistory>>,
+    mut price_history_events: EventWriter<SendToClient<PriceHistory>>,
+    history: Res<MarketHistory>,
+) {
+    for event in get_price_history_events.read() {
+        let candles = history.candles(event.message.material, event.message.resolution);
+        let count = event.message.count.min(MAX_CANDLES_PER_REQUEST) as usize;
+        let first = candles.len().saturating_sub(count);
+
+        price_history_events.send(SendToClient {
+            client: Some(event.client),
+            message: PriceHistory {
+                request: event.message.request,
+                material: event.message.material,
+                resolution: event.message.resolution,
+                candles: candles[first..].to_vec(),
+            },
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{
+        market::{Candle, CandleResolution, Credits, Price},
+        materials::MaterialID,
+        units::Mass,
+    };
+
+    use super::{MarketHistory, Trade, TRADE_RETENTION_SECONDS};
+
+    fn trade(time: u64, price: i64, kilograms: i64) -> Trade {
+        Trade {
+            time,
+            price: Price::per_kilogram(Credits::new(price)),
+            quantity: Mass::from_kilograms(kilograms),
+        }
+    }
+
+    fn candle(start: u64, [open, high, low, close]: [i64; 4], kilograms: i64) -> Candle {
+        let price = |credits| Price::per_kilogram(Credits::new(credits));
+        Candle {
+            start,
+            open: price(open),
+            high: price(high),
+            low: price(low),
+            close: price(close),
+            volume: Mass::from_kilograms(kilograms),
+        }
+    }
+
+    #[test]
+    fn trades_are_aggregated_into_candles() {
+        let ore = MaterialID::from_name("ore");
+        let mut history = MarketHistory::default();
+        let trades = [(5, 10, 1), (20, 14, 2), (59, 8, 1), (60, 9, 3), (900, 11, 1)];
+        for (time, price, kilograms) in trades {
+            history.record(ore, trade(time, price, kilograms));
+        }
+
+        assert_eq!(
+            history.candles(ore, CandleResolution::OneMinute),
+            &[
+                candle(0, [10, 14, 8, 8], 4),
+                candle(60, [9, 9, 9, 9], 3),
+                candle(900, [11, 11, 11, 11], 1),
+            ]
+        );
+        assert_eq!(
+            history.candles(ore, CandleResolution::FifteenMinutes),
+            &[candle(0, [10, 14, 8, 9], 7), candle(900, [11, 11, 11, 11], 1)]
+        );
+        assert_eq!(
+            history.candles(ore, CandleResolution::OneDay),
+            &[candle(0, [10, 14, 8, 11], 8)]
+        );
+        assert_eq!(history.last_price(ore), Some(Price::per_kilogram(Credits::new(11))));
+        let ice = MaterialID::from_name("ice");
+        assert!(history.candles(ice, CandleResolution::OneDay).is_empty());
+    }
+
+    #[test]
+    fn old_trades_are_pruned_but_their_candles_kept() {
+        let ore = MaterialID::from_name("ore");
+        let mut history = MarketHistory::default();
+        history.record(ore, trade(0, 10, 1));
+        history.record(ore, trade(TRADE_RETENTION_SECONDS, 12, 1));
+        assert_eq!(history.trades[&ore].len(), 2);
+
+        history.record(ore, trade(TRADE_RETENTION_SECONDS + 1, 14, 1));
+        let times: Vec<u64> = history.trades[&ore].iter().map(|trade| trade.time).collect();
+        assert_eq!(times, vec![TRADE_RETENTION_SECONDS, TRADE_RETENTION_SECONDS + 1]);
+        let candles = history.candles(ore, CandleResolution::OneMinute);
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[0].open, Price::per_kilogram(Credits::new(10)));
+    }
+
+    #[test]
+    fn history_survives_saving_and_loading() {
+        let ore = MaterialID::from_name("ore");
+        let mut history = MarketHistory {
+            game_time: 1234.5,
+            ..Default::default()
+        };
+        history.record(ore, trade(100, 10, 1));
+        history.record(ore, trade(4000, 12, 2));
+
+        let name = format!("market-history-{}.bin", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        history.save(&path).unwrap();
+        let loaded = MarketHistory::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.game_time, 1234.5);
+        assert_eq!(loaded.trades[&ore].len(), 2);
+        for resolution in CandleResolution::ALL {
+            assert_eq!(loaded.candles(ore, resolution), history.candles(ore, resolution));
+        }
+    }
+}
@@ -0,0 +1,686 @@
+use std::time::Duration;
+
+use bevy::{
+    app::{App, AppExit, Last, Plugin, Startup, Update},
+    ecs::{
+        entity::Entity,
+        event::{Event, EventReader, EventWriter},
+        removal_detection::RemovedComponents,
+        schedule::{
+            common_conditions::{in_state, not, on_event, resource_exists},
+            IntoSystemConfigs,
+        },
+        system::{Query, Res, ResMut, Resource, SystemParam},
+    },
+    log::{debug, warn},
+    time::common_conditions::on_timer,
+    utils::{HashMap, HashSet},
+};
+use common::{
+    market::{is_whole_lots, Credits, OrderID, OrderSide, Price},
+    materials::{MaterialID, MaterialManager},
+    network::events::{
+        CancelOrder, GetWorldState, OrderCancelled, OrderFilled, OrderPlaced, OrderRejected,
+        OrderRejectionReason, PlaceOrder, TopOfBook,
+    },
+    units::Mass,
+};
+
+use crate::{
+    economy::{Inventory, Wallet},
+    network::{ClientEntityMapper, ClientMapping, ReceiveFromClient, Replay, SendToClient},
+    ServerState,
+};
+
+use super::{
+    market_history::{
+        load_market_history, record_trades, save_market_history, send_price_history,
+    },
+    Fill, MarketHistory, Order, OrderBook,
+};
+
+pub struct MarketPlugin;
+
+impl Plugin for MarketPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Markets>()
+            .init_resource::<MarketHistory>()
+            .add_event::<SubmitOrder>()
+            .add_event::<WithdrawOrder>()
+            .add_event::<MarketTrade>()
+            .add_systems(
+                Update,
+                (
+                    receive_orders,
+                    cancel_orders_of_removed_traders,
+                    withdraw_orders,
+                    submit_orders,
+                    record_trades,
+                    send_top_of_book,
+                    send_market_state,
+                    send_price_history,
+                )
+                    .chain()
+                    .run_if(in_state(ServerState::Running)),
+            )
+            // A replay starts from an empty history and mustn't overwrite the real one
+            .add_systems(
+                Startup,
+                load_market_history.run_if(not(resource_exists::<Replay>())),
+            )
+            .add_systems(
+                Update,
+                save_market_history
+                    .run_if(in_state(ServerState::Running))
+                    .run_if(not(resource_exists::<Replay>()))
+                    .run_if(on_timer(Duration::from_secs(60))),
+            )
+            // Whatever traded since the last save would otherwise be lost on shutting down
+            .add_systems(
+                Last,
+                save_market_history
+                    .run_if(in_state(ServerState::Running))
+                    .run_if(not(resource_exists::<Replay>()))
+                    .run_if(on_event::<AppExit>()),
+            );
+    }
+}
+
+// Orders from both players and the server itself go through these events, so anything with a
+// Wallet and an Inventory can trade
+#[derive(Event, Clone, Copy)]
+pub struct SubmitOrder {
+    pub owner: Entity,
+    pub material: MaterialID,
+    pub side: OrderSide,
+    pub price: Price,
+    pub quantity: Mass,
+}
+
+#[derive(Event, Clone, Copy)]
+pub struct WithdrawOrder {
+    pub owner: Entity,
+    pub order: OrderID,
+}
+
+// Sent once per fill so other systems (e.g. price history) can observe the market
+#[derive(Event, Clone, Copy)]
+pub struct MarketTrade {
+    pub material: MaterialID,
+    pub price: Price,
+    pub quantity: Mass,
+}
+
+#[derive(Resource, Default)]
+pub struct Markets {
+    books: HashMap<MaterialID, OrderBook>,
+    order_materials: HashMap<OrderID, MaterialID>,
+    // Credits held against resting bids, refunded when the order completes or is cancelled
+    escrow: HashMap<OrderID, Credits>,
+    next_order: OrderID,
+    last_top_of_book: HashMap<MaterialID, TopOfBook>,
+}
+
+impl Markets {
+    pub fn orders_owned_by(&self, material: MaterialID, owner: Entity) -> Vec<OrderID> {
+        self.books
+            .get(&material)
+            .map(|book| book.orders_owned_by(owner))
+            .unwrap_or_default()
+    }
+
+    pub fn top_of_book(&self, material: MaterialID) -> TopOfBook {
+        let book = self.books.get(&material);
+        let bid = book.and_then(|book| book.best(OrderSide::Bid));
+        let ask = book.and_then(|book| book.best(OrderSide::Ask));
+
+        TopOfBook {
+            material,
+            bid: bid.map(|(price, _)| price),
+            bid_quantity: bid.map(|(_, quantity)| quantity).unwrap_or_default(),
+            ask: ask.map(|(price, _)| price),
+            ask_quantity: ask.map(|(_, quantity)| quantity).unwrap_or_default(),
+        }
+    }
+
+    fn next_order_id(&mut self) -> OrderID {
+        self.next_order += 1;
+        self.next_order
+    }
+}
+
+fn receive_orders(
+    mut place_order_events: EventReader<ReceiveFromClient<PlaceOrder>>,
+    mut cancel_order_events: EventReader<ReceiveFromClient<CancelOrder>>,
+    mut submit_order_events: EventWriter<SubmitOrder>,
+    mut withdraw_order_events: EventWriter<WithdrawOrder>,
+    mut order_rejected_events: EventWriter<SendToClient<OrderRejected>>,
+    mapper: Res<ClientEntityMapper>,
+    material_manager: Res<MaterialManager>,
+) {
+    for event in place_order_events.read() {
+        if material_manager.get_material(event.message.material).is_none() {
+            order_rejected_events.send(SendToClient {
+                client: Some(event.client),
+                message: OrderRejected {
+                    material: event.message.material,
+                    reason: OrderRejectionReason::UnknownMaterial,
+                },
+            });
+            continue;
+        }
+
+        match mapper.clients.get(&event.client.raw()) {
+            Some(entity) => submit_order_events.send(SubmitOrder {
+                owner: *entity,
+                material: event.message.material,
+                side: event.message.side,
+                price: event.message.price,
+                quantity: event.message.quantity,
+            }),
+            None => {
+                warn!("Client {} tried to place an order without an entity", event.client);
+            }
+        }
+    }
+
+    for event in cancel_order_events.read() {
+        match mapper.clients.get(&event.client.raw()) {
+            Some(entity) => withdraw_order_events.send(WithdrawOrder {
+                owner: *entity,
+                order: event.message.order,
+            }),
+            None => {
+                warn!("Client {} tried to cancel an order without an entity", event.client);
+            }
+        }
+    }
+}
+
+// What the owner of an order is told as it's placed, rejected and filled
+#[derive(SystemParam)]
+struct OrderEventWriters<'w> {
+    placed: EventWriter<'w, SendToClient<OrderPlaced>>,
+    rejected: EventWriter<'w, SendToClient<OrderRejected>>,
+    filled: EventWriter<'w, SendToClient<OrderFilled>>,
+}
+
+fn submit_orders(
+    mut submit_order_events: EventReader<SubmitOrder>,
+    mut markets: ResMut<Markets>,
+    mut traders: Query<(&mut Wallet, &mut Inventory)>,
+    clients: Query<&ClientMapping>,
+    mut order_events: OrderEventWriters,
+    mut market_trade_events: EventWriter<MarketTrade>,
+) {
+    for event in submit_order_events.read() {
+        let client = clients.get(event.owner).ok().map(|mapping| mapping.id);
+        let mut reject = |reason: OrderRejectionReason| {
+            debug!("Rejected an order from {:?} ({:?})", event.owner, reason);
+            if let Some(client) = client {
+                order_events.rejected.send(SendToClient {
+                    client: Some(client),
+                    message: OrderRejected {
+                        material: event.material,
+                        reason,
+                    },
+                });
+            }
+        };
+
+        if event.quantity <= Mass::default() || !is_whole_lots(event.quantity) {
+            reject(OrderRejectionReason::InvalidQuantity);
+            continue;
+        }
+        if event.price <= Price::default() {
+            reject(OrderRejectionReason::InvalidPrice);
+            continue;
+        }
+
+        let Ok((mut wallet, mut inventory)) = traders.get_mut(event.owner) else {
+            warn!("Entity {:?} tried to trade without a wallet and inventory", event.owner);
+            continue;
+        };
+
+        // Every fill is for at most the order's quantity at no worse than its price, so checking
+        // the whole order here means no fill can overflow either
+        let Some(cost) = event.price.cost(event.quantity) else {
+            reject(OrderRejectionReason::ValueTooLarge);
+            continue;
+        };
+
+        let escrow = match event.side {
+            OrderSide::Bid => {
+                if !wallet.withdraw(cost) {
+                    reject(OrderRejectionReason::InsufficientCredits);
+                    continue;
+                }
+                Some(cost)
+            }
+            OrderSide::Ask => {
+                if !inventory.remove(event.material, event.quantity) {
+                    reject(OrderRejectionReason::InsufficientMaterial);
+                    continue;
+                }
+                None
+            }
+        };
+
+        let id = markets.next_order_id();
+        markets.order_materials.insert(id, event.material);
+        if let Some(escrow) = escrow {
+            markets.escrow.insert(id, escrow);
+        }
+
+        if let Some(client) = client {
+            order_events.placed.send(SendToClient {
+                client: Some(client),
+                message: OrderPlaced {
+                    order: id,
+                    material: event.material,
+                    side: event.side,
+                    price: event.price,
+                    quantity: event.quantity,
+                },
+            });
+        }
+
+        let fills = markets
+            .books
+            .entry(event.material)
+            .or_default()
+            .place(Order {
+                id,
+                owner: event.owner,
+                side: event.side,
+                price: event.price,
+                remaining: event.quantity,
+            });
+
+        for fill in fills {
+            settle(&fill, event.material, &mut markets, &mut traders);
+            market_trade_events.send(MarketTrade {
+                material: event.material,
+                price: fill.price,
+                quantity: fill.quantity,
+            });
+
+            for order in [fill.maker, fill.taker] {
+                if let Ok(mapping) = clients.get(order.owner) {
+                    order_events.filled.send(SendToClient {
+                        client: Some(mapping.id),
+                        message: OrderFilled {
+                            order: order.id,
+                            material: event.material,
+                            side: order.side,
+                            price: fill.price,
+                            quantity: fill.quantity,
+                            remaining: order.remaining,
+                        },
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn settle(
+    fill: &Fill,
+    material: MaterialID,
+    markets: &mut Markets,
+    traders: &mut Query<(&mut Wallet, &mut Inventory)>,
+) {
+    let (buyer, seller) = match fill.taker.side {
+        OrderSide::Bid => (fill.taker, fill.maker),
+        OrderSide::Ask => (fill.maker, fill.taker),
+    };
+    // Can't overflow as the resting bid's cost was checked when it was placed
+    let cost = fill.price.cost(fill.quantity).unwrap_or_default();
+
+    if let Some(escrow) = markets.escrow.get_mut(&buyer.id) {
+        *escrow = escrow.checked_sub(cost).unwrap_or_default();
+    }
+
+    match traders.get_mut(buyer.owner) {
+        Ok((mut wallet, mut inventory)) => {
+            inventory.add(material, fill.quantity);
+            if buyer.remaining == Mass::default() {
+                if let Some(change) = markets.escrow.remove(&buyer.id) {
+                    if !wallet.deposit(change) {
+                        warn!("Buyer {:?} can't hold the change of {}", buyer.owner, change);
+                    }
+                }
+            }
+        }
+        Err(error) => {
+            warn!("Failed to deliver {} to buyer {:?} ({})", material, buyer.owner, error);
+        }
+    }
+
+    match traders.get_mut(seller.owner) {
+        Ok((mut wallet, _)) => {
+            if !wallet.deposit(cost) {
+                warn!("Seller {:?} can't hold the payment of {}", seller.owner, cost);
+            }
+        }
+        Err(error) => {
+            warn!("Failed to pay seller {:?} ({})", seller.owner, error);
+        }
+    }
+
+    for order in [buyer, seller] {
+        if order.remaining == Mass::default() {
+            markets.order_materials.remove(&order.id);
+            markets.escrow.remove(&order.id);
+        }
+    }
+}
+
+fn withdraw_orders(
+    mut withdraw_order_events: EventReader<WithdrawOrder>,
+    mut markets: ResMut<Markets>,
+    mut traders: Query<(&mut Wallet, &mut Inventory)>,
+    clients: Query<&ClientMapping>,
+    mut order_cancelled_events: EventWriter<SendToClient<OrderCancelled>>,
+    mut order_rejected_events: EventWriter<SendToClient<OrderRejected>>,
+) {
+    for event in withdraw_order_events.read() {
+        let client = clients.get(event.owner).ok().map(|mapping| mapping.id);
+
+        let material = markets.order_materials.get(&event.order).copied();
+        let owned = material
+            .and_then(|material| markets.books.get(&material))
+            .and_then(|book| book.get(event.order))
+            .is_some_and(|order| order.owner == event.owner);
+
+        let (Some(material), true) = (material, owned) else {
+            if let Some(client) = client {
+                order_rejected_events.send(SendToClient {
+                    client: Some(client),
+                    message: OrderRejected {
+                        material: material.unwrap_or_default(),
+                        reason: OrderRejectionReason::UnknownOrder,
+                    },
+                });
+            }
+            continue;
+        };
+
+        let Some(order) = markets
+            .books
+            .get_mut(&material)
+            .and_then(|book| book.cancel(event.order))
+        else {
+            continue;
+        };
+
+        refund(&order, material, &mut markets, &mut traders);
+
+        if let Some(client) = client {
+            order_cancelled_events.send(SendToClient {
+                client: Some(client),
+                message: OrderCancelled {
+                    order: order.id,
+                    remaining: order.remaining,
+                },
+            });
+        }
+    }
+}
+
+fn refund(
+    order: &Order,
+    material: MaterialID,
+    markets: &mut Markets,
+    traders: &mut Query<(&mut Wallet, &mut Inventory)>,
+) {
+    markets.order_materials.remove(&order.id);
+    let escrow = markets.escrow.remove(&order.id);
+
+    if let Ok((mut wallet, mut inventory)) = traders.get_mut(order.owner) {
+        match order.side {
+            OrderSide::Bid => {
+                if !wallet.deposit(escrow.unwrap_or_default()) {
+                    warn!("{:?} can't hold the refund of order {}", order.owner, order.id);
+                }
+            }
+            OrderSide::Ask => inventory.add(material, order.remaining),
+        }
+    }
+}
+
+fn cancel_orders_of_removed_traders(
+    mut removed_wallets: RemovedComponents<Wallet>,
+    mut markets: ResMut<Markets>,
+) {
+    for owner in removed_wallets.read() {
+        let cancelled: Vec<Order> = markets
+            .books
+            .values_mut()
+            .flat_map(|book| book.cancel_owned_by(owner))
+            .collect();
+
+        for order in cancelled {
+            debug!("Cancelled order {} as its owner {:?} is gone", order.id, owner);
+            markets.order_materials.remove(&order.id);
+            markets.escrow.remove(&order.id);
+        }
+    }
+}
+
+fn send_top_of_book(
+    mut markets: ResMut<Markets>,
+    mut top_of_book_events: EventWriter<SendToClient<TopOfBook>>,
+) {
+    let materials: HashSet<MaterialID> = markets.books.keys().copied().collect();
+    for material in materials {
+        let top_of_book = markets.top_of_book(material);
+        if markets.last_top_of_book.get(&material) == Some(&top_of_book) {
+            continue;
+        }
+
+        markets.last_top_of_book.insert(material, top_of_book);
+        top_of_book_events.send(SendToClient {
+            client: None,
+            message: top_of_book,
+        });
+    }
+}
+
+// Markets are shared by every world, so the same state is sent whichever world was asked about
+fn send_market_state(
+    mut get_world_state_events: EventReader<ReceiveFromClient<GetWorldState>>,
+    markets: Res<Markets>,
+    mut top_of_book_events: EventWriter<SendToClient<TopOfBook>>,
+) {
+    for event in get_world_state_events.read() {
+        for top_of_book in markets.last_top_of_book.values() {
+            top_of_book_events.send(SendToClient {
+                client: Some(event.client),
+                message: *top_of_book,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        app::{App, Update},
+        ecs::{entity::Entity, schedule::IntoSystemConfigs},
+    };
+    use common::{
+        market::{Credits, OrderSide, Price},
+        materials::MaterialID,
+        network::events::{OrderCancelled, OrderFilled, OrderPlaced, OrderRejected},
+        units::{Mass, UnitT},
+    };
+
+    use crate::{
+        economy::{Inventory, Wallet},
+        network::SendToClient,
+    };
+
+    use super::{submit_orders, withdraw_orders, Markets, MarketTrade, SubmitOrder, WithdrawOrder};
+
+    fn market() -> App {
+        let mut app = App::new();
+        app.init_resource::<Markets>()
+            .add_event::<SubmitOrder>()
+            .add_event::<WithdrawOrder>()
+            .add_event::<MarketTrade>()
+            .add_event::<SendToClient<OrderPlaced>>()
+            .add_event::<SendToClient<OrderRejected>>()
+            .add_event::<SendToClient<OrderFilled>>()
+            .add_event::<SendToClient<OrderCancelled>>()
+            .add_systems(Update, (withdraw_orders, submit_orders).chain());
+        app
+    }
+
+    fn ore() -> MaterialID {
+        MaterialID::from_name("ore")
+    }
+
+    fn trader(app: &mut App, credits: UnitT, kilograms: UnitT) -> Entity {
+        let mut inventory = Inventory::default();
+        inventory.add(ore(), Mass::from_kilograms(kilograms));
+        app.world
+            .spawn((Wallet::new(Credits::new(credits)), inventory))
+            .id()
+    }
+
+    fn submit(app: &mut App, owner: Entity, side: OrderSide, price: UnitT, kilograms: UnitT) {
+        app.world.send_event(SubmitOrder {
+            owner,
+            material: ore(),
+            side,
+            price: Price::per_kilogram(Credits::new(price)),
+            quantity: Mass::from_kilograms(kilograms),
+        });
+        app.update();
+    }
+
+    fn credits(app: &App, trader: Entity) -> UnitT {
+        app.world.get::<Wallet>(trader).unwrap().credits.value()
+    }
+
+    fn ore_held(app: &App, trader: Entity) -> Mass {
+        app.world.get::<Inventory>(trader).unwrap().get(ore())
+    }
+
+    #[test]
+    fn buyers_get_their_change_back_when_filled_at_a_better_price() {
+        let mut app = market();
+        let seller = trader(&mut app, 0, 1);
+        let buyer = trader(&mut app, 100, 0);
+
+        submit(&mut app, seller, OrderSide::Ask, 10, 1);
+        submit(&mut app, buyer, OrderSide::Bid, 15, 1);
+
+        assert_eq!(credits(&app, buyer), 90);
+        assert_eq!(ore_held(&app, buyer), Mass::from_kilograms(1));
+        assert_eq!(credits(&app, seller), 10);
+        assert_eq!(ore_held(&app, seller), Mass::default());
+        assert!(app.world.resource::<Markets>().escrow.is_empty());
+    }
+
+    #[test]
+    fn sellers_are_paid_the_resting_bid_price() {
+        let mut app = market();
+        let buyer = trader(&mut app, 100, 0);
+        let seller = trader(&mut app, 0, 1);
+
+        submit(&mut app, buyer, OrderSide::Bid, 15, 1);
+        assert_eq!(credits(&app, buyer), 85);
+        submit(&mut app, seller, OrderSide::Ask, 10, 1);
+
+        assert_eq!(credits(&app, buyer), 85);
+        assert_eq!(credits(&app, seller), 15);
+        assert_eq!(ore_held(&app, buyer), Mass::from_kilograms(1));
+    }
+
+    #[test]
+    fn cancelling_a_partly_filled_bid_refunds_the_rest_of_the_escrow() {
+        let mut app = market();
+        let buyer = trader(&mut app, 100, 0);
+        let seller = trader(&mut app, 0, 1);
+
+        submit(&mut app, buyer, OrderSide::Bid, 10, 3);
+        submit(&mut app, seller, OrderSide::Ask, 10, 1);
+        assert_eq!(credits(&app, buyer), 70);
+        assert_eq!(credits(&app, seller), 10);
+
+        let order = app.world.resource::<Markets>().orders_owned_by(ore(), buyer)[0];
+        app.world.send_event(WithdrawOrder { owner: buyer, order });
+        app.update();
+
+        assert_eq!(credits(&app, buyer), 90);
+        assert_eq!(ore_held(&app, buyer), Mass::from_kilograms(1));
+        assert!(app.world.resource::<Markets>().escrow.is_empty());
+    }
+
+    #[test]
+    fn cancelling_an_ask_returns_the_material() {
+        let mut app = market();
+        let seller = trader(&mut app, 0, 2);
+        let stranger = trader(&mut app, 0, 0);
+
+        submit(&mut app, seller, OrderSide::Ask, 10, 2);
+        assert_eq!(ore_held(&app, seller), Mass::default());
+
+        let order = app.world.resource::<Markets>().orders_owned_by(ore(), seller)[0];
+        app.world.send_event(WithdrawOrder { owner: stranger, order });
+        app.update();
+        assert_eq!(ore_held(&app, seller), Mass::default());
+
+        app.world.send_event(WithdrawOrder { owner: seller, order });
+        app.update();
+        assert_eq!(ore_held(&app, seller), Mass::from_kilograms(2));
+        assert!(app
+            .world
+            .resource::<Markets>()
+            .orders_owned_by(ore(), seller)
+            .is_empty());
+    }
+
+    #[test]
+    fn orders_for_part_of_a_kilogram_are_rejected() {
+        let mut app = market();
+        let seller = trader(&mut app, 0, 1);
+        let buyer = trader(&mut app, 100, 0);
+
+        for (owner, side) in [(seller, OrderSide::Ask), (buyer, OrderSide::Bid)] {
+            app.world.send_event(SubmitOrder {
+                owner,
+                material: ore(),
+                side,
+                price: Price::per_kilogram(Credits::new(1)),
+                quantity: Mass::from_grams(999),
+            });
+            app.update();
+        }
+
+        assert_eq!(credits(&app, buyer), 100);
+        assert_eq!(credits(&app, seller), 0);
+        assert_eq!(ore_held(&app, seller), Mass::from_kilograms(1));
+        let markets = app.world.resource::<Markets>();
+        assert!(markets.orders_owned_by(ore(), seller).is_empty());
+        assert!(markets.orders_owned_by(ore(), buyer).is_empty());
+    }
+
+    #[test]
+    fn orders_costing_more_than_credits_can_hold_are_rejected() {
+        let mut app = market();
+        let buyer = trader(&mut app, UnitT::MAX, 0);
+        let seller = trader(&mut app, 0, 2);
+
+        submit(&mut app, buyer, OrderSide::Bid, UnitT::MAX, 2);
+        submit(&mut app, seller, OrderSide::Ask, UnitT::MAX, 2);
+
+        assert_eq!(credits(&app, buyer), UnitT::MAX);
+        assert_eq!(ore_held(&app, seller), Mass::from_kilograms(2));
+        let markets = app.world.resource::<Markets>();
+        assert!(markets.orders_owned_by(ore(), buyer).is_empty());
+        assert!(markets.orders_owned_by(ore(), seller).is_empty());
+    }
+}
@@ -0,0 +1,7 @@
+mod market_history;
+mod market_plugin;
+mod order_book;
+
+pub use market_history::{export_market_history, MarketHistory};
+pub use market_plugin::{MarketPlugin, MarketTrade, Markets, SubmitOrder, WithdrawOrder};
+pub use order_book::{Fill, Order, OrderBook};
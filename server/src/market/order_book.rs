@@ -0,0 +1,243 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use bevy::{ecs::entity::Entity, utils::HashMap};
+use common::{
+    market::{OrderID, OrderSide, Price},
+    units::Mass,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Order {
+    pub id: OrderID,
+    pub owner: Entity,
+    pub side: OrderSide,
+    pub price: Price,
+    pub remaining: Mass,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Fill {
+    pub maker: Order,
+    pub taker: Order,
+    pub price: Price,
+    pub quantity: Mass,
+}
+
+// Price levels are kept in ascending order for both sides, so the best bid is the last level and
+// the best ask is the first. Orders within a level are filled oldest first
+#[derive(Default)]
+pub struct OrderBook {
+    bids: BTreeMap<Price, VecDeque<Order>>,
+    asks: BTreeMap<Price, VecDeque<Order>>,
+    index: HashMap<OrderID, (OrderSide, Price)>,
+}
+
+impl OrderBook {
+    pub fn place(&mut self, order: Order) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        let mut taker = order;
+        let maker_side = taker.side.opposite();
+
+        while taker.remaining > Mass::default() {
+            let best_price = match self.best(maker_side) {
+                Some((price, _)) => price,
+                None => break,
+            };
+
+            let crosses = match taker.side {
+                OrderSide::Bid => best_price <= taker.price,
+                OrderSide::Ask => best_price >= taker.price,
+            };
+            if !crosses {
+                break;
+            }
+
+            let levels = match maker_side {
+                OrderSide::Bid => &mut self.bids,
+                OrderSide::Ask => &mut self.asks,
+            };
+            let level = levels.get_mut(&best_price).unwrap();
+            let maker = level.front_mut().unwrap();
+
+            let quantity = maker.remaining.min(taker.remaining);
+            maker.remaining -= quantity;
+            taker.remaining -= quantity;
+
+            fills.push(Fill {
+                maker: *maker,
+                taker,
+                price: best_price,
+                quantity,
+            });
+
+            if maker.remaining == Mass::default() {
+                self.index.remove(&maker.id);
+                level.pop_front();
+            }
+            if level.is_empty() {
+                levels.remove(&best_price);
+            }
+        }
+
+        if taker.remaining > Mass::default() {
+            self.index.insert(taker.id, (taker.side, taker.price));
+            self.levels_mut(taker.side)
+                .entry(taker.price)
+                .or_default()
+                .push_back(taker);
+        }
+
+        fills
+    }
+
+    pub fn cancel(&mut self, id: OrderID) -> Option<Order> {
+        let (side, price) = self.index.remove(&id)?;
+        let levels = self.levels_mut(side);
+        let level = levels.get_mut(&price)?;
+        let position = level.iter().position(|order| order.id == id)?;
+        let order = level.remove(position);
+        if level.is_empty() {
+            levels.remove(&price);
+        }
+        order
+    }
+
+    pub fn cancel_owned_by(&mut self, owner: Entity) -> Vec<Order> {
+        self.orders_owned_by(owner)
+            .into_iter()
+            .filter_map(|id| self.cancel(id))
+            .collect()
+    }
+
+    pub fn orders_owned_by(&self, owner: Entity) -> Vec<OrderID> {
+        self.bids
+            .values()
+            .chain(self.asks.values())
+            .flatten()
+            .filter(|order| order.owner == owner)
+            .map(|order| order.id)
+            .collect()
+    }
+
+    pub fn get(&self, id: OrderID) -> Option<&Order> {
+        let (side, price) = self.index.get(&id)?;
+        let levels = match side {
+            OrderSide::Bid => &self.bids,
+            OrderSide::Ask => &self.asks,
+        };
+        levels.get(price)?.iter().find(|order| order.id == id)
+    }
+
+    pub fn best(&self, side: OrderSide) -> Option<(Price, Mass)> {
+        let level = match side {
+            OrderSide::Bid => self.bids.iter().next_back(),
+            OrderSide::Ask => self.asks.iter().next(),
+        };
+        level.map(|(price, orders)| {
+            let quantity = orders
+                .iter()
+                .fold(Mass::default(), |total, order| total + order.remaining);
+            (*price, quantity)
+        })
+    }
+
+    fn levels_mut(&mut self, side: OrderSide) -> &mut BTreeMap<Price, VecDeque<Order>> {
+        match side {
+            OrderSide::Bid => &mut self.bids,
+            OrderSide::Ask => &mut self.asks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::entity::Entity;
+    use common::{
+        market::{Credits, OrderID, OrderSide, Price},
+        units::Mass,
+    };
+
+    use super::{Order, OrderBook};
+
+    fn order(id: OrderID, side: OrderSide, price: i64, kilograms: i64) -> Order {
+        Order {
+            id,
+            owner: Entity::from_raw(id as u32),
+            side,
+            price: Price::per_kilogram(Credits::new(price)),
+            remaining: Mass::from_kilograms(kilograms),
+        }
+    }
+
+    #[test]
+    fn best_price_then_oldest_order_fills_first() {
+        let mut book = OrderBook::default();
+        assert!(book.place(order(1, OrderSide::Ask, 12, 1)).is_empty());
+        assert!(book.place(order(2, OrderSide::Ask, 10, 1)).is_empty());
+        assert!(book.place(order(3, OrderSide::Ask, 10, 1)).is_empty());
+
+        let fills = book.place(order(4, OrderSide::Bid, 12, 3));
+        let makers: Vec<OrderID> = fills.iter().map(|fill| fill.maker.id).collect();
+        assert_eq!(makers, vec![2, 3, 1]);
+        let prices: Vec<i64> = fills
+            .iter()
+            .map(|fill| fill.price.as_credits_per_kilogram().value())
+            .collect();
+        assert_eq!(prices, vec![10, 10, 12]);
+        assert!(book.best(OrderSide::Ask).is_none());
+        assert!(book.best(OrderSide::Bid).is_none());
+    }
+
+    #[test]
+    fn orders_that_dont_cross_rest() {
+        let mut book = OrderBook::default();
+        assert!(book.place(order(1, OrderSide::Bid, 9, 1)).is_empty());
+        assert!(book.place(order(2, OrderSide::Ask, 10, 1)).is_empty());
+        assert_eq!(
+            book.best(OrderSide::Bid),
+            Some((Price::per_kilogram(Credits::new(9)), Mass::from_kilograms(1)))
+        );
+        assert_eq!(
+            book.best(OrderSide::Ask),
+            Some((Price::per_kilogram(Credits::new(10)), Mass::from_kilograms(1)))
+        );
+    }
+
+    #[test]
+    fn partial_fills_leave_the_rest_on_the_book() {
+        let mut book = OrderBook::default();
+        book.place(order(1, OrderSide::Ask, 10, 5));
+
+        let fills = book.place(order(2, OrderSide::Bid, 10, 2));
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].quantity, Mass::from_kilograms(2));
+        assert_eq!(fills[0].maker.remaining, Mass::from_kilograms(3));
+        assert_eq!(fills[0].taker.remaining, Mass::default());
+        assert_eq!(book.get(1).unwrap().remaining, Mass::from_kilograms(3));
+
+        let fills = book.place(order(3, OrderSide::Bid, 10, 4));
+        assert_eq!(fills[0].quantity, Mass::from_kilograms(3));
+        assert!(book.get(1).is_none());
+        assert_eq!(book.get(3).unwrap().remaining, Mass::from_kilograms(1));
+        assert_eq!(
+            book.best(OrderSide::Bid),
+            Some((Price::per_kilogram(Credits::new(10)), Mass::from_kilograms(1)))
+        );
+    }
+
+    #[test]
+    fn cancelled_orders_leave_the_book() {
+        let mut book = OrderBook::default();
+        book.place(order(1, OrderSide::Bid, 10, 1));
+        book.place(order(2, OrderSide::Bid, 10, 2));
+
+        assert_eq!(book.cancel(1).unwrap().remaining, Mass::from_kilograms(1));
+        assert!(book.cancel(1).is_none());
+        assert_eq!(book.orders_owned_by(Entity::from_raw(2)), vec![2]);
+
+        let owned = book.cancel_owned_by(Entity::from_raw(2));
+        assert_eq!(owned.len(), 1);
+        assert!(book.best(OrderSide::Bid).is_none());
+        assert!(book.place(order(3, OrderSide::Ask, 10, 1)).is_empty());
+    }
+}
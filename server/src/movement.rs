@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    ecs::{
+        component::Component,
+        event::{EventReader, EventWriter},
+        system::{Query, Res},
+    },
+    time::{Fixed, Time},
+    log::{debug, warn},
+    transform::components::Transform,
+};
+use common::{
+    movement::apply_input,
+    network::events::{InputAcknowledged, InputSequence, PlayerInput},
+};
+
+use crate::network::{ClientEntityMapper, ClientMapping, ReceiveFromClient, SendToClient};
+
+// Inputs are only ever applied one a tick, so this is also the most ticks an input can wait behind
+// the others before the oldest are thrown away
+const MAX_QUEUED_INPUTS: usize = 16;
+
+// Inputs that have arrived from a player but haven't been simulated yet. Each input is one tick of
+// movement on the client, so each one is applied as exactly one tick here too, however many
+// arrive at once
+#[derive(Component, Default)]
+pub struct InputQueue {
+    inputs: VecDeque<PlayerInput>,
+    last_processed: InputSequence,
+}
+
+impl InputQueue {
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+}
+
+pub fn queue_inputs(
+    mut input_events: EventReader<ReceiveFromClient<PlayerInput>>,
+    mut query: Query<&mut InputQueue>,
+    mapper: Res<ClientEntityMapper>,
+) {
+    for event in input_events.read() {
+        let Some(entity) = mapper.clients.get(&event.client.raw()) else {
+            warn!(
+                "Tried to queue player input for client {} but they don't have an entity associated",
+                event.client
+            );
+            continue;
+        };
+        let Ok(mut queue) = query.get_mut(*entity) else {
+            warn!("Failed to get component InputQueue on entity {:?}", entity);
+            continue;
+        };
+
+        let newest = queue
+            .inputs
+            .back()
+            .map_or(queue.last_processed, |input| input.sequence);
+        if event.message.sequence <= newest {
+            continue;
+        }
+
+        queue.inputs.push_back(event.message);
+        if queue.inputs.len() > MAX_QUEUED_INPUTS {
+            queue.inputs.pop_front();
+        }
+    }
+}
+
+pub fn process_inputs(
+    time: Res<Time<Fixed>>,
+    mut query: Query<(&ClientMapping, &mut InputQueue, &mut PlayerInput, &mut Transform)>,
+    mut acknowledged_events: EventWriter<SendToClient<InputAcknowledged>>,
+) {
+    for (client, mut queue, mut current_input, mut transform) in &mut query {
+        let Some(input) = queue.inputs.pop_front() else {
+            continue;
+        };
+
+        let translation = apply_input(&input, time.delta_seconds(), transform.translation);
+        queue.last_processed = input.sequence;
+        *current_input = input;
+
+        if translation != transform.translation {
+            debug!("New position for {} is {:?}", client.id, translation);
+            transform.translation = translation;
+        }
+
+        acknowledged_events.send(SendToClient {
+            client: Some(client.id),
+            message: InputAcknowledged {
+                sequence: queue.last_processed,
+                x: translation.x,
+                y: translation.y,
+                z: translation.z,
+            },
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{
+        app::{App, Update},
+        ecs::{entity::Entity, event::Events, schedule::IntoSystemConfigs},
+        time::{Fixed, Time},
+        transform::components::Transform,
+    };
+    use bevy_renet::renet::ClientId;
+    use common::{
+        movement::PLAYER_SPEED,
+        network::events::{InputAcknowledged, InputSequence, PlayerInput},
+    };
+
+    use crate::network::{ClientEntityMapper, ClientMapping, ReceiveFromClient, SendToClient};
+
+    use super::{process_inputs, queue_inputs, InputQueue, MAX_QUEUED_INPUTS};
+
+    const CLIENT: ClientId = ClientId::from_raw(1);
+    const TICK: Duration = Duration::from_millis(100);
+
+    fn server() -> (App, Entity) {
+        let mut time = Time::<Fixed>::default();
+        time.advance_by(TICK);
+
+        let mut app = App::new();
+        app.insert_resource(time)
+            .init_resource::<ClientEntityMapper>()
+            .add_event::<ReceiveFromClient<PlayerInput>>()
+            .add_event::<SendToClient<InputAcknowledged>>()
+            .add_systems(Update, (queue_inputs, process_inputs).chain());
+        let player = app
+            .world
+            .spawn((
+                ClientMapping { id: CLIENT },
+                InputQueue::default(),
+                PlayerInput::default(),
+                Transform::default(),
+            ))
+            .id();
+        app.world
+            .resource_mut::<ClientEntityMapper>()
+            .clients
+            .insert(CLIENT.raw(), player);
+        (app, player)
+    }
+
+    fn send(app: &mut App, sequence: InputSequence) {
+        app.world.send_event(ReceiveFromClient {
+            client: CLIENT,
+            message: PlayerInput {
+                sequence,
+                forward: 1,
+                ..Default::default()
+            },
+        });
+    }
+
+    // The sequence acknowledged on the next tick, if any
+    fn tick(app: &mut App) -> Option<InputSequence> {
+        app.update();
+        let events = app.world.resource::<Events<SendToClient<InputAcknowledged>>>();
+        let acknowledged: Vec<_> = events.iter_current_update_events().collect();
+        assert!(acknowledged.len() <= 1);
+        acknowledged.first().map(|event| event.message.sequence)
+    }
+
+    #[test]
+    fn inputs_arriving_together_are_applied_a_tick_at_a_time() {
+        let (mut app, player) = server();
+        for sequence in 1..=3 {
+            send(&mut app, sequence);
+        }
+
+        let per_tick = PLAYER_SPEED * TICK.as_secs_f32();
+        for sequence in 1..=3 {
+            assert_eq!(tick(&mut app), Some(sequence));
+            let translation = app.world.get::<Transform>(player).unwrap().translation;
+            assert!((translation.y - sequence as f32 * per_tick).abs() < 0.001);
+        }
+        assert_eq!(tick(&mut app), None);
+    }
+
+    #[test]
+    fn the_oldest_inputs_are_dropped_once_too_many_are_waiting() {
+        let (mut app, _) = server();
+        let sent = MAX_QUEUED_INPUTS as InputSequence + 4;
+        for sequence in 1..=sent {
+            send(&mut app, sequence);
+        }
+
+        // The first tick applies one of the inputs that were kept
+        assert_eq!(tick(&mut app), Some(5));
+    }
+}
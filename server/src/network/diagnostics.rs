@@ -0,0 +1,134 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics},
+    ecs::system::{Res, ResMut, Resource},
+    log::info,
+};
+use bevy_renet::renet::{ClientId, NetworkInfo, RenetServer};
+use common::network::{
+    channels::MessageChannel,
+    message::MessageTypeID,
+    metrics::{queued_bytes, MessageTraffic},
+};
+
+pub const CONNECTED_CLIENTS: DiagnosticId =
+    DiagnosticId::from_u128(0x6e65_7477_6f72_6b00_0000_0000_0000_0101);
+pub const MEAN_RTT: DiagnosticId =
+    DiagnosticId::from_u128(0x6e65_7477_6f72_6b00_0000_0000_0000_0102);
+pub const BYTES_SENT: DiagnosticId =
+    DiagnosticId::from_u128(0x6e65_7477_6f72_6b00_0000_0000_0000_0103);
+pub const BYTES_RECEIVED: DiagnosticId =
+    DiagnosticId::from_u128(0x6e65_7477_6f72_6b00_0000_0000_0000_0104);
+
+const HISTORY_LENGTH: usize = 120;
+pub const STATS_LOG_INTERVAL: Duration = Duration::from_secs(60);
+// Message types logged for each client
+const LOGGED_MESSAGE_TYPES: usize = 4;
+
+pub fn network_diagnostics() -> [Diagnostic; 4] {
+    [
+        Diagnostic::new(CONNECTED_CLIENTS, "network_clients", HISTORY_LENGTH),
+        Diagnostic::new(MEAN_RTT, "network_mean_rtt", HISTORY_LENGTH).with_suffix("ms"),
+        Diagnostic::new(BYTES_SENT, "network_bytes_sent", HISTORY_LENGTH).with_suffix("B/s"),
+        Diagnostic::new(BYTES_RECEIVED, "network_bytes_received", HISTORY_LENGTH)
+            .with_suffix("B/s"),
+    ]
+}
+
+#[derive(Default)]
+pub struct ClientStats {
+    pub traffic: MessageTraffic,
+    pub info: Option<NetworkInfo>,
+    pub queued: [usize; 4],
+}
+
+// How the connection to each client is doing. The traffic is counted as messages are sent and
+// received, the rest is read from renet each frame
+#[derive(Resource, Default)]
+pub struct ClientNetworkStats {
+    pub clients: HashMap<ClientId, ClientStats>,
+}
+
+impl ClientNetworkStats {
+    pub fn record_sent(&mut self, client: ClientId, message_type: MessageTypeID, bytes: usize) {
+        let stats = self.clients.entry(client).or_default();
+        stats.traffic.record_sent(message_type, bytes);
+    }
+
+    pub fn record_received(&mut self, client: ClientId, message_type: MessageTypeID, bytes: usize) {
+        let stats = self.clients.entry(client).or_default();
+        stats.traffic.record_received(message_type, bytes);
+    }
+}
+
+pub fn update_client_stats(
+    server: Res<RenetServer>,
+    mut stats: ResMut<ClientNetworkStats>,
+    mut diagnostics: Diagnostics,
+) {
+    let clients = server.clients_id();
+    stats.clients.retain(|client, _| clients.contains(client));
+
+    for client in clients {
+        let Ok(info) = server.network_info(client) else {
+            continue;
+        };
+        let client_stats = stats.clients.entry(client).or_default();
+        client_stats.info = Some(info);
+        client_stats.queued =
+            queued_bytes(|channel| server.channel_available_memory(client, channel));
+    }
+
+    let infos: Vec<&NetworkInfo> = stats
+        .clients
+        .values()
+        .filter_map(|client_stats| client_stats.info.as_ref())
+        .collect();
+
+    diagnostics.add_measurement(CONNECTED_CLIENTS, || infos.len() as f64);
+    // renet measures round trips in seconds
+    if !infos.is_empty() {
+        diagnostics.add_measurement(MEAN_RTT, || {
+            infos.iter().map(|info| info.rtt * 1000.0).sum::<f64>() / infos.len() as f64
+        });
+    }
+    diagnostics.add_measurement(BYTES_SENT, || {
+        infos.iter().map(|info| info.bytes_sent_per_second).sum()
+    });
+    diagnostics.add_measurement(BYTES_RECEIVED, || {
+        infos.iter().map(|info| info.bytes_received_per_second).sum()
+    });
+}
+
+pub fn log_client_stats(stats: Res<ClientNetworkStats>) {
+    for (client, client_stats) in &stats.clients {
+        if let Some(info) = &client_stats.info {
+            info!(
+                "Client {}: RTT {:.0}ms, loss {:.1}%, in {:.1}KB/s, out {:.1}KB/s",
+                client,
+                info.rtt * 1000.0,
+                info.packet_loss * 100.0,
+                info.bytes_received_per_second / 1024.0,
+                info.bytes_sent_per_second / 1024.0
+            );
+        }
+
+        let queued: Vec<String> = MessageChannel::ALL
+            .iter()
+            .zip(client_stats.queued)
+            .filter(|(_, queued)| *queued > 0)
+            .map(|(channel, queued)| format!("{:?} {}B", channel, queued))
+            .collect();
+        if !queued.is_empty() {
+            info!("Client {} queued: {}", client, queued.join(", "));
+        }
+
+        for (name, sent, received) in client_stats.traffic.busiest(LOGGED_MESSAGE_TYPES) {
+            info!(
+                "Client {} {}: sent {} ({}B), received {} ({}B)",
+                client, name, sent.messages, sent.bytes, received.messages, received.bytes
+            );
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::{
+    ecs::{
+        event::{Event, EventReader, EventWriter},
+        system::{Res, ResMut, Resource, SystemParam},
+    },
+    log::info,
+    time::Time,
+};
+use bevy_renet::renet::{ClientId, RenetServer};
+use common::{
+    materials::MaterialManager,
+    network::{
+        events::{Handshake, HandshakeAccepted, HandshakeRejected},
+        handshake::{check_handshake, Version},
+        settings::NetworkSettings,
+    },
+};
+
+use super::{ReceiveFromClient, SendToClient};
+
+const HANDSHAKE_TIMEOUT_SECONDS: f64 = 10.0;
+// Long enough for the rejection to reach the client before the connection goes
+const REJECTION_GRACE_SECONDS: f64 = 1.0;
+
+// Sent once a client's handshake has been accepted, which is when it gets a player
+#[derive(Event)]
+pub struct ClientAccepted {
+    pub client: ClientId,
+}
+
+#[derive(Resource, Default)]
+pub struct ClientHandshakes {
+    accepted: HashSet<ClientId>,
+    // When each client still waiting to send a handshake was first seen
+    waiting: HashMap<ClientId, f64>,
+    // When each rejected client is to be disconnected
+    rejected: HashMap<ClientId, f64>,
+}
+
+impl ClientHandshakes {
+    pub fn is_accepted(&self, client: ClientId) -> bool {
+        self.accepted.contains(&client)
+    }
+}
+
+// Where the outcome of a handshake goes: a reply to the client and, once it's accepted, the rest
+// of the server
+#[derive(SystemParam)]
+pub struct HandshakeOutcomes<'w> {
+    accepted: EventWriter<'w, SendToClient<HandshakeAccepted>>,
+    rejected: EventWriter<'w, SendToClient<HandshakeRejected>>,
+    client_accepted: EventWriter<'w, ClientAccepted>,
+}
+
+pub fn receive_handshakes(
+    time: Res<Time>,
+    settings: Res<NetworkSettings>,
+    material_manager: Res<MaterialManager>,
+    mut handshake_events: EventReader<ReceiveFromClient<Handshake>>,
+    mut handshakes: ResMut<ClientHandshakes>,
+    mut outcomes: HandshakeOutcomes,
+) {
+    let content_hash = material_manager.content_hash().unwrap_or_default();
+    for event in handshake_events.read() {
+        let client = event.client;
+        if handshakes.accepted.contains(&client) || handshakes.rejected.contains_key(&client) {
+            continue;
+        }
+        handshakes.waiting.remove(&client);
+
+        match check_handshake(&event.message, content_hash) {
+            Ok(()) => {
+                info!("Accepted {}", event.message);
+                handshakes.accepted.insert(client);
+                outcomes.accepted.send(SendToClient {
+                    client: Some(client),
+                    message: HandshakeAccepted {
+                        server_version: Version::common(),
+                        tick_rate: settings.tick_rate,
+                    },
+                });
+                outcomes.client_accepted.send(ClientAccepted { client });
+            }
+            Err(reason) => {
+                info!("Rejected {} ({:?})", event.message, reason);
+                handshakes
+                    .rejected
+                    .insert(client, time.elapsed_seconds_f64() + REJECTION_GRACE_SECONDS);
+                outcomes.rejected.send(SendToClient {
+                    client: Some(client),
+                    message: HandshakeRejected { reason },
+                });
+            }
+        }
+    }
+}
+
+// Disconnects clients that were rejected, once they've had a chance to hear why, and ones that
+// never sent a handshake at all
+pub fn expire_handshakes(
+    time: Res<Time>,
+    mut server: ResMut<RenetServer>,
+    mut handshakes: ResMut<ClientHandshakes>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let clients = server.clients_id();
+    handshakes.accepted.retain(|client| clients.contains(client));
+    handshakes.waiting.retain(|client, _| clients.contains(client));
+    handshakes.rejected.retain(|client, _| clients.contains(client));
+
+    for client in clients {
+        if handshakes.accepted.contains(&client) {
+            continue;
+        }
+
+        let expired = match handshakes.rejected.get(&client) {
+            Some(disconnect_at) => now >= *disconnect_at,
+            None => {
+                let first_seen = *handshakes.waiting.entry(client).or_insert(now);
+                now - first_seen > HANDSHAKE_TIMEOUT_SECONDS
+            }
+        };
+        if expired {
+            info!("Disconnecting {} as it wasn't accepted", client);
+            server.disconnect(client);
+        }
+    }
+}
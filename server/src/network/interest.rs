@@ -0,0 +1,212 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::{
+    ecs::{
+        entity::Entity,
+        query::With,
+        system::{Query, Res, ResMut, Resource},
+    },
+    math::{IVec2, Vec3},
+    transform::components::Transform,
+};
+use bevy_renet::renet::{ClientId, RenetServer};
+use common::network::configuration::WorldID;
+
+use crate::world::InWorld;
+
+use super::{ClientEntityMapper, Replicate};
+
+#[derive(Resource)]
+pub struct InterestSettings {
+    // Entities within this distance of a client's player are sent to that client
+    pub radius: f32,
+    // Entities already in scope stay in until they're this much further away, so something sat on
+    // the edge isn't spawned and despawned over and over
+    pub hysteresis: f32,
+}
+
+impl Default for InterestSettings {
+    fn default() -> Self {
+        Self {
+            radius: 800.0,
+            hysteresis: 100.0,
+        }
+    }
+}
+
+// The replicated entities each client can currently see
+#[derive(Resource, Default)]
+pub struct ClientInterest {
+    relevant: HashMap<ClientId, HashSet<Entity>>,
+}
+
+impl ClientInterest {
+    pub fn is_relevant(&self, client: ClientId, entity: Entity) -> bool {
+        self.relevant
+            .get(&client)
+            .is_some_and(|relevant| relevant.contains(&entity))
+    }
+}
+
+// Buckets entities into square cells as wide as the leave radius, so only the 3x3 cells around a
+// player need to be checked
+struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<(Entity, Vec3)>>,
+}
+
+impl SpatialGrid {
+    fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, position: Vec3) -> IVec2 {
+        IVec2::new(
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn insert(&mut self, entity: Entity, position: Vec3) {
+        let cell = self.cell(position);
+        self.cells.entry(cell).or_default().push((entity, position));
+    }
+
+    fn near(&self, position: Vec3) -> impl Iterator<Item = &(Entity, Vec3)> {
+        let centre = self.cell(position);
+        (-1..=1)
+            .flat_map(move |x| (-1..=1).map(move |y| centre + IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+    }
+}
+
+// Where a replicated entity is, if it's anywhere
+type Placement = (Entity, Option<&'static Transform>, Option<&'static InWorld>);
+
+pub fn update_interest(
+    server: Res<RenetServer>,
+    settings: Res<InterestSettings>,
+    mapper: Res<ClientEntityMapper>,
+    entities: Query<Placement, With<Replicate>>,
+    mut interest: ResMut<ClientInterest>,
+) {
+    let leave_radius = settings.radius + settings.hysteresis;
+    let cell_size = leave_radius.max(1.0);
+    let mut grids: HashMap<WorldID, SpatialGrid> = HashMap::new();
+    // Entities without a position or a world aren't anywhere in particular, so they're seen by
+    // every client in the same world, or by everyone if they aren't in one
+    let mut always_relevant: Vec<(Entity, Option<WorldID>)> = Vec::new();
+    for (entity, transform, world) in &entities {
+        match (transform, world) {
+            (Some(transform), Some(world)) => grids
+                .entry(world.0)
+                .or_insert_with(|| SpatialGrid::new(cell_size))
+                .insert(entity, transform.translation),
+            _ => always_relevant.push((entity, world.map(|world| world.0))),
+        }
+    }
+
+    let clients = server.clients_id();
+    interest.relevant.retain(|client, _| clients.contains(client));
+
+    for client in clients {
+        let player = mapper.clients.get(&client.raw()).copied();
+        let (viewer, viewer_world) = player
+            .and_then(|player| entities.get(player).ok())
+            .map_or((None, None), |(_, transform, world)| {
+                (transform.map(|transform| transform.translation), world.map(|world| world.0))
+            });
+
+        let previous = interest.relevant.remove(&client).unwrap_or_default();
+        let mut relevant: HashSet<Entity> = always_relevant
+            .iter()
+            .filter(|(_, world)| world.is_none() || *world == viewer_world)
+            .map(|(entity, _)| *entity)
+            .collect();
+        relevant.extend(player);
+
+        let grid = viewer_world.and_then(|world| grids.get(&world));
+        if let (Some(viewer), Some(grid)) = (viewer, grid) {
+            for (entity, position) in grid.near(viewer) {
+                let radius = if previous.contains(entity) {
+                    leave_radius
+                } else {
+                    settings.radius
+                };
+                if position.distance(viewer) <= radius {
+                    relevant.insert(*entity);
+                }
+            }
+        }
+
+        interest.relevant.insert(client, relevant);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        app::{App, Update},
+        ecs::entity::Entity,
+        transform::components::Transform,
+    };
+    use bevy_renet::renet::{ClientId, RenetServer};
+    use common::network::channels::connection_config;
+
+    use crate::{
+        network::{ClientEntityMapper, Replicate},
+        world::InWorld,
+    };
+
+    use super::{update_interest, ClientInterest, InterestSettings};
+
+    const CLIENT: ClientId = ClientId::from_raw(1);
+
+    // One client whose player sits at the origin of the default world
+    fn server() -> App {
+        let mut server = RenetServer::new(connection_config());
+        server.add_connection(CLIENT);
+
+        let mut app = App::new();
+        app.insert_resource(server)
+            .init_resource::<InterestSettings>()
+            .init_resource::<ClientEntityMapper>()
+            .init_resource::<ClientInterest>()
+            .add_systems(Update, update_interest);
+        let player = app
+            .world
+            .spawn((Transform::default(), InWorld(0), Replicate))
+            .id();
+        app.world
+            .resource_mut::<ClientEntityMapper>()
+            .clients
+            .insert(CLIENT.raw(), player);
+        app
+    }
+
+    fn relevant_at(app: &mut App, entity: Entity, x: f32) -> bool {
+        app.world.get_mut::<Transform>(entity).unwrap().translation.x = x;
+        app.update();
+        app.world.resource::<ClientInterest>().is_relevant(CLIENT, entity)
+    }
+
+    #[test]
+    fn entities_leave_further_out_than_they_enter() {
+        let mut app = server();
+        let entity = app
+            .world
+            .spawn((Transform::default(), InWorld(0), Replicate))
+            .id();
+
+        assert!(!relevant_at(&mut app, entity, 850.0));
+        assert!(relevant_at(&mut app, entity, 750.0));
+        assert!(relevant_at(&mut app, entity, 850.0));
+        assert!(!relevant_at(&mut app, entity, 950.0));
+        assert!(!relevant_at(&mut app, entity, 850.0));
+        assert!(relevant_at(&mut app, entity, 800.0));
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use bevy::{
+    ecs::{
+        system::Resource,
+        world::{FromWorld, World},
+    },
+    utils::thiserror::Error,
+};
+use bevy_renet::renet::ClientId;
+use common::network::{
+    events::{
+        GetPriceHistory, GetWorldState, Handshake, JoinWorld, PlayerInput, SnapshotAcknowledged,
+    },
+    message::{MessageTypeID, NetworkMessage},
+    settings::NetworkSettings,
+};
+
+// Penalties drain away at this many points a second, so only a client that keeps misbehaving is
+// disconnected
+const PENALTY_DECAY_PER_SECOND: f64 = 1.0;
+const MAX_PENALTY: f64 = 20.0;
+
+#[derive(Clone, Copy)]
+pub struct MessageLimit {
+    pub max_bytes: usize,
+    // Messages a client can send each second on average, with up to `burst` at once
+    pub per_second: f64,
+    pub burst: f64,
+}
+
+impl MessageLimit {
+    const fn new(max_bytes: usize, per_second: f64, burst: f64) -> Self {
+        Self {
+            max_bytes,
+            per_second,
+            burst,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct MessageLimits {
+    default: MessageLimit,
+    by_type: HashMap<MessageTypeID, MessageLimit>,
+}
+
+impl MessageLimits {
+    pub fn get(&self, message_type: MessageTypeID) -> MessageLimit {
+        self.by_type.get(&message_type).copied().unwrap_or(self.default)
+    }
+}
+
+// Messages sent every tick are allowed twice the tick rate, so a client catching up after a stall
+// isn't punished. Ones that make the server send a lot back are kept much rarer
+impl FromWorld for MessageLimits {
+    fn from_world(world: &mut World) -> Self {
+        let tick_rate = world.resource::<NetworkSettings>().tick_rate;
+        let per_tick = MessageLimit::new(64, 2.0 * tick_rate, tick_rate);
+
+        Self {
+            default: MessageLimit::new(1024, 10.0, 20.0),
+            by_type: HashMap::from([
+                (PlayerInput::TYPE_ID, per_tick),
+                (SnapshotAcknowledged::TYPE_ID, per_tick),
+                (GetWorldState::TYPE_ID, MessageLimit::new(64, 0.5, 3.0)),
+                (GetPriceHistory::TYPE_ID, MessageLimit::new(64, 2.0, 10.0)),
+                (JoinWorld::TYPE_ID, MessageLimit::new(64, 1.0, 3.0)),
+                (Handshake::TYPE_ID, MessageLimit::new(256, 1.0, 2.0)),
+            ]),
+        }
+    }
+}
+
+#[derive(Error, Clone, Copy, Debug)]
+pub enum Violation {
+    #[error("Message of {bytes} bytes is larger than its type allows")]
+    Oversized { bytes: usize },
+    #[error("Messages are arriving faster than their type allows")]
+    RateLimited,
+    #[error("Message couldn't be read")]
+    Malformed,
+}
+
+impl Violation {
+    fn penalty(self) -> f64 {
+        match self {
+            // Sending a little too fast can happen by accident, sending garbage can't
+            Violation::RateLimited => 1.0,
+            Violation::Oversized { .. } | Violation::Malformed => 10.0,
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: f64,
+}
+
+#[derive(Default)]
+struct ClientLimit {
+    buckets: HashMap<MessageTypeID, TokenBucket>,
+    penalty: f64,
+    penalty_updated: f64,
+}
+
+#[derive(Resource, Default)]
+pub struct ClientLimits {
+    clients: HashMap<ClientId, ClientLimit>,
+}
+
+impl ClientLimits {
+    pub fn retain(&mut self, clients: &[ClientId]) {
+        self.clients.retain(|client, _| clients.contains(client));
+    }
+
+    // Takes one message's worth from the client's allowance for the type
+    pub fn check(
+        &mut self,
+        client: ClientId,
+        message_type: MessageTypeID,
+        limit: MessageLimit,
+        bytes: usize,
+        now: f64,
+    ) -> Result<(), Violation> {
+        if bytes > limit.max_bytes {
+            return Err(Violation::Oversized { bytes });
+        }
+
+        let bucket = self
+            .clients
+            .entry(client)
+            .or_default()
+            .buckets
+            .entry(message_type)
+            .or_insert(TokenBucket {
+                tokens: limit.burst,
+                updated: now,
+            });
+        bucket.tokens =
+            (bucket.tokens + (now - bucket.updated) * limit.per_second).min(limit.burst);
+        bucket.updated = now;
+        if bucket.tokens < 1.0 {
+            return Err(Violation::RateLimited);
+        }
+
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+
+    // Whether the client has misbehaved enough to be disconnected
+    pub fn penalise(&mut self, client: ClientId, violation: Violation, now: f64) -> bool {
+        let limit = self.clients.entry(client).or_default();
+        let decayed = (now - limit.penalty_updated) * PENALTY_DECAY_PER_SECOND;
+        limit.penalty = (limit.penalty - decayed).max(0.0) + violation.penalty();
+        limit.penalty_updated = now;
+        limit.penalty >= MAX_PENALTY
+    }
+}
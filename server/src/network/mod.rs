@@ -1,8 +1,20 @@
 mod network_plugin;
 mod client_entity_mapper;
+mod diagnostics;
 mod event_types;
+mod handshake;
+mod interest;
+mod limits;
+mod recording;
+mod replication;
+mod sessions;
+mod snapshots;
 
 pub use network_plugin::NetworkPlugin;
 pub use client_entity_mapper::{ClientEntityMapper, ClientMapping};
+pub use interest::{update_interest, ClientInterest};
+pub use recording::{Recorder, RecordingHeader, Replay};
+pub use replication::Replicate;
+pub use snapshots::send_snapshots;
 
 pub use event_types::{SendToClient, ReceiveFromClient};
\ No newline at end of file
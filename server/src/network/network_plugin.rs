@@ -1,125 +1,203 @@
-use std::{fmt::Display, net::UdpSocket, time::SystemTime};
+use std::{
+    collections::HashMap,
+    io,
+    net::{SocketAddr, UdpSocket},
+    time::SystemTime,
+};
 
 use bevy::{
-    app::{App, FixedUpdate, Plugin, Update},
+    app::{App, AppExit, FixedUpdate, Plugin, PostUpdate, Startup, Update},
     ecs::{
         event::{EventReader, EventWriter},
         schedule::{
             common_conditions::{in_state, resource_exists},
             IntoSystemConfigs,
         },
-        system::{Commands, ResMut},
+        system::{Commands, Local, Res, ResMut, SystemParam},
+        world::{Mut, World},
     },
-    log::{debug, info, warn},
+    diagnostic::RegisterDiagnostic,
+    log::{debug, error, info, warn},
+    time::{common_conditions::on_timer, Time},
     transform::components::Transform,
+    utils::thiserror::Error,
 };
 
 use bevy_renet::{
     renet::{
-        transport::{NetcodeServerTransport, ServerAuthentication, ServerConfig},
-        ConnectionConfig, DefaultChannel, RenetServer, ServerEvent,
+        transport::{
+            generate_random_bytes, NetcodeServerTransport, ServerAuthentication, ServerConfig,
+        },
+        ClientId, RenetServer, ServerEvent,
     },
     transport::NetcodeServerPlugin,
     RenetServerPlugin,
 };
 
+use common::components::Appearance;
 use common::network::{
-    configuration::{PROTOCOL_ID, SERVER_SOCKET_ADDRESS},
-    events::{
-        CreateEntity, DestroyEntity, EntityPosition, GetPlayerEntity, GetWorldState, PlayerEntity,
-        PlayerInput,
+    channels::{
+        connection_config, read_sequenced, write_sequenced, IncomingSequence, MessageChannel,
+        OutgoingSequence,
     },
+    conditions::SimulatedLink,
+    configuration::{DEFAULT_WORLD, PROTOCOL_ID},
+    events::{Handshake, PlayerInput},
+    login::account_from_user_data,
+    message::{encode_message, Envelope, MessageTypeID, NetworkMessage},
+    message_router::MessageRouter,
+    protocol::{register_protocol, ProtocolRegistrar},
+    replication::register_replicated_components,
+    settings::NetworkSettings,
 };
-use serde::{Deserialize, Serialize};
 
-use crate::{network::ClientMapping, ServerState};
+use crate::{
+    economy::{Inventory, Wallet, STARTING_CREDITS},
+    game_clock::ServerTick,
+    login::{private_key_from_environment, spawn_login_service},
+    movement::InputQueue,
+    network::ClientMapping,
+    world::InWorld,
+    ServerState,
+};
 
-use super::{ClientEntityMapper, ReceiveFromClient, SendToClient};
+use super::{
+    diagnostics::{
+        log_client_stats, network_diagnostics, update_client_stats, ClientNetworkStats,
+        STATS_LOG_INTERVAL,
+    },
+    handshake::{expire_handshakes, receive_handshakes, ClientAccepted, ClientHandshakes},
+    interest::{ClientInterest, InterestSettings},
+    limits::{ClientLimits, MessageLimits, Violation},
+    recording::{
+        exchange_replay_packets, finish_replay, flush_recording, replay_events, RecordedEvent,
+        Recorder, Replay, RECORDING_FLUSH_INTERVAL,
+    },
+    replication::{send_replication, Replicate},
+    sessions::{expire_sessions, Sessions},
+    snapshots::{
+        receive_snapshot_acknowledgements, send_snapshots, ClientSnapshotStates, SnapshotSettings,
+    },
+    ClientEntityMapper, ReceiveFromClient, SendToClient,
+};
 
-pub struct NetworkPlugin;
+const PLAYER_APPEARANCE: Appearance = Appearance {
+    colour: [0.1, 0.3, 0.7, 1.0],
+    size: [25.0, 25.0],
+};
 
-enum NetworkEventDirection {
-    Send,
-    Receive,
-    Both,
+pub struct NetworkPlugin {
+    // Whether to issue connect tokens from this process, rather than leaving it to a separate
+    // login service sharing the private key
+    pub login_service: bool,
 }
 
-trait NetworkEventAdder {
-    fn register_network_event<T>(&mut self, direction: NetworkEventDirection) -> &mut Self
-    where
-        T: Display + Serialize + for<'a> Deserialize<'a> + Sync + Send + 'static;
-}
+// Registers the server's side of each message declared in the shared protocol
+struct ServerProtocolRegistrar<'a>(&'a mut App);
 
-impl NetworkEventAdder for App {
-    fn register_network_event<T>(&mut self, direction: NetworkEventDirection) -> &mut Self
-    where
-        T: Display + Serialize + for<'a> Deserialize<'a> + Sync + Send + 'static,
-    {
-        match direction {
-            NetworkEventDirection::Send => {
-                self.add_event::<SendToClient<T>>();
-                self.add_systems(
-                    Update,
-                    (send_messages::<T>)
-                        .run_if(resource_exists::<RenetServer>())
-                        .run_if(in_state(ServerState::Running)),
-                );
-            }
-            NetworkEventDirection::Receive => {
-                self.add_event::<ReceiveFromClient<T>>();
-                self.add_systems(
-                    Update,
-                    (receive_messages::<T>)
-                        .run_if(resource_exists::<RenetServer>())
-                        .run_if(in_state(ServerState::Running)),
-                );
-            }
-            NetworkEventDirection::Both => {
-                self.add_event::<SendToClient<T>>();
-                self.add_event::<ReceiveFromClient<T>>();
-                self.add_systems(
-                    Update,
-                    (send_messages::<T>, receive_messages::<T>)
-                        .run_if(resource_exists::<RenetServer>())
-                        .run_if(in_state(ServerState::Running)),
-                );
-            }
-        };
-        self
+impl ProtocolRegistrar for ServerProtocolRegistrar<'_> {
+    fn register<T: NetworkMessage>(&mut self) {
+        let app = &mut *self.0;
+        if T::DIRECTION.sent_by_server() {
+            app.add_event::<SendToClient<T>>();
+            app.add_systems(
+                Update,
+                (send_messages::<T>)
+                    .run_if(resource_exists::<RenetServer>())
+                    .run_if(in_state(ServerState::Running)),
+            );
+        }
+        if T::DIRECTION.sent_by_client() {
+            app.add_event::<ReceiveFromClient<T>>();
+            app.world
+                .resource_mut::<MessageRouter<ClientId>>()
+                .add::<T, _>(|client, message| ReceiveFromClient { client, message });
+        }
     }
 }
 
 impl Plugin for NetworkPlugin {
     fn build(&self, app: &mut App) {
-        let socket = UdpSocket::bind(SERVER_SOCKET_ADDRESS).unwrap();
-        let current_time = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap();
-
-        let server_config = ServerConfig {
-            current_time,
-            max_clients: 64,
-            protocol_id: PROTOCOL_ID,
-            public_addresses: vec![SERVER_SOCKET_ADDRESS],
-            authentication: ServerAuthentication::Unsecure,
-        };
-
-        let transport = NetcodeServerTransport::new(server_config, socket).unwrap();
-        let server = RenetServer::new(ConnectionConfig::default());
+        let settings = app.world.resource::<NetworkSettings>().clone();
+        // A replay brings its own clients, so nothing is listened for
+        if !app.world.contains_resource::<Replay>() {
+            if let Err(error) = add_transport(app, &settings, self.login_service) {
+                error!("{}", error);
+                app.add_systems(Startup, exit);
+            }
+        }
 
         app.add_plugins(RenetServerPlugin);
-        app.add_plugins(NetcodeServerPlugin);
-        app.insert_resource(server);
-        app.insert_resource(transport);
+        app.insert_resource(RenetServer::new(connection_config()));
         app.init_resource::<ClientEntityMapper>();
+        app.init_resource::<MessageRouter<ClientId>>();
+        app.init_resource::<SnapshotSettings>();
+        app.init_resource::<ClientSnapshotStates>();
+        app.init_resource::<InterestSettings>();
+        app.init_resource::<ClientInterest>();
+        app.init_resource::<ClientHandshakes>();
+        app.init_resource::<Sessions>();
+        app.init_resource::<MessageLimits>();
+        app.init_resource::<ClientLimits>();
+        app.init_resource::<ClientNetworkStats>();
+        for diagnostic in network_diagnostics() {
+            app.register_diagnostic(diagnostic);
+        }
+        app.add_event::<ClientAccepted>();
+
+        register_protocol(&mut ServerProtocolRegistrar(app));
+        register_replicated_components(app);
+
+        app.add_systems(
+            Update,
+            (
+                replay_events.run_if(resource_exists::<Replay>()),
+                clear_disconnected_messages.run_if(resource_exists::<SimulatedLink<ClientId>>()),
+                receive_messages,
+                receive_snapshot_acknowledgements,
+                receive_handshakes,
+                expire_handshakes,
+                spawn_players,
+                expire_sessions,
+            )
+                .chain()
+                .run_if(resource_exists::<RenetServer>())
+                .run_if(in_state(ServerState::Running)),
+        );
+
+        app.add_systems(
+            FixedUpdate,
+            send_replication
+                .after(send_snapshots)
+                .run_if(resource_exists::<RenetServer>())
+                .run_if(in_state(ServerState::Running)),
+        );
+
+        app.add_systems(
+            Update,
+            (
+                update_client_stats,
+                log_client_stats.run_if(on_timer(STATS_LOG_INTERVAL)),
+            )
+                .chain()
+                .run_if(resource_exists::<RenetServer>())
+                .run_if(in_state(ServerState::Running)),
+        );
 
-        app.register_network_event::<PlayerInput>(NetworkEventDirection::Receive)
-            .register_network_event::<EntityPosition>(NetworkEventDirection::Send)
-            .register_network_event::<CreateEntity>(NetworkEventDirection::Send)
-            .register_network_event::<DestroyEntity>(NetworkEventDirection::Send)
-            .register_network_event::<GetWorldState>(NetworkEventDirection::Receive)
-            .register_network_event::<GetPlayerEntity>(NetworkEventDirection::Receive)
-            .register_network_event::<PlayerEntity>(NetworkEventDirection::Send);
+        app.add_systems(
+            Update,
+            (
+                flush_recording
+                    .run_if(resource_exists::<Recorder>())
+                    .run_if(on_timer(RECORDING_FLUSH_INTERVAL)),
+                finish_replay.run_if(resource_exists::<Replay>()),
+            )
+                .run_if(in_state(ServerState::Running)),
+        );
+        app.add_systems(
+            PostUpdate,
+            exchange_replay_packets.run_if(resource_exists::<Replay>()),
+        );
 
         app.add_systems(
             FixedUpdate,
@@ -130,112 +208,390 @@ impl Plugin for NetworkPlugin {
     }
 }
 
-fn send_messages<T: Display + Serialize + for<'a> Deserialize<'a> + Sync + Send + 'static>(
+#[derive(Error, Debug)]
+enum TransportError {
+    #[error("Failed to start the login service ({0})")]
+    LoginService(io::Error),
+    #[error("Failed to listen for clients on {address} ({error})")]
+    Socket { address: SocketAddr, error: io::Error },
+}
+
+fn add_transport(
+    app: &mut App,
+    settings: &NetworkSettings,
+    login_service: bool,
+) -> Result<(), TransportError> {
+    let private_key = private_key_from_environment().unwrap_or_else(|| {
+        warn!("No private key is configured so only this process can issue connect tokens");
+        generate_random_bytes()
+    });
+    if login_service {
+        spawn_login_service(private_key, settings).map_err(TransportError::LoginService)?;
+    }
+
+    let address = settings.server_bind_address();
+    let socket_error = |error| TransportError::Socket { address, error };
+    let socket = UdpSocket::bind(address).map_err(socket_error)?;
+    let current_time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+
+    let server_config = ServerConfig {
+        current_time,
+        max_clients: settings.max_clients,
+        protocol_id: PROTOCOL_ID,
+        public_addresses: vec![settings.server_public_address()],
+        authentication: ServerAuthentication::Secure { private_key },
+    };
+
+    let transport = NetcodeServerTransport::new(server_config, socket).map_err(socket_error)?;
+
+    if !settings.conditions.is_perfect() {
+        warn!("Simulating network conditions {:?}", settings.conditions);
+        app.insert_resource(SimulatedLink::<ClientId>::new(settings.conditions));
+    }
+
+    app.add_plugins(NetcodeServerPlugin);
+    app.insert_resource(transport);
+    Ok(())
+}
+
+// A server nobody can connect to has nothing to do, so it stops after its first update
+fn exit(mut exit_events: EventWriter<AppExit>) {
+    exit_events.send(AppExit);
+}
+
+// Sequenced messages are numbered per client, so a message broadcast on a sequenced channel is
+// sent to each client on its own
+fn send_messages<T: NetworkMessage>(
     mut server: ResMut<RenetServer>,
     mut reader: EventReader<SendToClient<T>>,
+    mut sequences: Local<HashMap<ClientId, OutgoingSequence>>,
+    mut stats: ResMut<ClientNetworkStats>,
 ) {
-    reader.read().for_each(|event| {
-        let serialisation_result = bincode::serde::encode_to_vec(&event.message, bincode::config::standard());
-        match serialisation_result {
-            Ok(serialised_message) => match event.client {
-                Some(receiver) => {
-                    debug!("Sent a message to {}, ({})", receiver.raw(), event.message);
-                    server.send_message(
-                        receiver,
-                        DefaultChannel::ReliableOrdered,
-                        serialised_message,
-                    );
-                }
-                None => {
-                    debug!("Broadcast a message ({})", event.message);
-                    server.broadcast_message(DefaultChannel::ReliableOrdered, serialised_message);
-                }
-            },
+    let clients = server.clients_id();
+    sequences.retain(|client, _| clients.contains(client));
+
+    for event in reader.read() {
+        let message = match encode_message(&event.message) {
+            Ok(message) => message,
             Err(serialisation_error) => {
                 warn!(
                     "Tried to serialise a message but failed ({})",
                     serialisation_error
                 );
+                continue;
             }
+        };
+
+        let receivers = match event.client {
+            Some(receiver) => {
+                debug!("Sent a message to {}, ({})", receiver.raw(), event.message);
+                vec![receiver]
+            }
+            None => {
+                debug!("Broadcast a message ({})", event.message);
+                if !T::CHANNEL.is_sequenced() {
+                    for client in &clients {
+                        stats.record_sent(*client, T::TYPE_ID, message.len());
+                    }
+                    server.broadcast_message(T::CHANNEL, message);
+                    continue;
+                }
+                clients.clone()
+            }
+        };
+
+        for receiver in receivers {
+            let message = if T::CHANNEL.is_sequenced() {
+                let sequence = sequences.entry(receiver).or_default().advance();
+                write_sequenced(sequence, message.clone())
+            } else {
+                message.clone()
+            };
+            stats.record_sent(receiver, T::TYPE_ID, message.len());
+            server.send_message(receiver, T::CHANNEL, message);
         }
-    })
+    }
 }
 
-fn receive_messages<T: Display + Serialize + for<'a> Deserialize<'a> + Sync + Send + 'static>(
-    mut server: ResMut<RenetServer>,
-    mut writer: EventWriter<ReceiveFromClient<T>>,
+// Client IDs come from the account, so anything still held back from a client that's gone would
+// otherwise be delivered to it when it logs in again
+fn clear_disconnected_messages(
+    mut server_events: EventReader<ServerEvent>,
+    mut link: ResMut<SimulatedLink<ClientId>>,
 ) {
-    for client in server.clients_id() {
-        while let Some(message) = server.receive_message(client, DefaultChannel::ReliableOrdered) {
-            let type_name = std::any::type_name::<T>();
-            if !message.starts_with(type_name.as_bytes()) {
-                warn!("Got a message that may be of type {} but doesn't fit the type", type_name);
-                continue;
+    for event in server_events.read() {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = event {
+            link.clear_sender(*client_id);
+        }
+    }
+}
+
+// Every message from every client goes through here, so no message can be taken off the channel
+// by a system that doesn't know how to decode it. Messages over their size or rate limit are
+// dropped before they're decoded, and a client that keeps breaking the limits or sending ones
+// that can't be decoded is disconnected
+fn receive_messages(world: &mut World, mut sequences: Local<HashMap<ClientId, IncomingSequence>>) {
+    let now = world.resource::<Time>().elapsed_seconds_f64();
+    let tick = world.resource::<ServerTick>().0;
+    world.resource_scope(|world, mut server: Mut<RenetServer>| {
+        let clients = server.clients_id();
+        sequences.retain(|client, _| clients.contains(client));
+        world.resource_mut::<ClientLimits>().retain(&clients);
+
+        let mut received = Vec::new();
+        for client in &clients {
+            for channel in MessageChannel::ALL {
+                while let Some(bytes) = server.receive_message(*client, channel) {
+                    received.push((*client, channel, bytes.to_vec()));
+                }
             }
+        }
+        if let Some(mut link) = world.get_resource_mut::<SimulatedLink<ClientId>>() {
+            received = link.simulate(now, received);
+        }
 
-            let deserialisation_result = bincode::serde::decode_from_slice(&message, bincode::config::standard());
-            match deserialisation_result {
-                Ok((message, _)) => {
-                    debug!("Received a message from {}, ({})", client, message);
-                    writer.send(ReceiveFromClient { client, message });
+        world.resource_scope(|world, router: Mut<MessageRouter<ClientId>>| {
+            let mut disconnected = Vec::new();
+            for (client, channel, bytes) in received {
+                if disconnected.contains(&client) || !clients.contains(&client) {
+                    continue;
                 }
-                Err(deserialisation_error) => {
-                    warn!(
-                        "Failed to deserialise a message from {} ({})",
-                        client, deserialisation_error
-                    );
+
+                let message = if channel.is_sequenced() {
+                    match read_sequenced(&bytes) {
+                        Some((sequence, message))
+                            if sequences.entry(client).or_default().accept(sequence, message) =>
+                        {
+                            message
+                        }
+                        Some(_) => {
+                            debug!("Dropped a stale message from {}", client);
+                            continue;
+                        }
+                        None => {
+                            warn!(
+                                "Received a sequenced message from {} without a sequence",
+                                client
+                            );
+                            continue;
+                        }
+                    }
+                } else {
+                    &bytes[..]
+                };
+
+                let message_type = match check_limits(world, client, message, now) {
+                    Ok(message_type) => message_type,
+                    Err(violation) => {
+                        if report(world, &mut server, client, violation, now) {
+                            disconnected.push(client);
+                        }
+                        continue;
+                    }
+                };
+
+                // Until its handshake is accepted a client mightn't even agree on what each
+                // message means
+                let accepted = world.resource::<ClientHandshakes>().is_accepted(client);
+                if !accepted && message_type != Handshake::TYPE_ID {
+                    debug!("Dropped a message from {} before its handshake", client);
+                    continue;
+                }
+
+                if let Some(mut recorder) = world.get_resource_mut::<Recorder>() {
+                    recorder.record(tick, client, RecordedEvent::Message(message.to_vec()));
+                }
+
+                match router.dispatch(world, client, message) {
+                    Ok(message_type) => {
+                        debug!("Received a message of type {} from {}", message_type, client);
+                        world.resource_mut::<ClientNetworkStats>().record_received(
+                            client,
+                            message_type,
+                            message.len(),
+                        );
+                    }
+                    Err(error) => {
+                        warn!("Failed to receive a message from {} ({})", client, error);
+                        if report(world, &mut server, client, Violation::Malformed, now) {
+                            disconnected.push(client);
+                        }
+                    }
                 }
             }
+        });
+    });
+}
+
+fn check_limits(
+    world: &mut World,
+    client: ClientId,
+    message: &[u8],
+    now: f64,
+) -> Result<MessageTypeID, Violation> {
+    let message_type = Envelope::read(message)
+        .map_err(|_| Violation::Malformed)?
+        .message_type;
+    let limit = world.resource::<MessageLimits>().get(message_type);
+    world
+        .resource_mut::<ClientLimits>()
+        .check(client, message_type, limit, message.len(), now)?;
+    Ok(message_type)
+}
+
+// Returns whether the client has now been disconnected
+fn report(
+    world: &mut World,
+    server: &mut RenetServer,
+    client: ClientId,
+    violation: Violation,
+    now: f64,
+) -> bool {
+    debug!("Dropped a message from {} ({})", client, violation);
+    if !world.resource_mut::<ClientLimits>().penalise(client, violation, now) {
+        return false;
+    }
+
+    warn!("Disconnecting {} for breaking message limits ({})", client, violation);
+    server.disconnect(client);
+    true
+}
+
+// Where a connecting client's account comes from: its connect token, or the recording being
+// replayed
+#[derive(SystemParam)]
+struct AccountSources<'w> {
+    transport: Option<Res<'w, NetcodeServerTransport>>,
+    replay: Option<Res<'w, Replay>>,
+}
+
+impl AccountSources<'_> {
+    // Connect tokens are only issued with an account, so this only fails for a token from
+    // somewhere else
+    fn account(&self, client: ClientId) -> Option<String> {
+        match (&self.transport, &self.replay) {
+            (Some(transport), _) => transport
+                .user_data(client)
+                .and_then(|user_data| account_from_user_data(&user_data)),
+            (None, Some(replay)) => replay.account(client),
+            (None, None) => None,
         }
     }
 }
 
+// Records connections and disconnections, when there's a recording being made
+#[derive(SystemParam)]
+struct EventRecording<'w> {
+    tick: Res<'w, ServerTick>,
+    recorder: Option<ResMut<'w, Recorder>>,
+}
+
+impl EventRecording<'_> {
+    fn record(&mut self, client: ClientId, event: RecordedEvent) {
+        let tick = self.tick.0;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(tick, client, event);
+        }
+    }
+}
+
+// Which entity and session belong to each client
+#[derive(SystemParam)]
+struct ClientOwnership<'w> {
+    mapper: ResMut<'w, ClientEntityMapper>,
+    sessions: ResMut<'w, Sessions>,
+}
+
 fn handle_events(
+    time: Res<Time>,
     mut server_events: EventReader<ServerEvent>,
-    mut mapper: ResMut<ClientEntityMapper>,
+    mut server: ResMut<RenetServer>,
+    accounts: AccountSources,
+    mut recording: EventRecording,
+    mut ownership: ClientOwnership,
     mut commands: Commands,
-    mut create_event_writer: EventWriter<SendToClient<CreateEntity>>,
-    mut destroy_event_writer: EventWriter<SendToClient<DestroyEntity>>,
 ) {
     for event in server_events.read() {
         match event {
             ServerEvent::ClientConnected { client_id: client } => {
-                info!("Client connected: {}", client);
-
-                let entity = commands
-                    .spawn((
-                        ClientMapping { id: *client },
-                        Transform::IDENTITY,
-                        PlayerInput::default(),
-                    ))
-                    .id();
-                mapper.clients.insert(client.raw(), entity);
-                create_event_writer.send(SendToClient {
-                    client: None,
-                    message: CreateEntity { entity },
-                });
+                let Some(account) = accounts.account(*client) else {
+                    warn!("Client {} connected without an account", client);
+                    server.disconnect(*client);
+                    continue;
+                };
+                info!("Client connected: {} ({})", client, account);
+                let event = RecordedEvent::Connected {
+                    account: account.clone(),
+                };
+                recording.record(*client, event);
+                ownership.sessions.connect(*client, account);
             }
             ServerEvent::ClientDisconnected {
                 client_id: client,
                 reason,
             } => {
                 info!("Client Disconnected: {} ({})", client, reason);
-                match mapper.clients.get(&client.raw()) {
+                recording.record(*client, RecordedEvent::Disconnected);
+                match ownership.mapper.clients.remove(&client.raw()) {
+                    // The player stays where it was, doing nothing, in case it reconnects
                     Some(entity) => {
-                        commands.entity(*entity).despawn();
-                        destroy_event_writer.send(SendToClient {
-                            client: None,
-                            message: DestroyEntity {
-                                entity: entity.clone(),
-                            },
-                        });
-                        mapper.clients.remove(&client.raw());
+                        commands
+                            .entity(entity)
+                            .remove::<ClientMapping>()
+                            .insert(PlayerInput::default());
+                        let now = time.elapsed_seconds_f64();
+                        ownership.sessions.detach(*client, entity, now);
                     }
                     None => {
-                        warn!("Client disconnected but lacked an entity");
+                        debug!("Client disconnected before it was given an entity");
+                        ownership.sessions.disconnect(*client);
                     }
                 }
             }
         }
     }
 }
+
+fn spawn_players(
+    mut client_accepted_events: EventReader<ClientAccepted>,
+    server: Res<RenetServer>,
+    mut mapper: ResMut<ClientEntityMapper>,
+    mut sessions: ResMut<Sessions>,
+    mut commands: Commands,
+) {
+    for event in client_accepted_events.read() {
+        if !server.is_connected(event.client) {
+            continue;
+        }
+
+        // Inputs are numbered per connection, so the old connection's can't be compared with
+        // the new one's
+        if let Some(entity) = sessions.resume(event.client) {
+            info!("Client {} resumed its session", event.client);
+            commands.entity(entity).insert((
+                ClientMapping { id: event.client },
+                PlayerInput::default(),
+                InputQueue::default(),
+            ));
+            mapper.clients.insert(event.client.raw(), entity);
+            continue;
+        }
+
+        let entity = commands
+            .spawn((
+                ClientMapping { id: event.client },
+                Transform::IDENTITY,
+                InWorld(DEFAULT_WORLD),
+                PlayerInput::default(),
+                InputQueue::default(),
+                Wallet::new(STARTING_CREDITS),
+                Inventory::default(),
+                Replicate,
+                PLAYER_APPEARANCE,
+            ))
+            .id();
+        mapper.clients.insert(event.client.raw(), entity);
+    }
+}
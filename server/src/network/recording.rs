@@ -0,0 +1,201 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    time::Duration,
+};
+
+use bevy::{
+    app::AppExit,
+    ecs::{
+        entity::Entity,
+        event::EventWriter,
+        query::With,
+        system::{Query, Res, ResMut, Resource},
+        world::{Mut, World},
+    },
+    log::{info, warn},
+    time::Time,
+    transform::components::Transform,
+};
+use bevy_renet::renet::{ClientId, RenetClient, RenetServer};
+use common::network::{
+    channels::{connection_config, MessageChannel},
+    configuration::Tick,
+    message_router::MessageRouter,
+    recording::{read_recording, RecordingError, RecordingWriter},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{economy::Wallet, game_clock::ServerTick, movement::InputQueue};
+
+// At most this much of a recording is lost if the server dies without closing it
+pub const RECORDING_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct RecordingHeader {
+    pub seed: u64,
+    pub tick_rate: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum RecordedEvent {
+    Connected { account: String },
+    Disconnected,
+    // Only messages that got past the limits and the handshake are recorded, so replaying one
+    // always dispatches it
+    Message(Vec<u8>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordedEntry {
+    pub tick: Tick,
+    pub client: u64,
+    pub event: RecordedEvent,
+}
+
+// Writes down everything clients do to the world along with the tick it happened on
+#[derive(Resource)]
+pub struct Recorder {
+    writer: RecordingWriter,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, header: RecordingHeader) -> Result<Self, RecordingError> {
+        info!("Recording client messages to {}", path.display());
+        Ok(Self {
+            writer: RecordingWriter::create(path, &header)?,
+        })
+    }
+
+    pub fn record(&mut self, tick: Tick, client: ClientId, event: RecordedEvent) {
+        let entry = RecordedEntry {
+            tick,
+            client: client.raw(),
+            event,
+        };
+        if let Err(error) = self.writer.write(&entry) {
+            warn!("Failed to record a client event ({})", error);
+        }
+    }
+}
+
+pub fn flush_recording(mut recorder: ResMut<Recorder>) {
+    if let Err(error) = recorder.writer.flush() {
+        warn!("Failed to flush the recording ({})", error);
+    }
+}
+
+// Plays a recording back into a server with no transport. Each recorded client gets a connection
+// on the server and a renet client on this end that only acknowledges what it's sent, so the
+// server treats it the same as it did the real one
+#[derive(Resource)]
+pub struct Replay {
+    entries: VecDeque<RecordedEntry>,
+    end_tick: Tick,
+    accounts: HashMap<ClientId, String>,
+    clients: HashMap<ClientId, RenetClient>,
+    finished: bool,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<(RecordingHeader, Self), RecordingError> {
+        let (header, entries): (RecordingHeader, Vec<RecordedEntry>) = read_recording(path)?;
+        info!(
+            "Replaying {} client events from {} with seed {}",
+            entries.len(),
+            path.display(),
+            header.seed
+        );
+        // One more tick so the last messages are simulated
+        let end_tick = entries.last().map_or(0, |entry| entry.tick + 1);
+        let replay = Self {
+            entries: entries.into(),
+            end_tick,
+            accounts: HashMap::new(),
+            clients: HashMap::new(),
+            finished: false,
+        };
+        Ok((header, replay))
+    }
+
+    pub fn account(&self, client: ClientId) -> Option<String> {
+        self.accounts.get(&client).cloned()
+    }
+}
+
+// Takes the place of receiving messages, dispatching each one on the tick it arrived on before
+pub fn replay_events(world: &mut World) {
+    let tick = world.resource::<ServerTick>().0;
+    world.resource_scope(|world, mut replay: Mut<Replay>| {
+        world.resource_scope(|world, router: Mut<MessageRouter<ClientId>>| {
+            while replay.entries.front().is_some_and(|entry| entry.tick <= tick) {
+                let entry = replay.entries.pop_front().unwrap();
+                let client = ClientId::from_raw(entry.client);
+                match entry.event {
+                    RecordedEvent::Connected { account } => {
+                        let mut renet_client = RenetClient::new(connection_config());
+                        renet_client.set_connected();
+                        replay.clients.insert(client, renet_client);
+                        replay.accounts.insert(client, account);
+                        world.resource_mut::<RenetServer>().add_connection(client);
+                    }
+                    RecordedEvent::Disconnected => {
+                        replay.clients.remove(&client);
+                        world.resource_mut::<RenetServer>().remove_connection(client);
+                    }
+                    RecordedEvent::Message(bytes) => {
+                        if let Err(error) = router.dispatch(world, client, &bytes) {
+                            warn!("Failed to replay a message from {} ({})", client, error);
+                        }
+                    }
+                }
+            }
+        });
+    });
+}
+
+// Does the transport's job for the replayed clients, and throws away everything sent to them
+pub fn exchange_replay_packets(
+    time: Res<Time>,
+    mut server: ResMut<RenetServer>,
+    mut replay: ResMut<Replay>,
+) {
+    for (client_id, client) in &mut replay.clients {
+        client.update(time.delta());
+        if let Ok(packets) = server.get_packets_to_send(*client_id) {
+            for packet in packets {
+                client.process_packet(&packet);
+            }
+        }
+        for channel in MessageChannel::ALL {
+            while client.receive_message(channel).is_some() {}
+        }
+        for packet in client.get_packets_to_send() {
+            let _ = server.process_packet_from(&packet, *client_id);
+        }
+    }
+}
+
+// Logs where every player ended up, to compare with how the recorded session ended
+pub fn finish_replay(
+    tick: Res<ServerTick>,
+    mut replay: ResMut<Replay>,
+    players: Query<(Entity, &Transform), With<Wallet>>,
+    input_queues: Query<&InputQueue>,
+    mut exit_events: EventWriter<AppExit>,
+) {
+    if replay.finished || !replay.entries.is_empty() || tick.0 < replay.end_tick {
+        return;
+    }
+    // Inputs that arrived together are applied a tick apiece, so they can outlast the recording
+    if !input_queues.iter().all(InputQueue::is_empty) {
+        return;
+    }
+
+    info!("Replay finished at tick {}", tick.0);
+    for (entity, transform) in &players {
+        info!("{:?} is at {}", entity, transform.translation);
+    }
+    replay.finished = true;
+    exit_events.send(AppExit);
+}
@@ -0,0 +1,204 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::{
+    ecs::{component::Component, entity::Entity, query::With, system::Local, world::World},
+    log::warn,
+};
+use bevy_renet::renet::{ClientId, RenetServer};
+use common::network::{
+    events::{ReplicationChange, ReplicationUpdate},
+    replication::{ComponentID, ReplicationRegistry},
+};
+
+use crate::game_clock::ServerTick;
+
+use super::{ClientInterest, SendToClient};
+
+// Marks an entity to be spawned, along with its replicated components, on every client it's
+// relevant to
+#[derive(Component, Default)]
+pub struct Replicate;
+
+type EntityState = Vec<Option<Vec<u8>>>;
+
+// What one client has been sent about each replicated entity. Updates go over a reliable ordered
+// channel, so this is also what the client will have once it has caught up
+#[derive(Default)]
+pub struct ClientReplicationState {
+    entities: HashMap<Entity, EntityState>,
+}
+
+impl ClientReplicationState {
+    // Only entities in `changed` are compared component by component, the rest are just checked
+    // for coming into or going out of scope
+    fn diff(
+        &mut self,
+        current: &HashMap<Entity, EntityState>,
+        changed: &HashSet<Entity>,
+        is_relevant: impl Fn(Entity) -> bool,
+    ) -> Vec<ReplicationChange> {
+        let mut changes = Vec::new();
+
+        for (entity, state) in current {
+            if !is_relevant(*entity) {
+                continue;
+            }
+            let known = self.entities.get(entity);
+            if known.is_some() && !changed.contains(entity) {
+                continue;
+            }
+            if known.is_none() {
+                changes.push(ReplicationChange::Spawn(*entity));
+            }
+
+            for (index, data) in state.iter().enumerate() {
+                let component = index as ComponentID;
+                let previous = known.and_then(|known| known.get(index)).and_then(Option::as_ref);
+                match (previous, data) {
+                    (None, Some(data)) => changes.push(ReplicationChange::Insert {
+                        entity: *entity,
+                        component,
+                        data: data.clone(),
+                    }),
+                    (Some(previous), Some(data)) if previous != data => {
+                        changes.push(ReplicationChange::Update {
+                            entity: *entity,
+                            component,
+                            data: data.clone(),
+                        })
+                    }
+                    (Some(_), None) => changes.push(ReplicationChange::Remove {
+                        entity: *entity,
+                        component,
+                    }),
+                    _ => {}
+                }
+            }
+            self.entities.insert(*entity, state.clone());
+        }
+
+        self.entities.retain(|entity, _| {
+            let kept = current.contains_key(entity) && is_relevant(*entity);
+            if !kept {
+                changes.push(ReplicationChange::Despawn(*entity));
+            }
+            kept
+        });
+        changes
+    }
+}
+
+// The latest state of every replicated entity. Only entities with a replicated component added,
+// changed or removed since the last tick are serialised again
+#[derive(Default)]
+pub struct ReplicatedEntities {
+    states: HashMap<Entity, EntityState>,
+}
+
+impl ReplicatedEntities {
+    fn update(&mut self, world: &mut World) -> HashSet<Entity> {
+        let entities: HashSet<Entity> = world
+            .query_filtered::<Entity, With<Replicate>>()
+            .iter(world)
+            .collect();
+        self.states.retain(|entity, _| entities.contains(entity));
+
+        let registry = world.resource::<ReplicationRegistry>();
+        let mut changed = HashSet::new();
+        for entity in entities {
+            let known = self.states.get(&entity);
+            if known.is_some_and(|state| !registry.changed(world, entity, state)) {
+                continue;
+            }
+            match registry.serialise(world, entity) {
+                Ok(state) => {
+                    self.states.insert(entity, state);
+                    changed.insert(entity);
+                }
+                Err(error) => warn!(
+                    "Failed to serialise {:?} for replication ({})",
+                    entity, error
+                ),
+            }
+        }
+        changed
+    }
+}
+
+// Runs on the fixed tick, after interest has been updated, so a tick's changes go out together
+pub fn send_replication(
+    world: &mut World,
+    mut entities: Local<ReplicatedEntities>,
+    mut states: Local<HashMap<ClientId, ClientReplicationState>>,
+) {
+    let clients = world.resource::<RenetServer>().clients_id();
+    states.retain(|client, _| clients.contains(client));
+    // Changes are only seen by the next run, so this keeps running with nobody connected
+    let changed = entities.update(world);
+    if clients.is_empty() {
+        return;
+    }
+
+    // Entities coming into or going out of a client's scope turn into spawns and despawns
+    let tick = world.resource::<ServerTick>().0;
+    let interest = world.resource::<ClientInterest>();
+    let updates: Vec<SendToClient<ReplicationUpdate>> = clients
+        .into_iter()
+        .filter_map(|client| {
+            let changes =
+                states
+                    .entry(client)
+                    .or_default()
+                    .diff(&entities.states, &changed, |entity| {
+                        interest.is_relevant(client, entity)
+                    });
+            (!changes.is_empty()).then_some(SendToClient {
+                client: Some(client),
+                message: ReplicationUpdate { tick, changes },
+            })
+        })
+        .collect();
+    world.send_event_batch(updates);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::{
+        entity::Entity,
+        system::{IntoSystem, Local, System},
+        world::World,
+    };
+    use common::{components::Appearance, network::replication::ReplicationRegistry};
+
+    use super::{Replicate, ReplicatedEntities};
+
+    #[test]
+    fn only_entities_that_changed_are_serialised_again() {
+        let mut world = World::new();
+        let mut registry = ReplicationRegistry::default();
+        registry.register::<Appearance>();
+        world.insert_resource(registry);
+        let appearance = Appearance {
+            colour: [1.0; 4],
+            size: [1.0, 1.0],
+        };
+        let still = world.spawn((Replicate, appearance)).id();
+        let moving = world.spawn((Replicate, appearance)).id();
+
+        let mut update = IntoSystem::into_system(
+            |world: &mut World, mut entities: Local<ReplicatedEntities>| {
+                let mut changed: Vec<Entity> = entities.update(world).into_iter().collect();
+                changed.sort();
+                changed
+            },
+        );
+        update.initialize(&mut world);
+        assert_eq!(update.run((), &mut world), vec![still, moving]);
+        assert!(update.run((), &mut world).is_empty());
+
+        world.get_mut::<Appearance>(moving).unwrap().size = [2.0, 2.0];
+        assert_eq!(update.run((), &mut world), vec![moving]);
+        world.entity_mut(still).remove::<Appearance>();
+        assert_eq!(update.run((), &mut world), vec![still]);
+    }
+}
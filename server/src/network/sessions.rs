@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use bevy::{
+    ecs::{
+        entity::Entity,
+        system::{Commands, Res, ResMut, Resource},
+    },
+    log::info,
+    time::Time,
+};
+use bevy_renet::renet::ClientId;
+
+// How long a disconnected player's entity is kept for them to reconnect to
+const SESSION_GRACE_SECONDS: f64 = 60.0;
+
+struct DetachedSession {
+    entity: Entity,
+    expires_at: f64,
+}
+
+// Players are identified by their account rather than their connection, so one that drops and
+// reconnects picks up the entity it left behind instead of being given a new one
+#[derive(Resource, Default)]
+pub struct Sessions {
+    accounts: HashMap<ClientId, String>,
+    detached: HashMap<String, DetachedSession>,
+}
+
+impl Sessions {
+    pub fn connect(&mut self, client: ClientId, account: String) {
+        self.accounts.insert(client, account);
+    }
+
+    pub fn disconnect(&mut self, client: ClientId) {
+        self.accounts.remove(&client);
+    }
+
+    // Keeps the client's entity waiting for its account to come back
+    pub fn detach(&mut self, client: ClientId, entity: Entity, now: f64) {
+        if let Some(account) = self.accounts.remove(&client) {
+            let expires_at = now + SESSION_GRACE_SECONDS;
+            self.detached.insert(account, DetachedSession { entity, expires_at });
+        }
+    }
+
+    // The entity left behind by the client's account, if it's still waiting
+    pub fn resume(&mut self, client: ClientId) -> Option<Entity> {
+        let account = self.accounts.get(&client)?;
+        self.detached.remove(account).map(|session| session.entity)
+    }
+}
+
+pub fn expire_sessions(time: Res<Time>, mut sessions: ResMut<Sessions>, mut commands: Commands) {
+    let now = time.elapsed_seconds_f64();
+    sessions.detached.retain(|account, session| {
+        if now < session.expires_at {
+            return true;
+        }
+
+        info!("Session for {} expired", account);
+        commands.entity(session.entity).despawn();
+        false
+    });
+}
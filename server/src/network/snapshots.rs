@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use bevy::{
+    ecs::{
+        entity::Entity,
+        event::{EventReader, EventWriter},
+        query::With,
+        system::{Query, Res, ResMut, Resource, SystemParam},
+    },
+    transform::components::Transform,
+};
+use bevy_renet::renet::{ClientId, RenetServer};
+use common::network::{
+    configuration::Tick,
+    events::{Snapshot, SnapshotAcknowledged},
+    snapshot::{QuantisedPosition, SnapshotHistory, SnapshotState},
+};
+
+use crate::game_clock::ServerTick;
+
+use super::{ClientEntityMapper, ClientInterest, ReceiveFromClient, Replicate, SendToClient};
+
+#[derive(Resource)]
+pub struct SnapshotSettings {
+    // Roughly how many bytes of positions each client is sent per tick
+    pub bytes_per_tick: usize,
+    // Entities within this distance of a client's player are sent before ones further away
+    pub nearby_distance: f32,
+}
+
+impl Default for SnapshotSettings {
+    fn default() -> Self {
+        Self {
+            bytes_per_tick: 1200,
+            nearby_distance: 500.0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct ClientSnapshots {
+    history: SnapshotHistory,
+    acknowledged: Option<Tick>,
+    // Grows every tick an entity has changed but didn't fit in the budget, so nothing waits forever
+    priorities: HashMap<Entity, f32>,
+}
+
+#[derive(Resource, Default)]
+pub struct ClientSnapshotStates {
+    clients: HashMap<ClientId, ClientSnapshots>,
+}
+
+pub fn receive_snapshot_acknowledgements(
+    mut acknowledged_events: EventReader<ReceiveFromClient<SnapshotAcknowledged>>,
+    mut states: ResMut<ClientSnapshotStates>,
+) {
+    for event in acknowledged_events.read() {
+        let Some(client) = states.clients.get_mut(&event.client) else {
+            continue;
+        };
+        if client.acknowledged.is_none_or(|tick| event.message.tick > tick) {
+            client.acknowledged = Some(event.message.tick);
+        }
+    }
+}
+
+// Which entities each client can see, and which one it's seeing them from
+#[derive(SystemParam)]
+pub struct ClientViews<'w> {
+    mapper: Res<'w, ClientEntityMapper>,
+    interest: Res<'w, ClientInterest>,
+}
+
+pub fn send_snapshots(
+    server: Res<RenetServer>,
+    tick: Res<ServerTick>,
+    settings: Res<SnapshotSettings>,
+    views: ClientViews,
+    entities: Query<(Entity, &Transform), With<Replicate>>,
+    mut states: ResMut<ClientSnapshotStates>,
+    mut snapshot_events: EventWriter<SendToClient<Snapshot>>,
+) {
+    let clients = server.clients_id();
+    states.clients.retain(|client, _| clients.contains(client));
+
+    let positions: HashMap<Entity, QuantisedPosition> = entities
+        .iter()
+        .map(|(entity, transform)| (entity, QuantisedPosition::from_vec3(transform.translation)))
+        .collect();
+
+    for client in clients {
+        // Entities that have left the client's scope are listed as removed like despawned ones
+        let current: HashMap<Entity, QuantisedPosition> = positions
+            .iter()
+            .filter(|(entity, _)| views.interest.is_relevant(client, **entity))
+            .map(|(entity, position)| (*entity, *position))
+            .collect();
+
+        let viewer = views
+            .mapper
+            .clients
+            .get(&client.raw())
+            .and_then(|entity| entities.get(*entity).ok())
+            .map(|(_, transform)| transform.translation);
+
+        let snapshots = states.clients.entry(client).or_default();
+        let acknowledged = snapshots
+            .acknowledged
+            .and_then(|tick| snapshots.history.get(tick).map(|state| (tick, state.clone())));
+        let (baseline, state) = match acknowledged {
+            Some((tick, state)) => (Some(tick), state),
+            None => (None, SnapshotState::default()),
+        };
+
+        let mut changed: Vec<(Entity, QuantisedPosition, f32)> = current
+            .iter()
+            .filter(|(entity, position)| state.get(**entity) != Some(**position))
+            .map(|(entity, position)| {
+                let proximity = viewer.map_or(0.0, |viewer| {
+                    let distance = viewer.distance(position.to_vec3());
+                    1.0 - (distance / settings.nearby_distance).min(1.0)
+                });
+                let priority = snapshots.priorities.entry(*entity).or_default();
+                *priority += 1.0 + proximity * 4.0;
+                (*entity, *position, *priority)
+            })
+            .collect();
+        changed.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+        let mut bytes = 0;
+        let mut included = Vec::new();
+        for (entity, position, _) in changed {
+            let entity_snapshot = state.encode(entity, position);
+            let size = bincode::serde::encode_to_vec(entity_snapshot, bincode::config::standard())
+                .map_or(0, |encoded| encoded.len());
+            // Always send at least one entity so a tiny budget can't stop everything
+            if bytes + size > settings.bytes_per_tick && !included.is_empty() {
+                break;
+            }
+            bytes += size;
+            snapshots.priorities.remove(&entity);
+            included.push(entity_snapshot);
+        }
+
+        let removed: Vec<Entity> = state
+            .entities()
+            .filter(|entity| !current.contains_key(entity))
+            .collect();
+        snapshots
+            .priorities
+            .retain(|entity, _| current.contains_key(entity));
+
+        if included.is_empty() && removed.is_empty() {
+            continue;
+        }
+
+        let snapshot = Snapshot {
+            tick: tick.0,
+            baseline,
+            entities: included,
+            removed,
+        };
+        snapshots.history.insert(tick.0, state.apply(&snapshot));
+
+        snapshot_events.send(SendToClient {
+            client: Some(client),
+            message: snapshot,
+        });
+    }
+}
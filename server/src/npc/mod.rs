@@ -0,0 +1,5 @@
+mod npc_plugin;
+mod trader_agent;
+
+pub use npc_plugin::{NpcConfigs, NpcPlugin};
+pub use trader_agent::{Consumer, MarketMaker, Producer, TraderAgent};
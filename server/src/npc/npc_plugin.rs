@@ -0,0 +1,547 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::{Assets, UntypedHandle},
+    ecs::{
+        entity::Entity,
+        event::EventWriter,
+        schedule::{common_conditions::in_state, IntoSystemConfigs, OnEnter},
+        system::{Commands, Query, Res, Resource},
+    },
+    log::{debug, info, warn},
+    time::{Time, Timer, TimerMode},
+};
+use bevy_asset_loader::asset_collection::AssetCollection;
+use common::{
+    loaders::KdlAsset,
+    market::{Credits, OrderSide, Price, ORDER_LOT},
+    materials::{load_materials, MaterialID, MaterialManager},
+    units::{Mass, UnitT},
+};
+use kdl::{KdlDocument, KdlNode};
+
+use crate::{
+    economy::{Inventory, Wallet},
+    market::{MarketHistory, Markets, SubmitOrder, WithdrawOrder},
+    ServerState,
+};
+
+use super::{Consumer, MarketMaker, Producer, TraderAgent};
+
+pub struct NpcPlugin;
+
+impl Plugin for NpcPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(ServerState::GeneratingAssets),
+            spawn_traders.after(load_materials),
+        )
+        .add_systems(
+            Update,
+            (run_market_makers, run_consumers, run_producers)
+                .run_if(in_state(ServerState::Running)),
+        );
+    }
+}
+
+#[derive(AssetCollection, Resource, Default)]
+pub struct NpcConfigs {
+    #[asset(path = "npcs", collection)]
+    configs: Vec<UntypedHandle>,
+}
+
+fn number(document: &KdlDocument, name: &str) -> f64 {
+    let value = document.get(name).unwrap().get(0).unwrap().value();
+    value
+        .as_f64()
+        .or_else(|| value.as_i64().map(|value| value as f64))
+        .unwrap()
+}
+
+fn kilograms(document: &KdlDocument, name: &str) -> Mass {
+    Mass::from_grams((number(document, name) * 1000.0) as UnitT)
+}
+
+fn create_trader_from_config(
+    config: &KdlNode,
+    commands: &mut Commands,
+    material_manager: &MaterialManager,
+) {
+    let Some(children) = config.children() else {
+        return;
+    };
+
+    let name = children
+        .get("name")
+        .unwrap()
+        .get(0)
+        .unwrap()
+        .value()
+        .as_string()
+        .unwrap();
+
+    let material_name = children
+        .get("material")
+        .unwrap()
+        .get(0)
+        .unwrap()
+        .value()
+        .as_string()
+        .unwrap();
+
+    let Some(material) = material_manager.get_material_id(material_name) else {
+        warn!("Tried to create trader {} but couldn't find material {}", name, material_name);
+        return;
+    };
+
+    let mut inventory = Inventory::default();
+    inventory.add(material, kilograms(children, "inventory"));
+
+    let mut trader = commands.spawn((
+        TraderAgent {
+            name: String::from(name),
+            material,
+            timer: Timer::from_seconds(number(children, "interval") as f32, TimerMode::Repeating),
+        },
+        Wallet::new(Credits::new(number(children, "credits") as UnitT)),
+        inventory,
+    ));
+
+    if let Some(strategy) = children.get("market_maker").and_then(|node| node.children()) {
+        trader.insert(MarketMaker {
+            spread: number(strategy, "spread") as f32,
+            quantity: kilograms(strategy, "quantity"),
+            max_inventory: kilograms(strategy, "max_inventory"),
+        });
+    } else if let Some(strategy) = children.get("consumer").and_then(|node| node.children()) {
+        trader.insert(Consumer {
+            demand: kilograms(strategy, "demand"),
+            elasticity: number(strategy, "elasticity") as f32,
+            max_premium: number(strategy, "max_premium") as f32,
+            income: Credits::new(number(strategy, "income") as UnitT),
+        });
+    } else if let Some(strategy) = children.get("producer").and_then(|node| node.children()) {
+        trader.insert(Producer {
+            production: kilograms(strategy, "production"),
+            cost_factor: number(strategy, "cost_factor") as f32,
+            margin: number(strategy, "margin") as f32,
+            elasticity: number(strategy, "elasticity") as f32,
+            max_inventory: kilograms(strategy, "max_inventory"),
+        });
+    } else {
+        warn!("Trader {} doesn't have a strategy", name);
+        trader.despawn();
+        return;
+    }
+
+    info!("Spawned trader {} for {}", name, material_name);
+}
+
+fn spawn_traders(
+    mut commands: Commands,
+    loaded_files: Res<NpcConfigs>,
+    config_assets: Res<Assets<KdlAsset>>,
+    material_manager: Res<MaterialManager>,
+) {
+    for handle in &loaded_files.configs {
+        let opt_asset = config_assets.get(handle);
+        if let Some(asset) = opt_asset {
+            let document = &asset.0;
+
+            for node in document.nodes() {
+                let node_name = node.name().to_string();
+                match node_name.as_str() {
+                    "define_trader" => {
+                        create_trader_from_config(node, &mut commands, &material_manager);
+                    }
+                    _ => {
+                        warn!("NPC config command {} is not implemented", node_name);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The last traded price if there is one, then the middle of the book, and finally the material's
+// base value for markets that haven't traded yet
+fn reference_price(
+    material: MaterialID,
+    markets: &Markets,
+    history: &MarketHistory,
+    material_manager: &MaterialManager,
+) -> Option<Price> {
+    if let Some(price) = history.last_price(material) {
+        return Some(price);
+    }
+
+    let top_of_book = markets.top_of_book(material);
+    if let (Some(bid), Some(ask)) = (top_of_book.bid, top_of_book.ask) {
+        let mid = (bid.as_credits_per_kilogram().value() + ask.as_credits_per_kilogram().value()) / 2;
+        return Some(Price::per_kilogram(Credits::new(mid)));
+    }
+
+    material_manager
+        .get_material(material)
+        .map(|properties| properties.base_value())
+}
+
+fn scale_mass(mass: Mass, factor: f32) -> Mass {
+    Mass::from_milligrams((mass.as_milligrams() as f64 * factor as f64) as UnitT)
+}
+
+// The market only takes whole lots, so any part of a lot is left out of an order
+fn whole_lots(mass: Mass) -> Mass {
+    let lot = ORDER_LOT.as_milligrams();
+    Mass::from_milligrams(mass.as_milligrams() / lot * lot)
+}
+
+fn price_ratio(numerator: Price, denominator: Price) -> f32 {
+    numerator.as_credits_per_kilogram().value() as f32
+        / denominator.as_credits_per_kilogram().value().max(1) as f32
+}
+
+fn withdraw_orders(
+    owner: Entity,
+    material: MaterialID,
+    markets: &Markets,
+    withdraw_order_events: &mut EventWriter<WithdrawOrder>,
+) {
+    for order in markets.orders_owned_by(material, owner) {
+        withdraw_order_events.send(WithdrawOrder { owner, order });
+    }
+}
+
+fn run_market_makers(
+    time: Res<Time>,
+    mut agents: Query<(Entity, &mut TraderAgent, &MarketMaker, &Inventory)>,
+    markets: Res<Markets>,
+    history: Res<MarketHistory>,
+    material_manager: Res<MaterialManager>,
+    mut submit_order_events: EventWriter<SubmitOrder>,
+    mut withdraw_order_events: EventWriter<WithdrawOrder>,
+) {
+    for (entity, mut agent, strategy, inventory) in &mut agents {
+        if !agent.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        let Some(reference) =
+            reference_price(agent.material, &markets, &history, &material_manager)
+        else {
+            continue;
+        };
+
+        withdraw_orders(entity, agent.material, &markets, &mut withdraw_order_events);
+
+        let held = inventory.get(agent.material);
+        let half = (strategy.max_inventory / 2).as_milligrams().max(1);
+        let skew = ((held.as_milligrams() - half) as f32 / half as f32).clamp(-1.0, 1.0);
+        let shift = -skew * strategy.spread / 2.0;
+
+        let bid = reference.scaled(1.0 - strategy.spread / 2.0 + shift);
+        let mut ask = reference.scaled(1.0 + strategy.spread / 2.0 + shift);
+        if ask <= bid {
+            ask = Price::per_kilogram(bid.as_credits_per_kilogram() + Credits::new(1));
+        }
+
+        debug!("{} quoting {} / {}", agent.name, bid, ask);
+
+        let bid_quantity = whole_lots(strategy.quantity);
+        if held < strategy.max_inventory && bid_quantity > Mass::default() {
+            submit_order_events.send(SubmitOrder {
+                owner: entity,
+                material: agent.material,
+                side: OrderSide::Bid,
+                price: bid,
+                quantity: bid_quantity,
+            });
+        }
+
+        let ask_quantity = whole_lots(strategy.quantity.min(held));
+        if ask_quantity > Mass::default() {
+            submit_order_events.send(SubmitOrder {
+                owner: entity,
+                material: agent.material,
+                side: OrderSide::Ask,
+                price: ask,
+                quantity: ask_quantity,
+            });
+        }
+    }
+}
+
+fn run_consumers(
+    time: Res<Time>,
+    mut agents: Query<(Entity, &mut TraderAgent, &Consumer, &mut Wallet, &mut Inventory)>,
+    markets: Res<Markets>,
+    history: Res<MarketHistory>,
+    material_manager: Res<MaterialManager>,
+    mut submit_order_events: EventWriter<SubmitOrder>,
+    mut withdraw_order_events: EventWriter<WithdrawOrder>,
+) {
+    for (entity, mut agent, strategy, mut wallet, mut inventory) in &mut agents {
+        if !agent.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        if !wallet.deposit(strategy.income) {
+            warn!("{} can't hold any more credits", agent.name);
+        }
+        let consumed = inventory.get(agent.material);
+        inventory.remove(agent.material, consumed);
+
+        let Some(base_value) = material_manager
+            .get_material(agent.material)
+            .map(|properties| properties.base_value())
+        else {
+            continue;
+        };
+        let Some(reference) =
+            reference_price(agent.material, &markets, &history, &material_manager)
+        else {
+            continue;
+        };
+
+        withdraw_orders(entity, agent.material, &markets, &mut withdraw_order_events);
+
+        let price = reference.min(base_value.scaled(1.0 + strategy.max_premium));
+        let quantity = whole_lots(scale_mass(
+            strategy.demand,
+            price_ratio(base_value, price).powf(strategy.elasticity),
+        ));
+        if quantity > Mass::default() {
+            submit_order_events.send(SubmitOrder {
+                owner: entity,
+                material: agent.material,
+                side: OrderSide::Bid,
+                price,
+                quantity,
+            });
+        }
+    }
+}
+
+fn run_producers(
+    time: Res<Time>,
+    mut agents: Query<(Entity, &mut TraderAgent, &Producer, &mut Wallet, &mut Inventory)>,
+    markets: Res<Markets>,
+    history: Res<MarketHistory>,
+    material_manager: Res<MaterialManager>,
+    mut submit_order_events: EventWriter<SubmitOrder>,
+    mut withdraw_order_events: EventWriter<WithdrawOrder>,
+) {
+    for (entity, mut agent, strategy, mut wallet, mut inventory) in &mut agents {
+        if !agent.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        let Some(base_value) = material_manager
+            .get_material(agent.material)
+            .map(|properties| properties.base_value())
+        else {
+            continue;
+        };
+        let cost_basis = base_value.scaled(strategy.cost_factor);
+
+        if inventory.get(agent.material) < strategy.max_inventory
+            && cost_basis
+                .cost(strategy.production)
+                .is_some_and(|cost| wallet.withdraw(cost))
+        {
+            inventory.add(agent.material, strategy.production);
+        }
+
+        let Some(reference) =
+            reference_price(agent.material, &markets, &history, &material_manager)
+        else {
+            continue;
+        };
+
+        withdraw_orders(entity, agent.material, &markets, &mut withdraw_order_events);
+
+        let price = reference.max(cost_basis.scaled(1.0 + strategy.margin));
+        let quantity = whole_lots(
+            scale_mass(
+                strategy.production,
+                price_ratio(price, cost_basis).powf(strategy.elasticity),
+            )
+            .min(inventory.get(agent.material)),
+        );
+        if quantity > Mass::default() {
+            submit_order_events.send(SubmitOrder {
+                owner: entity,
+                material: agent.material,
+                side: OrderSide::Ask,
+                price,
+                quantity,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{
+        app::{App, Update},
+        ecs::{entity::Entity, event::Events},
+        time::{Time, TimePlugin, TimeUpdateStrategy, Timer, TimerMode},
+    };
+    use common::{
+        market::{Credits, OrderSide, Price},
+        materials::{
+            BasicMaterialProperties, MaterialManager, MaterialProperties, ThermalProperties,
+        },
+        units::{Density, Energy, HeatCapacity, Mass, Temperature, UnitT, Volume},
+    };
+
+    use crate::{
+        economy::{Inventory, Wallet},
+        market::{MarketHistory, Markets, SubmitOrder, WithdrawOrder},
+        npc::{Consumer, MarketMaker, Producer, TraderAgent},
+    };
+
+    use super::{run_consumers, run_market_makers, run_producers};
+
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    // A market with nothing traded yet, so every trader goes by the ore's base value of 10
+    fn market() -> App {
+        let mut material_manager = MaterialManager::default();
+        material_manager.register_material(MaterialProperties::Basic(BasicMaterialProperties {
+            name: String::from("Ore"),
+            density: Density {
+                mass: Mass::from_kilograms(4_000),
+                volume: Volume::from_cubic_metres(1),
+            },
+            thermal_properties: ThermalProperties {
+                heat_capacity: HeatCapacity {
+                    energy: Energy::from_joules(800),
+                    mass: Mass::from_kilograms(1),
+                },
+                melting_point: Temperature::from_kelvin(1_800),
+                boiling_point: Temperature::from_kelvin(3_000),
+            },
+            base_value: Price::per_kilogram(Credits::new(10)),
+        }));
+
+        let mut app = App::new();
+        app.add_plugins(TimePlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(INTERVAL))
+            .insert_resource(material_manager)
+            .init_resource::<Markets>()
+            .init_resource::<MarketHistory>()
+            .add_event::<SubmitOrder>()
+            .add_event::<WithdrawOrder>()
+            .add_systems(Update, (run_market_makers, run_consumers, run_producers));
+        // Time doesn't move on the first update, so every later one is a whole interval
+        app.update();
+        app
+    }
+
+    fn trader(app: &mut App, credits: UnitT, kilograms: UnitT) -> Entity {
+        let material = app.world.resource::<MaterialManager>().get_material_id("Ore").unwrap();
+        let mut inventory = Inventory::default();
+        inventory.add(material, Mass::from_kilograms(kilograms));
+        let agent = TraderAgent {
+            name: String::from("Trader"),
+            material,
+            timer: Timer::new(INTERVAL, TimerMode::Repeating),
+        };
+        app.world
+            .spawn((agent, Wallet::new(Credits::new(credits)), inventory))
+            .id()
+    }
+
+    // The orders submitted on the next interval, as (side, price, kilograms)
+    fn orders(app: &mut App) -> Vec<(OrderSide, UnitT, UnitT)> {
+        app.update();
+        assert_eq!(app.world.resource::<Time>().delta(), INTERVAL);
+        app.world
+            .resource::<Events<SubmitOrder>>()
+            .iter_current_update_events()
+            .map(|order| {
+                let price = order.price.as_credits_per_kilogram().value();
+                (order.side, price, order.quantity.as_kilograms())
+            })
+            .collect()
+    }
+
+    fn market_maker(max_inventory: UnitT) -> MarketMaker {
+        MarketMaker {
+            spread: 0.2,
+            quantity: Mass::from_kilograms(5),
+            max_inventory: Mass::from_kilograms(max_inventory),
+        }
+    }
+
+    #[test]
+    fn market_makers_quote_both_sides_around_the_reference() {
+        let mut app = market();
+        let maker = trader(&mut app, 1_000, 50);
+        app.world.entity_mut(maker).insert(market_maker(100));
+
+        assert_eq!(
+            orders(&mut app),
+            vec![(OrderSide::Bid, 9, 5), (OrderSide::Ask, 11, 5)]
+        );
+    }
+
+    #[test]
+    fn full_market_makers_only_sell_and_lower_their_quotes() {
+        let mut app = market();
+        let maker = trader(&mut app, 1_000, 100);
+        app.world.entity_mut(maker).insert(market_maker(100));
+
+        assert_eq!(orders(&mut app), vec![(OrderSide::Ask, 10, 5)]);
+    }
+
+    #[test]
+    fn consumers_are_paid_use_up_their_stock_and_bid() {
+        let mut app = market();
+        let consumer = trader(&mut app, 0, 3);
+        app.world.entity_mut(consumer).insert(Consumer {
+            demand: Mass::from_kilograms(10),
+            elasticity: 1.0,
+            max_premium: 0.5,
+            income: Credits::new(100),
+        });
+
+        assert_eq!(orders(&mut app), vec![(OrderSide::Bid, 10, 10)]);
+        assert_eq!(app.world.get::<Wallet>(consumer).unwrap().credits, Credits::new(100));
+        assert!(app.world.get::<Inventory>(consumer).unwrap().materials.is_empty());
+    }
+
+    #[test]
+    fn producers_pay_for_production_and_sell_what_they_have() {
+        let mut app = market();
+        let producer = trader(&mut app, 100, 0);
+        app.world.entity_mut(producer).insert(Producer {
+            production: Mass::from_kilograms(4),
+            cost_factor: 0.5,
+            margin: 0.2,
+            elasticity: 1.0,
+            max_inventory: Mass::from_kilograms(10),
+        });
+
+        // Costs 5 per kilogram to make, and the reference price of 10 is well above the margin
+        assert_eq!(orders(&mut app), vec![(OrderSide::Ask, 10, 4)]);
+        assert_eq!(app.world.get::<Wallet>(producer).unwrap().credits, Credits::new(80));
+    }
+
+    #[test]
+    fn producers_without_credits_sell_nothing() {
+        let mut app = market();
+        let producer = trader(&mut app, 0, 0);
+        app.world.entity_mut(producer).insert(Producer {
+            production: Mass::from_kilograms(4),
+            cost_factor: 0.5,
+            margin: 0.2,
+            elasticity: 1.0,
+            max_inventory: Mass::from_kilograms(10),
+        });
+
+        assert!(orders(&mut app).is_empty());
+    }
+}
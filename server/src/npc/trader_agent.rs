@@ -0,0 +1,42 @@
+use bevy::{ecs::component::Component, time::Timer};
+use common::{market::Credits, materials::MaterialID, units::Mass};
+
+// Shared state for every NPC trader. The strategy itself is a separate component so each one can
+// be driven by its own system
+#[derive(Component)]
+pub struct TraderAgent {
+    pub name: String,
+    pub material: MaterialID,
+    pub timer: Timer,
+}
+
+// Quotes both sides around the reference price, skewing the quotes to push its inventory back
+// towards half of `max_inventory`
+#[derive(Component, Clone, Copy)]
+pub struct MarketMaker {
+    pub spread: f32,
+    pub quantity: Mass,
+    pub max_inventory: Mass,
+}
+
+// Buys `demand` per interval when the price is at the material's base value, buying less as the
+// price rises (and more as it falls) according to `elasticity`. Purchases are consumed each
+// interval and the trader is paid `income` to keep buying
+#[derive(Component, Clone, Copy)]
+pub struct Consumer {
+    pub demand: Mass,
+    pub elasticity: f32,
+    pub max_premium: f32,
+    pub income: Credits,
+}
+
+// Produces `production` per interval at a cost of `cost_factor` times the material's base value,
+// and sells for at least that cost plus `margin`, offering more as the price rises
+#[derive(Component, Clone, Copy)]
+pub struct Producer {
+    pub production: Mass,
+    pub cost_factor: f32,
+    pub margin: f32,
+    pub elasticity: f32,
+    pub max_inventory: Mass,
+}
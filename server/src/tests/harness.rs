@@ -0,0 +1,313 @@
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, TcpListener, UdpSocket},
+    path::Path,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use bevy::{
+    app::{App, AppExit, Update},
+    ecs::{
+        entity::Entity,
+        event::Events,
+        schedule::State,
+        system::{ResMut, Resource},
+    },
+    time::TimeUpdateStrategy,
+    MinimalPlugins,
+};
+use bevy_renet::{
+    renet::{
+        transport::{ClientAuthentication, NetcodeClientTransport},
+        RenetClient,
+    },
+    transport::NetcodeClientPlugin,
+    RenetClientPlugin,
+};
+use common::{
+    materials::MaterialManager,
+    network::{
+        channels::{
+            connection_config, read_sequenced, write_sequenced, MessageChannel, OutgoingSequence,
+        },
+        configuration::DEFAULT_WORLD,
+        events::{GetPlayerEntity, Handshake, HandshakeAccepted, PlayerEntity},
+        handshake::{Version, PROTOCOL_VERSION},
+        login::request_connect_token,
+        message::{encode_message, Envelope, MessageTypeID, NetworkMessage},
+        settings::NetworkSettings,
+    },
+};
+
+use crate::{
+    build_server,
+    network::{Recorder, RecordingHeader, Replay},
+    world::WorldSeed,
+    ServerState,
+};
+
+// Far more steps than anything should take, so a test fails rather than hangs
+const MAX_STEPS: usize = 1000;
+// Real time for packets to cross loopback between steps. The apps' clocks only ever move on by a
+// tick per step, however long this takes
+const STEP_SLEEP: Duration = Duration::from_millis(1);
+
+// Every message a test client has received that a test hasn't taken yet, with any sequence
+// removed
+#[derive(Resource, Default)]
+struct Inbox(Vec<Vec<u8>>);
+
+pub struct TestClient {
+    pub app: App,
+    // Numbered per message type as they're sent, the same as the real client
+    sequences: HashMap<MessageTypeID, OutgoingSequence>,
+}
+
+// A real server and headless clients in one process, talking over loopback. Each step updates the
+// server and then every client once, so tests see the same ticks however fast the machine is
+pub struct Harness {
+    pub server: App,
+    pub clients: Vec<TestClient>,
+    settings: NetworkSettings,
+}
+
+impl Harness {
+    // Starts a server with its own login service on ports nothing else is using, and waits for it
+    // to load
+    pub fn new() -> Self {
+        Self::start(|_, _| {})
+    }
+
+    // The same, but generating the world from the seed given
+    pub fn seeded(seed: u64) -> Self {
+        Self::start(|server, _| {
+            server.insert_resource(WorldSeed(seed));
+        })
+    }
+
+    // The same, but recording everything the clients send
+    pub fn recording(path: &Path) -> Self {
+        Self::start(|server, settings| {
+            let seed = WorldSeed(0);
+            let header = RecordingHeader {
+                seed: seed.0,
+                tick_rate: settings.tick_rate,
+            };
+            let recorder = Recorder::create(path, header).unwrap();
+            server.insert_resource(seed).insert_resource(recorder);
+        })
+    }
+
+    fn start(setup: impl FnOnce(&mut App, &NetworkSettings)) -> Self {
+        let settings = NetworkSettings {
+            port: free_port(|address| UdpSocket::bind(address)?.local_addr()),
+            login_port: free_port(|address| TcpListener::bind(address)?.local_addr()),
+            ..Default::default()
+        };
+
+        let mut server = App::new();
+        server
+            .add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(tick(&settings)));
+        setup(&mut server, &settings);
+        build_server(&mut server, settings.clone(), true);
+        server.finish();
+        server.cleanup();
+
+        let mut harness = Self {
+            server,
+            clients: Vec::new(),
+            settings,
+        };
+        assert!(
+            harness.step_until(|harness| {
+                let state = harness.server.world.resource::<State<ServerState>>();
+                *state.get() == ServerState::Running
+            }),
+            "The server never finished loading"
+        );
+        harness
+    }
+
+    // Logs in as the account, connects and has the handshake accepted. Returns the client's index.
+    // Each account's name doubles as its secret
+    pub fn connect(&mut self, account: &str) -> usize {
+        let address = self.settings.login_public_address();
+        let connect_token = request_connect_token(address, account, account)
+            .expect("The login service refused a connect token");
+        let socket = UdpSocket::bind(SocketAddr::new(self.settings.bind_address, 0)).unwrap();
+        let current_time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        let auth = ClientAuthentication::Secure { connect_token };
+        let transport = NetcodeClientTransport::new(current_time, auth, socket).unwrap();
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, RenetClientPlugin, NetcodeClientPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(tick(&self.settings)))
+            .insert_resource(RenetClient::new(connection_config()))
+            .insert_resource(transport)
+            .init_resource::<Inbox>()
+            .add_systems(Update, receive_messages);
+        app.finish();
+        app.cleanup();
+
+        self.clients.push(TestClient {
+            app,
+            sequences: HashMap::new(),
+        });
+        let client = self.clients.len() - 1;
+        assert!(
+            self.step_until(|harness| {
+                harness.clients[client]
+                    .app
+                    .world
+                    .resource::<RenetClient>()
+                    .is_connected()
+            }),
+            "{} never connected",
+            account
+        );
+
+        let content_hash = self
+            .server
+            .world
+            .resource::<MaterialManager>()
+            .content_hash()
+            .unwrap();
+        let common_version = Version::common();
+        self.send(
+            client,
+            Handshake {
+                client_version: common_version,
+                common_version,
+                protocol_version: PROTOCOL_VERSION,
+                content_hash,
+            },
+        );
+        assert!(
+            self.step_until(|harness| !harness.received::<HandshakeAccepted>(client).is_empty()),
+            "{}'s handshake was never accepted",
+            account
+        );
+        client
+    }
+
+    pub fn player_entity(&mut self, client: usize) -> Entity {
+        let request = GetPlayerEntity {
+            request: 1,
+            world: DEFAULT_WORLD,
+        };
+        self.send(client, request);
+        let mut responses = Vec::new();
+        assert!(self.step_until(|harness| {
+            responses.extend(harness.received::<PlayerEntity>(client));
+            !responses.is_empty()
+        }));
+        assert_eq!(responses[0].request, request.request);
+        responses[0].entity.expect("The server has no entity for the player")
+    }
+
+    pub fn step(&mut self) {
+        self.server.update();
+        for client in &mut self.clients {
+            client.app.update();
+        }
+        thread::sleep(STEP_SLEEP);
+    }
+
+    // Steps until the condition holds, returning false if it never does
+    pub fn step_until(&mut self, mut condition: impl FnMut(&mut Self) -> bool) -> bool {
+        for _ in 0..MAX_STEPS {
+            self.step();
+            if condition(self) {
+                return true;
+            }
+        }
+        false
+    }
+
+    // Queues it the same way the client sends it, so it goes out on the client's next step
+    pub fn send<T: NetworkMessage>(&mut self, client: usize, message: T) {
+        let client = &mut self.clients[client];
+        let mut bytes = encode_message(&message).unwrap();
+        if T::CHANNEL.is_sequenced() {
+            let sequence = client.sequences.entry(T::TYPE_ID).or_default().advance();
+            bytes = write_sequenced(sequence, bytes);
+        }
+        client
+            .app
+            .world
+            .resource_mut::<RenetClient>()
+            .send_message(T::CHANNEL, bytes);
+    }
+
+    // Takes every message of the type the client has received so far, oldest first. Messages of
+    // other types are left for later
+    pub fn received<T: NetworkMessage>(&mut self, client: usize) -> Vec<T> {
+        let mut inbox = self.clients[client].app.world.resource_mut::<Inbox>();
+        let mut messages = Vec::new();
+        inbox.0.retain(|bytes| match Envelope::read(bytes) {
+            Ok(envelope) if envelope.message_type == T::TYPE_ID => {
+                messages.push(envelope.decode::<T>().unwrap());
+                false
+            }
+            _ => true,
+        });
+        messages
+    }
+}
+
+// Loads a recording into a server of its own and plays all of it, returning the server as it was
+// left
+pub fn replay(path: &Path) -> App {
+    let (header, replay) = Replay::load(path).unwrap();
+    let settings = NetworkSettings {
+        tick_rate: header.tick_rate,
+        ..Default::default()
+    };
+
+    let mut server = App::new();
+    server
+        .add_plugins(MinimalPlugins)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(tick(&settings)))
+        .insert_resource(WorldSeed(header.seed))
+        .insert_resource(replay);
+    build_server(&mut server, settings, false);
+    server.finish();
+    server.cleanup();
+
+    for _ in 0..MAX_STEPS {
+        server.update();
+        // The replay stops the server once it's done, the same as when run for real
+        if !server.world.resource::<Events<AppExit>>().is_empty() {
+            return server;
+        }
+        thread::sleep(STEP_SLEEP);
+    }
+    panic!("The replay never finished");
+}
+
+fn tick(settings: &NetworkSettings) -> Duration {
+    Duration::from_secs_f64(settings.tick_seconds())
+}
+
+// Tests run in parallel, so every server needs ports of its own. The OS picks one that's free,
+// which is then let go for the server to take
+fn free_port(bind: impl Fn(SocketAddr) -> std::io::Result<SocketAddr>) -> u16 {
+    let address = SocketAddr::new(NetworkSettings::default().bind_address, 0);
+    bind(address).unwrap().port()
+}
+
+fn receive_messages(mut client: ResMut<RenetClient>, mut inbox: ResMut<Inbox>) {
+    for channel in MessageChannel::ALL {
+        while let Some(bytes) = client.receive_message(channel) {
+            if !channel.is_sequenced() {
+                inbox.0.push(bytes.to_vec());
+            } else if let Some((_, message)) = read_sequenced(&bytes) {
+                inbox.0.push(message.to_vec());
+            }
+        }
+    }
+}
@@ -0,0 +1,4 @@
+mod harness;
+mod networking;
+mod replay;
+mod world;
@@ -0,0 +1,105 @@
+use std::net::{SocketAddr, UdpSocket};
+
+use bevy::{
+    app::{App, AppExit},
+    ecs::{entity::Entity, event::Events},
+    transform::components::Transform,
+    MinimalPlugins,
+};
+use common::{
+    movement::PLAYER_SPEED,
+    network::{
+        events::{InputAcknowledged, PlayerInput, ReplicationChange, ReplicationUpdate},
+        settings::NetworkSettings,
+    },
+};
+
+use crate::build_server;
+
+use super::harness::Harness;
+
+fn spawned_on(harness: &mut Harness, client: usize, entity: Entity) -> bool {
+    harness.step_until(|harness| {
+        harness
+            .received::<ReplicationUpdate>(client)
+            .into_iter()
+            .flat_map(|update| update.changes)
+            .any(|change| change == ReplicationChange::Spawn(entity))
+    })
+}
+
+#[test]
+fn connecting_spawns_a_replicated_player() {
+    let mut harness = Harness::new();
+    let client = harness.connect("tester");
+    let entity = harness.player_entity(client);
+
+    assert!(harness.server.world.get_entity(entity).is_some());
+    assert!(spawned_on(&mut harness, client, entity));
+}
+
+#[test]
+fn players_see_each_other() {
+    let mut harness = Harness::new();
+    let first = harness.connect("first");
+    let second = harness.connect("second");
+    let first_entity = harness.player_entity(first);
+    let second_entity = harness.player_entity(second);
+
+    assert_ne!(first_entity, second_entity);
+    assert!(spawned_on(&mut harness, second, first_entity));
+    assert!(spawned_on(&mut harness, first, second_entity));
+}
+
+#[test]
+fn player_input_moves_the_player() {
+    let mut harness = Harness::new();
+    let client = harness.connect("tester");
+    let entity = harness.player_entity(client);
+
+    let inputs = 10;
+    for sequence in 1..=inputs {
+        let input = PlayerInput {
+            sequence,
+            forward: 1,
+            ..Default::default()
+        };
+        harness.send(client, input);
+        harness.step();
+    }
+
+    let mut acknowledged = Vec::new();
+    assert!(harness.step_until(|harness| {
+        acknowledged.extend(harness.received::<InputAcknowledged>(client));
+        acknowledged.last().is_some_and(|last| last.sequence == inputs)
+    }));
+
+    // Every input is one tick of movement, however the inputs were spread across ticks
+    let tick_seconds = harness.server.world.resource::<NetworkSettings>().tick_seconds();
+    let expected = inputs as f32 * PLAYER_SPEED * tick_seconds as f32;
+    let translation = harness.server.world.get::<Transform>(entity).unwrap().translation;
+    assert_eq!(translation.x, 0.0);
+    assert!((translation.y - expected).abs() < 0.001);
+
+    let last = acknowledged.last().unwrap();
+    assert_eq!([last.x, last.y, last.z], translation.to_array());
+}
+
+#[test]
+fn servers_that_cant_listen_stop() {
+    let bind_address = NetworkSettings::default().bind_address;
+    let taken = UdpSocket::bind(SocketAddr::new(bind_address, 0)).unwrap();
+    let settings = NetworkSettings {
+        port: taken.local_addr().unwrap().port(),
+        ..Default::default()
+    };
+
+    let mut server = App::new();
+    server.add_plugins(MinimalPlugins);
+    build_server(&mut server, settings, false);
+    server.finish();
+    server.cleanup();
+    server.update();
+
+    assert!(!server.world.resource::<Events<AppExit>>().is_empty());
+}
@@ -0,0 +1,47 @@
+use bevy::transform::components::Transform;
+use common::network::events::{InputAcknowledged, PlayerInput};
+
+use crate::network::ClientEntityMapper;
+
+use super::harness::{replay, Harness};
+
+#[test]
+fn replaying_a_recording_ends_in_the_same_place() {
+    let path = std::env::temp_dir().join(format!("replay-{}.bin", std::process::id()));
+    let mut harness = Harness::recording(&path);
+    let client = harness.connect("tester");
+    let entity = harness.player_entity(client);
+
+    let inputs = 10;
+    for sequence in 1..=inputs {
+        let input = PlayerInput {
+            sequence,
+            right: 1,
+            forward: (sequence % 2) as u8,
+            ..Default::default()
+        };
+        harness.send(client, input);
+        harness.step();
+    }
+    assert!(harness.step_until(|harness| {
+        harness
+            .received::<InputAcknowledged>(client)
+            .iter()
+            .any(|acknowledged| acknowledged.sequence == inputs)
+    }));
+    let recorded = harness.server.world.get::<Transform>(entity).unwrap().translation;
+    // Closes the recording
+    drop(harness);
+
+    let replayed = replay(&path);
+    std::fs::remove_file(&path).unwrap();
+    let entity = *replayed
+        .world
+        .resource::<ClientEntityMapper>()
+        .clients
+        .values()
+        .next()
+        .unwrap();
+    let translation = replayed.world.get::<Transform>(entity).unwrap().translation;
+    assert_eq!(translation, recorded);
+}
@@ -0,0 +1,111 @@
+use bevy::{math::Vec3, transform::components::Transform};
+use common::{
+    materials::MaterialID,
+    network::{
+        configuration::WorldID,
+        events::{
+            GetPlayerEntity, JoinWorld, JoinWorldFailed, JoinWorldFailure, PlayerEntity,
+            PlayerEntityFailure, ReplicationChange, ReplicationUpdate, WorldJoined,
+        },
+    },
+    units::Mass,
+};
+
+use crate::world::{Deposit, InWorld};
+
+use super::harness::Harness;
+
+fn deposits(harness: &mut Harness) -> Vec<(WorldID, MaterialID, Mass, Vec3)> {
+    harness
+        .server
+        .world
+        .query::<(&Deposit, &Transform, &InWorld)>()
+        .iter(&harness.server.world)
+        .map(|(deposit, transform, world)| {
+            (world.0, deposit.instance.properties, deposit.total, transform.translation)
+        })
+        .collect()
+}
+
+#[test]
+fn the_same_seed_generates_the_same_deposits() {
+    let first = deposits(&mut Harness::seeded(7));
+    let second = deposits(&mut Harness::seeded(7));
+    let other = deposits(&mut Harness::seeded(8));
+
+    assert!(!first.is_empty());
+    assert!(first == second);
+    assert!(first != other);
+}
+
+// Steps until the client has been sent the change. Any other replication updates sent to it by
+// then are taken and thrown away, messages of other types are left
+fn step_until_sent(harness: &mut Harness, client: usize, change: ReplicationChange) -> bool {
+    harness.step_until(|harness| {
+        harness
+            .received::<ReplicationUpdate>(client)
+            .into_iter()
+            .flat_map(|update| update.changes)
+            .any(|sent| sent == change)
+    })
+}
+
+fn join_world(
+    harness: &mut Harness,
+    client: usize,
+    world: WorldID,
+) -> Result<(), JoinWorldFailure> {
+    harness.send(client, JoinWorld { world });
+    let mut result = None;
+    assert!(harness.step_until(|harness| {
+        if let Some(joined) = harness.received::<WorldJoined>(client).first() {
+            assert_eq!(joined.world, world);
+            result = Some(Ok(()));
+        } else if let Some(failed) = harness.received::<JoinWorldFailed>(client).first() {
+            result = Some(Err(failed.reason));
+        }
+        result.is_some()
+    }));
+    result.unwrap()
+}
+
+#[test]
+fn players_in_different_worlds_never_see_each_other() {
+    let mut harness = Harness::new();
+    let alice = harness.connect("alice");
+    let bob = harness.connect("bob");
+    let alice_entity = harness.player_entity(alice);
+    let bob_entity = harness.player_entity(bob);
+    assert!(step_until_sent(&mut harness, bob, ReplicationChange::Spawn(alice_entity)));
+
+    assert_eq!(join_world(&mut harness, bob, 99), Err(JoinWorldFailure::UnknownWorld));
+    assert_eq!(join_world(&mut harness, bob, 0), Err(JoinWorldFailure::AlreadyInWorld));
+    assert_eq!(join_world(&mut harness, bob, 1), Ok(()));
+    assert!(step_until_sent(&mut harness, bob, ReplicationChange::Despawn(alice_entity)));
+    assert!(step_until_sent(&mut harness, alice, ReplicationChange::Despawn(bob_entity)));
+
+    assert_eq!(join_world(&mut harness, alice, 1), Ok(()));
+    assert!(step_until_sent(&mut harness, bob, ReplicationChange::Spawn(alice_entity)));
+}
+
+#[test]
+fn players_asking_about_another_world_are_told_where_they_are() {
+    let mut harness = Harness::new();
+    let client = harness.connect("tester");
+    let entity = harness.player_entity(client);
+    assert_eq!(join_world(&mut harness, client, 2), Ok(()));
+
+    let mut responses = Vec::new();
+    for (request, world) in [(1, 0), (2, 2)] {
+        harness.send(client, GetPlayerEntity { request, world });
+    }
+    assert!(harness.step_until(|harness| {
+        responses.extend(harness.received::<PlayerEntity>(client));
+        responses.len() == 2
+    }));
+
+    assert_eq!(responses[0].request, 1);
+    assert_eq!(responses[0].entity, Err(PlayerEntityFailure::InOtherWorld(2)));
+    assert_eq!(responses[1].request, 2);
+    assert_eq!(responses[1].entity, Ok(entity));
+}
@@ -0,0 +1,36 @@
+use bevy::ecs::component::Component;
+use common::{components::DepositState, materials::CompoundMaterialInstance, units::Mass};
+
+#[derive(Component)]
+pub struct Deposit {
+    pub instance: CompoundMaterialInstance,
+    pub total: Mass,
+    pub remaining: Mass,
+}
+
+impl Deposit {
+    pub fn new(instance: CompoundMaterialInstance, total: Mass) -> Self {
+        Self {
+            instance,
+            total,
+            remaining: total,
+        }
+    }
+
+    // Returns how much was actually extracted, which is less than asked for once the deposit
+    // runs low
+    pub fn extract(&mut self, mass: Mass) -> Mass {
+        let extracted = mass.min(self.remaining);
+        self.remaining -= extracted;
+        extracted
+    }
+
+    pub fn state(&self) -> DepositState {
+        DepositState {
+            material: self.instance.properties,
+            ratios: self.instance.ratios.clone(),
+            total: self.total,
+            remaining: self.remaining,
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use bevy::{
+    ecs::{
+        component::Component,
+        event::{EventReader, EventWriter},
+        system::{Query, Res, Resource},
+        world::{FromWorld, World},
+    },
+    log::{info, warn},
+    math::Vec3,
+    transform::components::Transform,
+};
+use common::network::{
+    configuration::WorldID,
+    events::{JoinWorld, JoinWorldFailed, JoinWorldFailure, WorldJoined},
+};
+
+use crate::network::{ClientEntityMapper, ReceiveFromClient, SendToClient};
+
+use super::WorldSeed;
+
+const WORLD_COUNT: WorldID = 3;
+
+// The world an entity is in. Entities in different worlds never see or touch each other
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InWorld(pub WorldID);
+
+pub struct WorldInstance {
+    pub seed: u64,
+}
+
+#[derive(Resource)]
+pub struct Worlds {
+    instances: BTreeMap<WorldID, WorldInstance>,
+}
+
+impl Worlds {
+    pub fn contains(&self, world: WorldID) -> bool {
+        self.instances.contains_key(&world)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (WorldID, &WorldInstance)> {
+        self.instances.iter().map(|(world, instance)| (*world, instance))
+    }
+}
+
+// Each world's seed is mixed from the server's, with the default world keeping the server's seed
+// as it is
+impl FromWorld for Worlds {
+    fn from_world(world: &mut World) -> Self {
+        let seed = world.resource::<WorldSeed>().0;
+        let instances = (0..WORLD_COUNT)
+            .map(|id| {
+                let instance = WorldInstance {
+                    seed: seed ^ id.wrapping_mul(0x9E37_79B9_7F4A_7C15),
+                };
+                (id, instance)
+            })
+            .collect();
+
+        Self { instances }
+    }
+}
+
+pub fn join_worlds(
+    mut join_world_events: EventReader<ReceiveFromClient<JoinWorld>>,
+    mut world_joined_events: EventWriter<SendToClient<WorldJoined>>,
+    mut join_world_failed_events: EventWriter<SendToClient<JoinWorldFailed>>,
+    worlds: Res<Worlds>,
+    mapper: Res<ClientEntityMapper>,
+    mut players: Query<(&mut InWorld, &mut Transform)>,
+) {
+    for event in join_world_events.read() {
+        let world = event.message.world;
+        let Some((mut in_world, mut transform)) = mapper
+            .clients
+            .get(&event.client.raw())
+            .and_then(|entity| players.get_mut(*entity).ok())
+        else {
+            warn!("Client {} tried to join world {} without an entity", event.client, world);
+            continue;
+        };
+
+        let failure = if !worlds.contains(world) {
+            Some(JoinWorldFailure::UnknownWorld)
+        } else if in_world.0 == world {
+            Some(JoinWorldFailure::AlreadyInWorld)
+        } else {
+            None
+        };
+
+        if let Some(reason) = failure {
+            join_world_failed_events.send(SendToClient {
+                client: Some(event.client),
+                message: JoinWorldFailed { world, reason },
+            });
+            continue;
+        }
+
+        // Interest is worked out per world, so this is all it takes for the client to be sent
+        // despawns for the old world and spawns for the new one
+        info!("Client {} moved from world {} to {}", event.client, in_world.0, world);
+        in_world.0 = world;
+        transform.translation = Vec3::ZERO;
+
+        world_joined_events.send(SendToClient {
+            client: Some(event.client),
+            message: WorldJoined { world },
+        });
+    }
+}
@@ -0,0 +1,288 @@
+use bevy::{
+    ecs::{
+        entity::Entity,
+        event::EventWriter,
+        system::{Commands, Local, Query, Res, SystemParam},
+    },
+    log::debug,
+    time::{Fixed, Time},
+    transform::components::Transform,
+    utils::HashMap,
+};
+use common::{
+    components::DepositState,
+    materials::{MaterialManager, MaterialProperties},
+    network::events::{Mined, MiningFailed, MiningFailure, PlayerInput},
+    units::{Mass, UnitT},
+};
+
+use crate::{
+    economy::Inventory,
+    network::{ClientMapping, SendToClient},
+};
+
+use super::{Deposit, InWorld};
+
+const MINING_RANGE: f32 = 50.0;
+// Kilograms extracted per second from a material with a hardness of 1, softer materials are mined
+// proportionally faster
+const MINING_RATE: f32 = 400.0;
+
+fn mining_rate(deposit: &Deposit, material_manager: &MaterialManager) -> f32 {
+    match material_manager.get_material(deposit.instance.properties) {
+        Some(MaterialProperties::Compound(material)) => MINING_RATE / material.hardness.max(1.0),
+        _ => MINING_RATE,
+    }
+}
+
+// What a miner's client is told as it mines, or fails to
+#[derive(SystemParam)]
+pub struct MiningEventWriters<'w> {
+    mined: EventWriter<'w, SendToClient<Mined>>,
+    failed: EventWriter<'w, SendToClient<MiningFailed>>,
+}
+
+pub fn mine_deposits(
+    time: Res<Time<Fixed>>,
+    material_manager: Res<MaterialManager>,
+    mut commands: Commands,
+    mut miners: Query<(
+        Entity,
+        &PlayerInput,
+        &Transform,
+        &InWorld,
+        &mut Inventory,
+        &ClientMapping,
+    )>,
+    mut deposits: Query<(Entity, &mut Deposit, &mut DepositState, &Transform, &InWorld)>,
+    mut last_failures: Local<HashMap<Entity, MiningFailure>>,
+    mut mining_events: MiningEventWriters,
+) {
+    for (miner, input, miner_transform, miner_world, mut inventory, mapping) in &mut miners {
+        if input.primary == 0 {
+            last_failures.remove(&miner);
+            continue;
+        }
+
+        let nearest = deposits
+            .iter_mut()
+            .filter(|(_, _, _, _, world)| *world == miner_world)
+            .map(|(entity, deposit, state, transform, _)| {
+                let distance = transform.translation.distance(miner_transform.translation);
+                (entity, deposit, state, distance)
+            })
+            .filter(|(_, _, _, distance)| *distance <= MINING_RANGE)
+            .min_by(|(_, _, _, a), (_, _, _, b)| a.total_cmp(b));
+
+        let result = match nearest {
+            None => Err(MiningFailure::NoDepositInRange),
+            Some((_, deposit, _, _)) if deposit.remaining <= Mass::default() => {
+                Err(MiningFailure::DepositDepleted)
+            }
+            Some((entity, mut deposit, mut state, _)) => {
+                let rate = mining_rate(&deposit, &material_manager);
+                let requested = Mass::from_grams((rate * time.delta_seconds() * 1000.0) as UnitT);
+                let extracted = deposit.extract(requested);
+                let material = deposit.instance.properties;
+                inventory.add(material, extracted);
+
+                mining_events.mined.send(SendToClient {
+                    client: Some(mapping.id),
+                    message: Mined {
+                        deposit: entity,
+                        material,
+                        extracted,
+                        held: inventory.get(material),
+                        remaining: deposit.remaining,
+                    },
+                });
+
+                if deposit.remaining <= Mass::default() {
+                    debug!("Deposit {:?} was depleted by {:?}", entity, miner);
+                    commands.entity(entity).despawn();
+                } else {
+                    *state = deposit.state();
+                }
+                Ok(())
+            }
+        };
+
+        match result {
+            Ok(()) => {
+                last_failures.remove(&miner);
+            }
+            // Only tell the client once per failure rather than every tick the button is held
+            Err(reason) => {
+                if last_failures.insert(miner, reason) != Some(reason) {
+                    mining_events.failed.send(SendToClient {
+                        client: Some(mapping.id),
+                        message: MiningFailed { reason },
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{
+        app::{App, Update},
+        ecs::{entity::Entity, event::Events},
+        time::{Fixed, Time},
+        transform::components::Transform,
+    };
+    use bevy_renet::renet::ClientId;
+    use common::{
+        market::{Credits, Price},
+        materials::{
+            BasicMaterialProperties, ComposingMaterial, CompoundMaterialInstance,
+            CompoundMaterialProperties, MaterialManager, MaterialProperties, ThermalProperties,
+        },
+        network::events::{Mined, MiningFailed, MiningFailure, PlayerInput},
+        units::{Density, Energy, HeatCapacity, Mass, Temperature, UnitT, Volume},
+    };
+
+    use crate::{
+        economy::Inventory,
+        network::{ClientMapping, SendToClient},
+        world::{Deposit, InWorld},
+    };
+
+    use super::mine_deposits;
+
+    // Rock has a hardness of 2, so it comes out at 200kg a second, or 50kg a step
+    const STEP: Duration = Duration::from_millis(250);
+
+    fn mine() -> App {
+        let mut material_manager = MaterialManager::default();
+        material_manager.register_material(MaterialProperties::Basic(BasicMaterialProperties {
+            name: String::from("Ore"),
+            density: Density {
+                mass: Mass::from_kilograms(4_000),
+                volume: Volume::from_cubic_metres(1),
+            },
+            thermal_properties: ThermalProperties {
+                heat_capacity: HeatCapacity {
+                    energy: Energy::from_joules(800),
+                    mass: Mass::from_kilograms(1),
+                },
+                melting_point: Temperature::from_kelvin(1_800),
+                boiling_point: Temperature::from_kelvin(3_000),
+            },
+            base_value: Price::per_kilogram(Credits::new(10)),
+        }));
+        let ore = material_manager.get_material_id("Ore").unwrap();
+        material_manager.register_material(MaterialProperties::Compound(
+            CompoundMaterialProperties {
+                name: String::from("Rock"),
+                composition: vec![ComposingMaterial {
+                    id: ore,
+                    mean: 1.0,
+                    sd: 0.0,
+                }],
+                base_value: Price::per_kilogram(Credits::new(5)),
+                hardness: 2.0,
+            },
+        ));
+
+        let mut time = Time::<Fixed>::default();
+        time.advance_by(STEP);
+
+        let mut app = App::new();
+        app.insert_resource(time)
+            .insert_resource(material_manager)
+            .add_event::<SendToClient<Mined>>()
+            .add_event::<SendToClient<MiningFailed>>()
+            .add_systems(Update, mine_deposits);
+        app
+    }
+
+    fn deposit(app: &mut App, kilograms: UnitT, position: Transform, world: InWorld) -> Entity {
+        let material_manager = app.world.resource::<MaterialManager>();
+        let rock = material_manager.get_material_id("Rock").unwrap();
+        let instance = CompoundMaterialInstance::new(rock, vec![1.0], material_manager);
+        let deposit = Deposit::new(instance, Mass::from_kilograms(kilograms));
+        app.world
+            .spawn((deposit.state(), deposit, position, world))
+            .id()
+    }
+
+    fn miner(app: &mut App, position: Transform, world: InWorld) -> Entity {
+        let input = PlayerInput {
+            primary: 1,
+            ..Default::default()
+        };
+        let mapping = ClientMapping {
+            id: ClientId::from_raw(1),
+        };
+        app.world
+            .spawn((input, position, world, Inventory::default(), mapping))
+            .id()
+    }
+
+    // What each step mined, as (kilograms extracted, kilograms remaining)
+    fn step(app: &mut App) -> Vec<(UnitT, UnitT)> {
+        app.update();
+        app.world
+            .resource::<Events<SendToClient<Mined>>>()
+            .iter_current_update_events()
+            .map(|event| {
+                let message = &event.message;
+                (message.extracted.as_kilograms(), message.remaining.as_kilograms())
+            })
+            .collect()
+    }
+
+    fn failures(app: &App) -> Vec<MiningFailure> {
+        app.world
+            .resource::<Events<SendToClient<MiningFailed>>>()
+            .iter_current_update_events()
+            .map(|event| event.message.reason)
+            .collect()
+    }
+
+    #[test]
+    fn deposits_are_mined_at_the_rate_for_their_hardness_until_depleted() {
+        let mut app = mine();
+        let deposit = deposit(&mut app, 120, Transform::from_xyz(30.0, 0.0, 0.0), InWorld(0));
+        let miner = miner(&mut app, Transform::default(), InWorld(0));
+
+        assert_eq!(step(&mut app), vec![(50, 70)]);
+        assert_eq!(step(&mut app), vec![(50, 20)]);
+        assert_eq!(step(&mut app), vec![(20, 0)]);
+        assert!(app.world.get_entity(deposit).is_none());
+
+        let rock = app.world.resource::<MaterialManager>().get_material_id("Rock").unwrap();
+        let inventory = app.world.get::<Inventory>(miner).unwrap();
+        assert_eq!(inventory.get(rock), Mass::from_kilograms(120));
+
+        assert!(step(&mut app).is_empty());
+        assert_eq!(failures(&app), vec![MiningFailure::NoDepositInRange]);
+    }
+
+    #[test]
+    fn deposits_out_of_range_are_not_mined() {
+        let mut app = mine();
+        deposit(&mut app, 120, Transform::from_xyz(60.0, 0.0, 0.0), InWorld(0));
+        miner(&mut app, Transform::default(), InWorld(0));
+
+        assert!(step(&mut app).is_empty());
+        assert_eq!(failures(&app), vec![MiningFailure::NoDepositInRange]);
+        // Only the first failure is sent while the button stays held
+        step(&mut app);
+        assert!(failures(&app).is_empty());
+    }
+
+    #[test]
+    fn deposits_in_other_worlds_are_not_mined() {
+        let mut app = mine();
+        deposit(&mut app, 120, Transform::default(), InWorld(1));
+        miner(&mut app, Transform::default(), InWorld(0));
+
+        assert!(step(&mut app).is_empty());
+        assert_eq!(failures(&app), vec![MiningFailure::NoDepositInRange]);
+    }
+}
@@ -0,0 +1,8 @@
+mod deposit;
+mod instances;
+mod mining;
+mod world_plugin;
+
+pub use deposit::Deposit;
+pub use instances::InWorld;
+pub use world_plugin::{WorldPlugin, WorldSeed};
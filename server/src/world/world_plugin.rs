@@ -0,0 +1,117 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    app::{App, FixedUpdate, Plugin, Update},
+    ecs::{
+        schedule::{common_conditions::in_state, IntoSystemConfigs, OnEnter},
+        system::{Commands, Res, Resource},
+    },
+    log::{info, warn},
+    transform::components::Transform,
+};
+use common::{
+    materials::{load_materials, MaterialID, MaterialInstance, MaterialManager, MaterialProperties},
+    network::configuration::WorldID,
+    units::Mass,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{network::Replicate, ServerState};
+
+use super::{
+    instances::{join_worlds, InWorld, Worlds},
+    mining::mine_deposits,
+    Deposit,
+};
+
+const DEPOSIT_COUNT: usize = 32;
+const WORLD_RADIUS: f32 = 1500.0;
+const MIN_DEPOSIT_TONNES: i64 = 200;
+const MAX_DEPOSIT_TONNES: i64 = 2000;
+
+pub struct WorldPlugin;
+
+impl Plugin for WorldPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldSeed>()
+            .init_resource::<Worlds>()
+            .add_systems(
+                OnEnter(ServerState::GeneratingAssets),
+                generate_deposits.after(load_materials),
+            )
+            .add_systems(
+                FixedUpdate,
+                mine_deposits.run_if(in_state(ServerState::Running)),
+            )
+            .add_systems(Update, join_worlds.run_if(in_state(ServerState::Running)));
+    }
+}
+
+// Everything generated for the world is derived from this, so the same seed gives the same world
+#[derive(Resource)]
+pub struct WorldSeed(pub u64);
+
+impl Default for WorldSeed {
+    fn default() -> Self {
+        Self(rand::random())
+    }
+}
+
+fn generate_deposits(
+    mut commands: Commands,
+    worlds: Res<Worlds>,
+    material_manager: Res<MaterialManager>,
+) {
+    // Sorted so that the same seed picks the same ores regardless of hash map ordering
+    let mut ores: Vec<MaterialID> = material_manager
+        .materials()
+        .filter(|(_, properties)| matches!(properties, MaterialProperties::Compound(_)))
+        .map(|(id, _)| *id)
+        .collect();
+    ores.sort();
+
+    if ores.is_empty() {
+        warn!("No ores are defined so no deposits will be generated");
+        return;
+    }
+
+    for (world, instance) in worlds.iter() {
+        let mut random = StdRng::seed_from_u64(instance.seed);
+        for _ in 0..DEPOSIT_COUNT {
+            spawn_deposit(&mut commands, &material_manager, &ores, world, &mut random);
+        }
+
+        info!(
+            "Generated {} deposits in world {} from seed {}",
+            DEPOSIT_COUNT, world, instance.seed
+        );
+    }
+}
+
+fn spawn_deposit(
+    commands: &mut Commands,
+    material_manager: &MaterialManager,
+    ores: &[MaterialID],
+    world: WorldID,
+    random: &mut StdRng,
+) {
+    let material = ores[random.gen_range(0..ores.len())];
+    let Some(MaterialInstance::Compound(instance)) =
+        material_manager.generate_material_instance_with_rng(material, random)
+    else {
+        return;
+    };
+
+    let angle = random.gen_range(0.0..TAU);
+    let distance = WORLD_RADIUS * random.gen::<f32>().sqrt();
+    let total = Mass::from_tonnes(random.gen_range(MIN_DEPOSIT_TONNES..=MAX_DEPOSIT_TONNES));
+
+    let deposit = Deposit::new(instance, total);
+    commands.spawn((
+        deposit.state(),
+        deposit,
+        Transform::from_xyz(distance * angle.cos(), distance * angle.sin(), 0.0),
+        InWorld(world),
+        Replicate,
+    ));
+}